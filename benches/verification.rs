@@ -0,0 +1,128 @@
+// Compares checking the signitures of a long chain one after another with the parallel
+// SignatureVerifier. Run with `cargo bench`.
+
+use blockchain::{
+    block::Block,
+    blockchain::Blockchain,
+    chain_spec::ChainSpec,
+    clock::{Clock, ManualClock},
+    keygen,
+    signature::SignatureScheme,
+    transaction::{Transaction, TransactionKind},
+    verifier::SignatureVerifier,
+};
+use k256::{ecdsa, schnorr};
+use std::time::{Duration, Instant};
+
+const BLOCKS: u64 = 2000;
+const SENDERS: usize = 4; // of each signature scheme, every sender pays once per block
+
+enum Sender {
+    Ecdsa(ecdsa::SigningKey, String),
+    Schnorr(schnorr::SigningKey, String),
+}
+
+impl Sender {
+    fn address(&self) -> &str {
+        match self {
+            Sender::Ecdsa(_, address) | Sender::Schnorr(_, address) => address,
+        }
+    }
+
+    fn pay(&self, to: &str, nonce: u64) -> Transaction {
+        let transaction = Transaction::new(
+            Some(self.address().to_owned()),
+            to.to_owned(),
+            1,
+            TransactionKind::Normal,
+        )
+        .with_nonce(nonce);
+
+        match self {
+            Sender::Ecdsa(private_key, _) => {
+                let mut transaction = transaction;
+                transaction.sign_transaction(private_key).unwrap();
+                transaction
+            }
+            Sender::Schnorr(private_key, _) => {
+                let mut transaction = transaction.with_scheme(SignatureScheme::Schnorr);
+                transaction.sign_transaction_schnorr(private_key).unwrap();
+                transaction
+            }
+        }
+    }
+}
+
+fn time(name: &str, run: impl FnOnce() -> bool) -> Duration {
+    let start = Instant::now();
+    assert!(run(), "{} rejected a valid chain", name);
+    let elapsed = start.elapsed();
+    println!("{:<32} {:>10.2?}", name, elapsed);
+
+    elapsed
+}
+
+fn main() {
+    let senders: Vec<_> = (0..SENDERS)
+        .flat_map(|_| {
+            let (ecdsa_key, ecdsa_public) = keygen::gen_key_pair();
+            let (schnorr_key, schnorr_public) = keygen::gen_schnorr_key_pair();
+            [
+                Sender::Ecdsa(ecdsa_key, keygen::address(&ecdsa_public)),
+                Sender::Schnorr(schnorr_key, keygen::schnorr_address(&schnorr_public)),
+            ]
+        })
+        .collect();
+
+    let spec = ChainSpec {
+        difficulty: 0,
+        allocations: senders
+            .iter()
+            .map(|sender| (sender.address().to_owned(), BLOCKS))
+            .collect(),
+        ..ChainSpec::default()
+    };
+    let clock = ManualClock::new(spec.genesis_timestamp);
+    let mut chain = Blockchain::new(spec).with_clock(clock.clone());
+
+    println!(
+        "building a chain of {} blocks with {} transactions each",
+        BLOCKS,
+        senders.len()
+    );
+    for height in 1..=BLOCKS {
+        clock.advance(10);
+
+        let mut transactions =
+            vec![
+                Transaction::new(None, "miner".into(), 0, TransactionKind::Normal)
+                    .with_nonce(height),
+            ];
+        for (i, sender) in senders.iter().enumerate() {
+            let to = senders[(i + 1) % senders.len()].address();
+            transactions.push(sender.pay(to, height - 1));
+        }
+
+        let block = Block::new(clock.now(), transactions, chain.latest_block().hash());
+        chain.import_block(block).unwrap();
+    }
+
+    let sequential = time("one by one", || {
+        chain
+            .blocks()
+            .iter()
+            .flat_map(|block| block.transactions())
+            .all(|transaction| transaction.valid())
+    });
+    let parallel = time("SignatureVerifier::verify_blocks", || {
+        SignatureVerifier::new()
+            .verify_blocks(&chain.blocks()[1..])
+            .is_ok()
+    });
+    time("Blockchain::valid", || chain.valid());
+
+    println!(
+        "speedup {:.1}x",
+        sequential.as_secs_f64() / parallel.as_secs_f64()
+    );
+}
@@ -0,0 +1,118 @@
+use crate::{
+    asset::AssetId,
+    block::Hash,
+    encoding::{Decode, DecodeError, Encode, Reader},
+    sparse_merkle::Proof,
+};
+use sha2::Digest;
+use std::collections::BTreeMap;
+
+// What the state holds for an address. The accounts are the leaves of a sparse merkle tree
+// keyed by the hash of their address, and the state root commits to its root.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Account {
+    pub balances: BTreeMap<Option<AssetId>, u64>, // none is the coin of the chain
+    pub nonce: u64,
+    pub stake: u64,
+    pub debts: BTreeMap<(String, Option<AssetId>), u64>, // what it owes each lender by asset
+}
+
+impl Account {
+    // where the account of an address is in the tree
+    pub fn key(address: &str) -> Hash {
+        sha2::Sha256::digest(address.as_bytes()).into()
+    }
+
+    pub fn hash(&self) -> Hash {
+        sha2::Sha256::digest(self.to_bytes()).into()
+    }
+
+    pub fn balance(&self, asset: Option<AssetId>) -> u64 {
+        self.balances.get(&asset).copied().unwrap_or_default()
+    }
+
+    // an account that holds and owes nothing and never sent a transaction is not in the tree
+    pub fn is_empty(&self) -> bool {
+        self == &Self::default()
+    }
+}
+
+impl Encode for Account {
+    fn encode(&self, out: &mut Vec<u8>) {
+        (self.balances.len() as u32).encode(out);
+        for (asset, balance) in &self.balances {
+            asset.as_ref().encode(out);
+            balance.encode(out);
+        }
+        self.nonce.encode(out);
+        self.stake.encode(out);
+        (self.debts.len() as u32).encode(out);
+        for ((lender, asset), debt) in &self.debts {
+            lender.encode(out);
+            asset.as_ref().encode(out);
+            debt.encode(out);
+        }
+    }
+}
+
+impl Decode for Account {
+    fn decode(reader: &mut Reader) -> Result<Self, DecodeError> {
+        let balances =
+            reader.list_with(|reader| Ok((reader.option(Reader::hash)?, reader.u64()?)))?;
+        let nonce = reader.u64()?;
+        let stake = reader.u64()?;
+        let debts = reader.list_with(|reader| {
+            let key = (reader.string()?, reader.option(Reader::hash)?);
+            Ok((key, reader.u64()?))
+        })?;
+
+        Ok(Self {
+            balances: balances.into_iter().collect(),
+            nonce,
+            stake,
+            debts: debts.into_iter().collect(),
+        })
+    }
+}
+
+// the state root commits to the accounts and, through a hash, to the rest of the state
+pub fn state_root(accounts_root: &Hash, rest: &Hash) -> Hash {
+    sha2::Sha256::digest([&accounts_root[..], rest].concat()).into()
+}
+
+// Proves the account of an address in a state root, or that the address has none
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AccountProof {
+    pub account: Option<Account>,
+    pub proof: Proof,
+    pub rest: Hash, // the hash of the state other than the accounts
+}
+
+impl AccountProof {
+    pub fn verify(&self, state_root: &Hash, address: &str) -> bool {
+        let value = self.account.as_ref().map(Account::hash);
+        self.proof
+            .root(&Account::key(address), value.as_ref())
+            .is_some_and(|accounts_root| {
+                self::state_root(&accounts_root, &self.rest) == *state_root
+            })
+    }
+}
+
+impl Encode for AccountProof {
+    fn encode(&self, out: &mut Vec<u8>) {
+        self.account.as_ref().encode(out);
+        self.proof.encode(out);
+        self.rest.encode(out);
+    }
+}
+
+impl Decode for AccountProof {
+    fn decode(reader: &mut Reader) -> Result<Self, DecodeError> {
+        Ok(Self {
+            account: reader.option(Account::decode)?,
+            proof: Proof::decode(reader)?,
+            rest: reader.hash()?,
+        })
+    }
+}
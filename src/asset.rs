@@ -0,0 +1,83 @@
+use crate::{
+    block::Hash,
+    encoding::{Decode, DecodeError, Encode, Reader},
+    transaction::{Transaction, TransactionKind},
+};
+
+pub const MAX_NAME: usize = 32; // how long the name of an asset may be
+pub const MAX_DECIMALS: u8 = 18;
+
+// an asset is identified by the hash of the transaction that issued it
+pub type AssetId = Hash;
+
+// A token issued by a user. Its whole supply is created when it is issued and amounts are in
+// its smallest unit, the decimals only say how they are shown.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Asset {
+    pub id: AssetId,
+    pub name: String,
+    pub issuer: String,
+    pub supply: u64,
+    pub decimals: u8,
+}
+
+impl Asset {
+    // the asset a transaction issues if it is an issuance
+    pub fn of(transaction: &Transaction) -> Option<Self> {
+        let TransactionKind::Issue { name, decimals } = transaction.kind() else {
+            return None;
+        };
+
+        Some(Self {
+            id: transaction.hash(),
+            name: name.clone(),
+            issuer: transaction.from().clone()?,
+            supply: transaction.amount(),
+            decimals: *decimals,
+        })
+    }
+
+    // the name and decimals have to fit in a block explorer
+    pub fn valid(&self) -> bool {
+        !self.name.is_empty() && self.name.len() <= MAX_NAME && self.decimals <= MAX_DECIMALS
+    }
+
+    // shows an amount in whole units, 1050 with 2 decimals is 10.50
+    pub fn format(&self, amount: u64) -> String {
+        let decimals = self.decimals as u32;
+        if decimals == 0 {
+            return format!("{} {}", amount, self.name);
+        }
+
+        let unit = 10u128.pow(decimals);
+        format!(
+            "{}.{:0width$} {}",
+            amount as u128 / unit,
+            amount as u128 % unit,
+            self.name,
+            width = decimals as usize
+        )
+    }
+}
+
+impl Encode for Asset {
+    fn encode(&self, out: &mut Vec<u8>) {
+        self.id.encode(out);
+        self.name.encode(out);
+        self.issuer.encode(out);
+        self.supply.encode(out);
+        self.decimals.encode(out);
+    }
+}
+
+impl Decode for Asset {
+    fn decode(reader: &mut Reader) -> Result<Self, DecodeError> {
+        Ok(Self {
+            id: reader.hash()?,
+            name: reader.string()?,
+            issuer: reader.string()?,
+            supply: reader.u64()?,
+            decimals: reader.u8()?,
+        })
+    }
+}
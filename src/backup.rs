@@ -0,0 +1,220 @@
+use crate::{
+    block::{Block, Hash},
+    blockchain::{Blockchain, BlockchainError},
+    encoding::{encode_list, hex, parse_hex, Decode, DecodeError, Encode, Reader},
+};
+use serde::{Deserialize, Serialize};
+use std::{fs, path::Path};
+
+const MAGIC: &[u8] = b"BLKS"; // how a binary backup starts, followed by the version
+const VERSION: u8 = 1;
+
+#[derive(Debug)]
+pub enum BackupError {
+    Io(std::io::Error),
+    Json(serde_json::Error),
+    Decode(DecodeError),
+    UnknownVersion(u8),
+    InvalidHex,
+    InvalidHash(u64), // a block in a json backup does not have the hash it is listed with
+    ForeignChain,     // the backup is of a chain with another id or genesis
+    InvalidRange,
+    Pruned(u64),        // the block at the height only has its header
+    Conflict(u64),      // the chain has another block at the height
+    MissingBlocks(u64), // the backup starts above the height the chain needs next
+    InvalidBlock(u64, BlockchainError),
+    Blockchain(BlockchainError),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Json,   // readable, each block is kept in its canonical encoding as hex
+    Binary, // the canonical encoding of the backup after a magic and a version
+}
+
+// A range of consecutive blocks of a chain that can be moved to another node. It names the
+// chain it came from so it is not imported into another one.
+#[derive(Clone)]
+pub struct Backup {
+    pub chain_id: u64,
+    pub genesis: Hash,
+    pub start: u64, // the height of the first block
+    pub blocks: Vec<Block>,
+}
+
+impl Backup {
+    // the blocks from start to end, both included. The end is capped at the latest block and
+    // blocks that were pruned cannot be exported.
+    pub fn export(chain: &Blockchain, start: u64, end: u64) -> Result<Self, BackupError> {
+        let end = end.min(chain.blocks().len() as u64 - 1);
+        if start > end {
+            return Err(BackupError::InvalidRange);
+        }
+
+        let blocks = (start..=end)
+            .map(|height| match chain.block(height) {
+                Ok(Some(block)) if !block.is_pruned() => Ok(block),
+                Ok(_) => Err(BackupError::Pruned(height)),
+                Err(error) => Err(BackupError::Blockchain(error)),
+            })
+            .collect::<Result<_, _>>()?;
+        let (chain_id, genesis) = chain.handshake();
+
+        Ok(Self {
+            chain_id,
+            genesis,
+            start,
+            blocks,
+        })
+    }
+
+    // the blocks with their heights
+    pub fn blocks(&self) -> impl Iterator<Item = (u64, &Block)> {
+        (self.start..).zip(&self.blocks)
+    }
+
+    // Imports the blocks the chain does not have yet, each is fully validated as if it came
+    // from a peer. The blocks the chain already has have to be the same. Returns how many
+    // blocks were imported. The whole backup is tried on a fork first so the chain is left as
+    // it was if any block is rejected.
+    pub fn import(&self, chain: &mut Blockchain) -> Result<u64, BackupError> {
+        self.verify(chain)?;
+        self.import_blocks(chain)
+    }
+
+    // checks that the backup would import into the chain without importing it, returns how
+    // many blocks it would add
+    pub fn verify(&self, chain: &Blockchain) -> Result<u64, BackupError> {
+        let mut fork = chain.fork().map_err(BackupError::Blockchain)?;
+        self.import_blocks(&mut fork)
+    }
+
+    // imports the blocks one by one, the ones before a block that was rejected stay imported
+    fn import_blocks(&self, chain: &mut Blockchain) -> Result<u64, BackupError> {
+        if (self.chain_id, self.genesis) != chain.handshake() {
+            return Err(BackupError::ForeignChain);
+        }
+
+        let mut imported = 0;
+        for (height, block) in self.blocks() {
+            let next = chain.blocks().len() as u64;
+            if height < next {
+                if chain.blocks()[height as usize].hash() != block.hash() {
+                    return Err(BackupError::Conflict(height));
+                }
+            } else if height == next {
+                chain
+                    .import_block(block.clone())
+                    .map_err(|error| BackupError::InvalidBlock(height, error))?;
+                imported += 1;
+            } else {
+                return Err(BackupError::MissingBlocks(next));
+            }
+        }
+
+        Ok(imported)
+    }
+
+    pub fn to_json(&self) -> Result<String, BackupError> {
+        let json = JsonBackup {
+            chain_id: self.chain_id,
+            genesis: hex(&self.genesis),
+            start: self.start,
+            blocks: self
+                .blocks()
+                .map(|(height, block)| JsonBlock {
+                    height,
+                    hash: hex(&block.hash()),
+                    timestamp: block.timestamp(),
+                    transactions: block.transactions().len(),
+                    bytes: hex(&block.to_bytes()),
+                })
+                .collect(),
+        };
+
+        serde_json::to_string_pretty(&json).map_err(BackupError::Json)
+    }
+
+    // the listed hashes and heights have to match the blocks, the rest is only for people
+    // reading the file
+    pub fn from_json(json: &str) -> Result<Self, BackupError> {
+        let json: JsonBackup = serde_json::from_str(json).map_err(BackupError::Json)?;
+        let genesis = parse_hex(&json.genesis)
+            .and_then(|genesis| genesis.try_into().ok())
+            .ok_or(BackupError::InvalidHex)?;
+
+        let mut blocks = Vec::new();
+        for (height, block) in (json.start..).zip(&json.blocks) {
+            let bytes = parse_hex(&block.bytes).ok_or(BackupError::InvalidHex)?;
+            let decoded = Block::from_bytes(&bytes).map_err(BackupError::Decode)?;
+            if block.height != height || hex(&decoded.hash()) != block.hash {
+                return Err(BackupError::InvalidHash(height));
+            }
+            blocks.push(decoded);
+        }
+
+        Ok(Self {
+            chain_id: json.chain_id,
+            genesis,
+            start: json.start,
+            blocks,
+        })
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>, format: Format) -> Result<(), BackupError> {
+        let contents = match format {
+            Format::Json => self.to_json()?.into_bytes(),
+            Format::Binary => [MAGIC, &[VERSION], &self.to_bytes()].concat(),
+        };
+
+        fs::write(path, contents).map_err(BackupError::Io)
+    }
+
+    // reads a backup in either format
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, BackupError> {
+        let contents = fs::read(path).map_err(BackupError::Io)?;
+        match contents.strip_prefix(MAGIC) {
+            Some([VERSION, rest @ ..]) => Self::from_bytes(rest).map_err(BackupError::Decode),
+            Some([version, ..]) => Err(BackupError::UnknownVersion(*version)),
+            Some([]) => Err(BackupError::Decode(DecodeError::UnexpectedEnd)),
+            None => Self::from_json(&String::from_utf8_lossy(&contents)),
+        }
+    }
+}
+
+impl Encode for Backup {
+    fn encode(&self, out: &mut Vec<u8>) {
+        self.chain_id.encode(out);
+        self.genesis.encode(out);
+        self.start.encode(out);
+        encode_list(&self.blocks, out);
+    }
+}
+
+impl Decode for Backup {
+    fn decode(reader: &mut Reader) -> Result<Self, DecodeError> {
+        Ok(Self {
+            chain_id: reader.u64()?,
+            genesis: reader.hash()?,
+            start: reader.u64()?,
+            blocks: reader.list()?,
+        })
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct JsonBackup {
+    chain_id: u64,
+    genesis: String,
+    start: u64,
+    blocks: Vec<JsonBlock>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct JsonBlock {
+    height: u64,
+    hash: String,
+    timestamp: u64,
+    transactions: usize,
+    bytes: String, // the canonical encoding of the block
+}
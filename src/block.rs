@@ -1,87 +1,265 @@
+use k256::{
+    ecdsa::{Signature, SigningKey, VerifyingKey},
+    schnorr::signature::{Signer, Verifier},
+};
 use sha2::Digest;
-use std::time::SystemTime;
 
-use crate::transaction::Transaction;
-
-const DIFFICULTY: usize = 2; // how many bytes need to be -
-const DIFFICULTY_TEST: [u8; DIFFICULTY] = [0; DIFFICULTY];
+use crate::{
+    encoding::{encode_list, Decode, DecodeError, Encode, Reader},
+    keygen,
+    merkle::{self, Sibling},
+    transaction::Transaction,
+    verifier::SignatureVerifier,
+};
 
 // alias for the size of a hash
 pub type Hash = [u8; 32];
 
+// Why a block was rejected
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BlockError {
+    UnknownParent,
+    InvalidSeal,
+    TimestampTooOld,
+    TimestampInFuture,
+    MissingCoinbase,
+    InvalidCoinbase,
+    ExtraCoinbase(Hash),
+    InvalidTransaction(Hash),
+    InvalidNonce(Hash),
+    UnsignedLoan(Hash),
+    Overpayment(Hash),
+    Overdraft(Hash),
+    ForeignStake(Hash),    // a stake whose receiver is not the sender
+    Premature(Hash),       // the lock of the transaction has not expired yet
+    UnsignedRelease(Hash), // a release by the buyer the seller has not signed
+    InvalidEscrow(Hash),   // a release or refund that does not match an open escrow
+    InvalidAsset(Hash),    // an asset that was never issued or can not be used this way
+    Unsupported(Hash),     // a transaction the ledger model of the chain does not allow
+    DoubleSpend(Hash),     // an input that was already spent or never existed
+    ForeignInput(Hash),    // an input that does not belong to the sender
+    InvalidOutputs(Hash),  // outputs that do not start with the payment or do not add up
+    InvalidStateRoot,      // the state after the block does not have the root in its header
+}
+
+// What a block commits to without its transactions, which is all a light client downloads
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Header {
+    pub prev_hash: Hash,
+    pub timestamp: u64,
+    pub nonce: u64,
+    pub transactions_root: Hash,
+    pub state_root: Hash,
+    pub sealer: Option<String>,
+    pub signiture: Vec<u8>,
+}
+
+impl Header {
+    // the hash of the block the header belongs to
+    pub fn hash(&self) -> Hash {
+        let mut bytes = Vec::new();
+        self.prev_hash.encode(&mut bytes);
+        self.timestamp.encode(&mut bytes);
+        self.nonce.encode(&mut bytes);
+        self.transactions_root.encode(&mut bytes);
+        self.state_root.encode(&mut bytes);
+        self.sealer.as_deref().encode(&mut bytes);
+
+        sha2::Sha256::digest(bytes).into()
+    }
+
+    pub fn valid_signiture(&self) -> bool {
+        valid_seal(self.sealer.as_deref(), &self.hash(), &self.signiture)
+    }
+}
+
+impl Encode for Header {
+    fn encode(&self, out: &mut Vec<u8>) {
+        self.prev_hash.encode(out);
+        self.timestamp.encode(out);
+        self.nonce.encode(out);
+        self.transactions_root.encode(out);
+        self.state_root.encode(out);
+        self.sealer.as_deref().encode(out);
+        self.signiture.encode(out);
+    }
+}
+
+impl Decode for Header {
+    fn decode(reader: &mut Reader) -> Result<Self, DecodeError> {
+        Ok(Self {
+            prev_hash: reader.hash()?,
+            timestamp: reader.u64()?,
+            nonce: reader.u64()?,
+            transactions_root: reader.hash()?,
+            state_root: reader.hash()?,
+            sealer: reader.option(Reader::string)?,
+            signiture: reader.bytes()?,
+        })
+    }
+}
+
+// checks that a hash was signed by the sealer
+fn valid_seal(sealer: Option<&str>, hash: &Hash, signiture: &[u8]) -> bool {
+    let Some(sealer) = sealer else {
+        return false;
+    };
+
+    let Ok(public_key) = serde_json::from_str::<VerifyingKey>(sealer) else {
+        return false;
+    };
+    let Ok(signiture) = Signature::from_slice(signiture) else {
+        return false;
+    };
+
+    public_key.verify(hash, &signiture).is_ok()
+}
+
 // A block of a blockchain
+#[derive(Debug, Clone)]
 pub struct Block {
     timestamp: u64,
     transactions: Vec<Transaction>,
+    transactions_root: Hash, // the merkle root of the ids of the transactions
+    state_root: Hash,        // the root of the state after the block, see ChainState::root
     prev_hash: Hash,
     hash: Hash, // as the hash may not be calculated yet
     nonce: u64,
+    sealer: Option<String>, // the validator that signed the block if the engine requires one
+    signiture: Vec<u8>,
+    pruned: bool, // the transactions were dropped, the root still commits to them
 }
 
 impl Block {
-    // creates a new block without the prev_hash
-    pub fn new(transactions: impl Into<Vec<Transaction>>, prev_hash: Hash) -> Self {
+    // creates a new unsealed block, the consensus engine is responsible for sealing it
+    pub fn new(timestamp: u64, transactions: impl Into<Vec<Transaction>>, prev_hash: Hash) -> Self {
         let transactions = transactions.into();
-        let timestamp = SystemTime::now()
-            .duration_since(SystemTime::UNIX_EPOCH)
-            .expect("Something went wrong getting current time")
-            .as_secs();
-
-        // mining the block and getting the nonce
-        let mut nonce = 0;
-        let hash = loop {
-            let hash = Self::calculate_hash(timestamp, nonce, &transactions, &prev_hash);
-            if hash[..DIFFICULTY] == DIFFICULTY_TEST {
+        let transactions_root = Self::calculate_root(&transactions);
+
+        let mut block = Self {
+            timestamp,
+            transactions,
+            transactions_root,
+            state_root: [0; 32],
+            prev_hash,
+            hash: [0; 32],
+            nonce: 0,
+            sealer: None,
+            signiture: Vec::new(),
+            pruned: false,
+        };
+        block.hash = block.calculate_hash();
+
+        block
+    }
+
+    // commits the block to the state after it, which has to be set before it is sealed
+    pub fn with_state_root(mut self, state_root: Hash) -> Self {
+        self.state_root = state_root;
+        self.hash = self.calculate_hash();
+        self
+    }
+
+    // a block without its transactions, which is what is left of it once it is pruned
+    pub fn from_header(header: Header) -> Self {
+        let mut block = Self {
+            timestamp: header.timestamp,
+            transactions: Vec::new(),
+            transactions_root: header.transactions_root,
+            state_root: header.state_root,
+            prev_hash: header.prev_hash,
+            hash: [0; 32],
+            nonce: header.nonce,
+            sealer: header.sealer,
+            signiture: header.signiture,
+            pruned: true,
+        };
+        block.hash = block.calculate_hash();
+
+        block
+    }
+
+    // drops the transactions to save space once the state no longer needs them
+    pub fn prune(&mut self) {
+        self.transactions = Vec::new();
+        self.pruned = true;
+    }
+
+    pub fn is_pruned(&self) -> bool {
+        self.pruned
+    }
+
+    // mining the block by searching for a nonce that gives a hash with enough leading zeros
+    pub fn mine(&mut self, difficulty: usize) {
+        self.nonce = 0;
+        self.hash = loop {
+            let hash = self.calculate_hash();
+            if hash[..difficulty].iter().all(|byte| *byte == 0) {
                 break hash;
             }
 
-            nonce += 1;
+            self.nonce += 1;
         };
+    }
 
-        Self {
-            timestamp,
-            transactions,
-            prev_hash,
-            hash,
-            nonce,
-        }
+    // signs the hash of the block with the key of the validator, the validator is part of
+    // the header so the hash changes
+    pub fn sign(&mut self, private_key: &SigningKey) {
+        self.sealer = Some(keygen::address(&VerifyingKey::from(private_key)));
+        self.hash = self.calculate_hash();
+
+        let signiture: Signature = private_key.sign(&self.hash);
+        self.signiture = signiture.to_bytes().to_vec();
+    }
+
+    // checks that the block was signed by its sealer
+    pub fn valid_signiture(&self) -> bool {
+        valid_seal(self.sealer.as_deref(), &self.hash, &self.signiture)
+    }
+
+    // checks that the stored hash matches the contents of the block
+    pub fn valid_hash(&self) -> bool {
+        self.transactions_root == Self::calculate_root(&self.transactions)
+            && self.hash == self.calculate_hash()
     }
 
     pub fn valid_transactions(&self) -> bool {
-        for transaction in &self.transactions {
-            if !transaction.valid() {
-                return false;
-            }
-        }
+        self.verify_signitures().is_ok()
+    }
 
-        true
+    // checks the signiture of every transaction, see SignatureVerifier to keep the parsed keys
+    // between blocks
+    pub fn verify_signitures(&self) -> Result<(), BlockError> {
+        SignatureVerifier::new().verify_block(self)
+    }
+
+    // the header is everything the hash covers, the transactions are covered by their root
+    fn encode_header(&self, out: &mut Vec<u8>) {
+        self.prev_hash.encode(out);
+        self.timestamp.encode(out);
+        self.nonce.encode(out);
+        self.transactions_root.encode(out);
+        self.state_root.encode(out);
+        self.sealer.as_deref().encode(out);
     }
 
     // the function to calculate a hash
-    pub fn calculate_hash(
-        timestamp: u64,
-        nonce: u64,
-        transactions: &[Transaction],
-        prev_hash: &[u8],
-    ) -> Hash {
-        // this code nees to be fixed later
-        let bytes: Vec<_> = [
-            prev_hash,
-            &timestamp.to_be_bytes(),
-            &nonce.to_be_bytes(),
-            &transactions
-                .iter()
-                .flat_map(|transaction| transaction.hash())
-                .collect::<Vec<u8>>(),
-        ]
-        .into_iter()
-        .flatten()
-        .copied()
-        .collect();
+    pub fn calculate_hash(&self) -> Hash {
+        let mut bytes = Vec::new();
+        self.encode_header(&mut bytes);
 
         sha2::Sha256::digest(bytes).into()
     }
 
+    fn calculate_root(transactions: &[Transaction]) -> Hash {
+        let ids: Vec<_> = transactions
+            .iter()
+            .map(|transaction| transaction.id())
+            .collect();
+
+        merkle::root(&ids)
+    }
+
     // gets the previous hash
     pub fn hash(&self) -> Hash {
         self.hash
@@ -103,4 +281,73 @@ impl Block {
     pub fn timestamp(&self) -> u64 {
         self.timestamp
     }
+
+    pub fn sealer(&self) -> Option<&str> {
+        self.sealer.as_deref()
+    }
+
+    pub fn transactions_root(&self) -> Hash {
+        self.transactions_root
+    }
+
+    pub fn state_root(&self) -> Hash {
+        self.state_root
+    }
+
+    pub fn header(&self) -> Header {
+        Header {
+            prev_hash: self.prev_hash,
+            timestamp: self.timestamp,
+            nonce: self.nonce,
+            transactions_root: self.transactions_root,
+            state_root: self.state_root,
+            sealer: self.sealer.clone(),
+            signiture: self.signiture.clone(),
+        }
+    }
+
+    // proves that the transaction at the index is in the block, see merkle::verify
+    pub fn proof(&self, index: usize) -> Option<Vec<Sibling>> {
+        let ids: Vec<_> = self
+            .transactions
+            .iter()
+            .map(|transaction| transaction.id())
+            .collect();
+
+        merkle::proof(&ids, index)
+    }
+}
+
+impl Encode for Block {
+    fn encode(&self, out: &mut Vec<u8>) {
+        self.encode_header(out);
+        self.signiture.encode(out);
+        encode_list(&self.transactions, out);
+    }
+}
+
+impl Decode for Block {
+    fn decode(reader: &mut Reader) -> Result<Self, DecodeError> {
+        let prev_hash = reader.hash()?;
+        let timestamp = reader.u64()?;
+        let nonce = reader.u64()?;
+        let transactions_root = reader.hash()?;
+        let state_root = reader.hash()?;
+        let sealer = reader.option(Reader::string)?;
+        let signiture = reader.bytes()?;
+        let transactions = reader.list()?;
+
+        let mut block = Self::new(timestamp, transactions, prev_hash);
+        if block.transactions_root != transactions_root {
+            return Err(DecodeError::RootMismatch);
+        }
+
+        block.state_root = state_root;
+        block.nonce = nonce;
+        block.sealer = sealer;
+        block.signiture = signiture;
+        block.hash = block.calculate_hash();
+
+        Ok(block)
+    }
 }
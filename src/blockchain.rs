@@ -1,11 +1,33 @@
 use crate::{
-    block::Block,
-    transaction::{self, Transaction, TransactionKind},
+    account::AccountProof,
+    asset::{Asset, AssetId},
+    block::{Block, BlockError, Hash, Header},
+    chain_spec::{ChainSpec, ConsensusSpec, Ledger},
+    clock::{Clock, SystemClock},
+    consensus::{Consensus, ConsensusError},
+    escrow::Escrow,
+    events::{Event, EventBus, Eviction},
+    keygen,
+    light::{Request, Response, TransactionProof, MAX_HEADERS},
+    mempool::{Mempool, MempoolError},
+    monetary::MonetaryPolicy,
+    snapshot::Snapshot,
+    state::ChainState,
+    storage::{recover, Batch, Location, MemoryStorage, Storage, StorageError, Write},
+    transaction::{Transaction, TransactionKind},
+    utxo::{OutPoint, Output},
+    verifier::SignatureVerifier,
 };
 use indexmap::IndexMap;
-use k256::ecdsa::SigningKey;
+use k256::ecdsa::{SigningKey, VerifyingKey};
+use std::{
+    collections::{BTreeMap, HashSet},
+    sync::{mpsc::Receiver, Arc},
+};
 
-const REWARD: u64 = 1000; // for now just constant
+pub(crate) const MAX_FUTURE_DRIFT: u64 = 2 * 60 * 60; // how far ahead of our clock a block may be
+const MEDIAN_TIME_SPAN: usize = 11; // how many blocks the median time is taken over
+const MEMORY_DEPTH: u64 = 100; // how many of the latest blocks keep their transactions in memory
 
 #[derive(Debug)]
 pub enum BlockchainError {
@@ -14,29 +36,415 @@ pub enum BlockchainError {
     BalanceTooSmall,
     InvalidSigner,
     NoTransactionFound,
+    Consensus(ConsensusError),
+    ForeignChain,
+    InvalidBlock(BlockError),
+    Mempool(MempoolError),
+    DoubleSpend, // an input that is spent on the chain or by another pending transaction
+    NotUtxo,     // the chain does not use the UTXO model
+    InvalidSnapshot, // a snapshot that does not match the headers it came with
+    StaleNonce,  // a nonce the sender already used on the chain
+    InvalidReorg, // a branch that does not replace blocks above the window with more blocks
+    Storage(StorageError),
 }
 
 // The actual blockchain
 pub struct Blockchain {
-    blocks: Vec<Block>,
-    mempool: Vec<Transaction>,
+    blocks: Vec<Block>, // the blocks up to the window only have their headers in memory
+    storage: Box<dyn Storage>, // where the blocks are kept and looked up
+    state: ChainState,  // the state after the latest block
+    base: Snapshot,     // the state the blocks that were not pruned are applied to
+    window: Snapshot,   // the state the blocks kept whole in memory are applied to
+    pruning: Option<u64>, // how many blocks below the latest keep their transactions
+    memory: u64,        // how many blocks below the latest keep their transactions in memory
+    snapshot_interval: Option<u64>, // how many blocks apart snapshots are taken
+    snapshot: Option<Snapshot>, // the latest snapshot that was taken
+    mempool: Mempool,
+    consensus: Box<dyn Consensus>,
+    clock: Arc<dyn Clock>,
+    policy: MonetaryPolicy,
+    spec: ChainSpec,
+    verifier: SignatureVerifier,
+    events: EventBus,
 }
 
 impl Default for Blockchain {
     fn default() -> Self {
-        Self {
-            blocks: vec![Block::new(Vec::new(), [0; 32])], // creating the genesis block
-            mempool: Vec::new(),
-        }
+        Self::new(ChainSpec::default())
     }
 }
 
 impl Blockchain {
-    // get a reference to the vec of blocks
+    // creates a chain using the engine described in the spec
+    pub fn new(spec: ChainSpec) -> Self {
+        let consensus = spec.consensus();
+        Self::from_parts(spec, consensus)
+    }
+
+    // creates a chain with an engine that is not part of the spec. The engine is rebuilt from
+    // its checkpoint once blocks leave the window, so it has to be one a spec can describe.
+    pub fn with_consensus(spec: ChainSpec, consensus: impl Consensus + 'static) -> Self {
+        Self::from_parts(spec, Box::new(consensus))
+    }
+
+    fn from_parts(spec: ChainSpec, consensus: Box<dyn Consensus>) -> Self {
+        let genesis = spec.genesis(); // creating the genesis block
+        let state = ChainState::genesis(&genesis, spec.ledger);
+        let base = Snapshot {
+            height: 0,
+            hash: genesis.hash(),
+            consensus: consensus.checkpoint(std::slice::from_ref(&genesis)),
+            state: state.clone(),
+        };
+        let mut storage = MemoryStorage::new();
+        storage
+            .put_block(0, &genesis)
+            .expect("The genesis should be stored");
+
+        Self {
+            blocks: vec![genesis],
+            storage: Box::new(storage),
+            state,
+            window: base.clone(),
+            base,
+            pruning: None,
+            memory: MEMORY_DEPTH,
+            snapshot_interval: None,
+            snapshot: None,
+            mempool: Mempool::default(),
+            consensus,
+            clock: Arc::new(SystemClock),
+            policy: spec.monetary_policy(),
+            spec,
+            verifier: SignatureVerifier::new(),
+            events: EventBus::default(),
+        }
+    }
+
+    // replaces the clock used to timestamp and check blocks
+    pub fn with_clock(mut self, clock: impl Clock + 'static) -> Self {
+        self.clock = Arc::new(clock);
+        self
+    }
+
+    // replaces the mempool, for example to change its limits
+    pub fn with_mempool(mut self, mempool: Mempool) -> Self {
+        self.mempool = mempool;
+        self
+    }
+
+    // drops the transactions of blocks more than depth below the latest block from the next
+    // block on. The engine is rebuilt from its checkpoint so it has to be one a spec can
+    // describe.
+    pub fn with_pruning(mut self, depth: u64) -> Self {
+        self.pruning = Some(depth);
+        self
+    }
+
+    // keeps the transactions of only the blocks up to depth below the latest block in memory,
+    // the older ones are read from the storage. Reorgs cannot go deeper than that.
+    pub fn with_memory(mut self, depth: u64) -> Self {
+        self.memory = depth.max(1);
+        self
+    }
+
+    // takes a snapshot every interval blocks for other nodes to fast sync from
+    pub fn with_snapshots(mut self, interval: u64) -> Self {
+        self.snapshot_interval = Some(interval.max(1));
+        self
+    }
+
+    // starts a chain from a snapshot instead of replaying every block, the headers are those of
+    // the blocks up to and including the one of the snapshot. The state has to have the root
+    // in the last header but only the links between the headers can be checked, so the root of
+    // the snapshot should come from a trusted node.
+    pub fn from_snapshot(
+        spec: ChainSpec,
+        snapshot: Snapshot,
+        headers: &[Header],
+    ) -> Result<Self, BlockchainError> {
+        let mut chain = Self::new(spec);
+        if headers.first() != Some(&chain.blocks[0].header()) {
+            return Err(BlockchainError::ForeignChain);
+        }
+
+        if headers.len() as u64 != snapshot.height + 1
+            || headers.last().map(Header::hash) != Some(snapshot.hash)
+            || headers.last().map(|header| header.state_root) != Some(snapshot.state.root())
+            || headers
+                .windows(2)
+                .any(|pair| pair[1].prev_hash != pair[0].hash())
+        {
+            return Err(BlockchainError::InvalidSnapshot);
+        }
+
+        chain.blocks = headers.iter().cloned().map(Block::from_header).collect();
+        let mut writes: Vec<_> = (0..)
+            .zip(chain.blocks.iter().cloned())
+            .map(|(height, block)| Write::Block(height, Box::new(block)))
+            .collect();
+        writes.push(Write::Snapshot(Box::new(snapshot.clone())));
+        chain.write(writes)?;
+        chain.consensus = chain.engine(&snapshot.consensus);
+        chain.state = snapshot.state.clone();
+        chain.window = snapshot.clone();
+        chain.base = snapshot;
+
+        Ok(chain)
+    }
+
+    // starts a chain from the blocks in a storage, which are imported again so a node carries
+    // on from where it stopped. A storage without blocks gets the genesis of the spec. A batch
+    // that was cut off by a crash is finished first. The state is not stored but rebuilt from
+    // the stored snapshot and the blocks after it, and the stored pending transactions are
+    // added again so those the chain no longer accepts are dropped.
+    pub fn open(spec: ChainSpec, storage: impl Storage + 'static) -> Result<Self, BlockchainError> {
+        Self::open_with_clock(spec, storage, SystemClock)
+    }
+
+    // the same as open with a clock other than the one of the machine, which the stored
+    // blocks are checked against as they are imported again
+    pub fn open_with_clock(
+        spec: ChainSpec,
+        mut storage: impl Storage + 'static,
+        clock: impl Clock + 'static,
+    ) -> Result<Self, BlockchainError> {
+        recover(&mut storage).map_err(BlockchainError::Storage)?;
+
+        let count = storage.count().map_err(BlockchainError::Storage)?;
+        let mut chain = match storage.snapshot().map_err(BlockchainError::Storage)? {
+            Some(snapshot) => {
+                let headers = (0..=snapshot.height)
+                    .map(|height| {
+                        storage
+                            .header(height)?
+                            .ok_or(StorageError::MissingBlock(height))
+                    })
+                    .collect::<Result<Vec<_>, _>>()
+                    .map_err(BlockchainError::Storage)?;
+                Self::from_snapshot(spec, snapshot, &headers)?
+            }
+            None => Self::new(spec),
+        }
+        .with_clock(clock);
+
+        if count == 0 {
+            Batch::from_iter([Write::Block(0, Box::new(chain.blocks[0].clone()))])
+                .commit(&mut storage)
+                .map_err(BlockchainError::Storage)?;
+        } else if storage.header(0).map_err(BlockchainError::Storage)?
+            != Some(chain.blocks[0].header())
+        {
+            return Err(BlockchainError::ForeignChain);
+        }
+
+        for height in chain.blocks.len() as u64..count {
+            let block = storage
+                .block(height)
+                .and_then(|block| block.ok_or(StorageError::MissingBlock(height)))
+                .map_err(BlockchainError::Storage)?;
+            chain.import_block(block)?;
+        }
+
+        for transaction in storage.mempool().map_err(BlockchainError::Storage)? {
+            let _ = chain.add_transaction(transaction);
+        }
+        chain.storage = Box::new(storage);
+        chain.set_mempool(chain.mempool.clone())?;
+
+        Ok(chain)
+    }
+
+    // an in-memory copy of the chain at its latest block, to try blocks on without changing
+    // the chain. The blocks of the copy only have their headers, its mempool is empty and it
+    // shares the clock of the chain.
+    pub fn fork(&self) -> Result<Self, BlockchainError> {
+        let headers: Vec<_> = self.blocks.iter().map(Block::header).collect();
+        let mut fork = Self::from_snapshot(self.spec.clone(), self.snapshot(), &headers)?;
+        fork.clock = self.clock.clone();
+
+        Ok(fork)
+    }
+
+    pub fn spec(&self) -> &ChainSpec {
+        &self.spec
+    }
+
+    // what a node sends a peer so that both can check they are on the same chain
+    pub fn handshake(&self) -> (u64, Hash) {
+        (self.spec.chain_id, self.blocks[0].hash())
+    }
+
+    // refuses peers that were started from a different spec
+    pub fn accept_peer(&self, chain_id: u64, genesis_hash: Hash) -> Result<(), BlockchainError> {
+        if (chain_id, genesis_hash) != self.handshake() {
+            return Err(BlockchainError::ForeignChain);
+        }
+
+        Ok(())
+    }
+
+    // the chain the engine and validation work on, only the latest blocks keep their
+    // transactions. Look blocks and transactions up through the storage with block,
+    // block_by_hash, transaction and history_of.
     pub fn blocks(&self) -> &[Block] {
         &self.blocks
     }
 
+    // the block at a height, without its transactions if it was pruned
+    pub fn block(&self, height: u64) -> Result<Option<Block>, BlockchainError> {
+        self.storage.block(height).map_err(BlockchainError::Storage)
+    }
+
+    pub fn height_of(&self, hash: &Hash) -> Result<Option<u64>, BlockchainError> {
+        self.storage
+            .height_of(hash)
+            .map_err(BlockchainError::Storage)
+    }
+
+    pub fn block_by_hash(&self, hash: &Hash) -> Result<Option<Block>, BlockchainError> {
+        self.storage
+            .block_by_hash(hash)
+            .map_err(BlockchainError::Storage)
+    }
+
+    // a transaction on the chain by its hash and where it is
+    pub fn transaction(
+        &self,
+        hash: &Hash,
+    ) -> Result<Option<(Location, Transaction)>, BlockchainError> {
+        self.storage
+            .transaction(hash)
+            .map_err(BlockchainError::Storage)
+    }
+
+    // the transactions on the chain involving the address in chain order
+    pub fn history_of(
+        &self,
+        address: &str,
+    ) -> Result<Vec<(Location, Transaction)>, BlockchainError> {
+        self.storage
+            .transactions_of(address)
+            .map_err(BlockchainError::Storage)
+    }
+
+    // the state after the latest block, everything the node needs to check new blocks
+    pub fn snapshot(&self) -> Snapshot {
+        Snapshot {
+            height: self.blocks.len() as u64 - 1,
+            hash: self.latest_block().hash(),
+            consensus: self.consensus.checkpoint(&self.blocks),
+            state: self.state.clone(),
+        }
+    }
+
+    // the snapshot taken at the latest multiple of the snapshot interval
+    pub fn latest_snapshot(&self) -> Option<&Snapshot> {
+        self.snapshot.as_ref()
+    }
+
+    // the height below which blocks have no transactions
+    pub fn pruned_height(&self) -> u64 {
+        self.base.height
+    }
+
+    // answers a light client
+    pub fn serve(&self, request: &Request) -> Result<Response, BlockchainError> {
+        Ok(match request {
+            Request::Headers { start, count } => Response::Headers(self.headers(*start, *count)?),
+            Request::Proofs {
+                address,
+                start,
+                end,
+            } => Response::Proofs(self.proofs_of(address, *start, *end)?),
+            Request::Account { address } => Response::Account {
+                address: address.clone(),
+                height: self.blocks.len() as u64 - 1,
+                proof: Box::new(self.prove_account(address)),
+            },
+        })
+    }
+
+    // proves what the address has after the latest block, or that it has no account, against
+    // the state root of its header
+    pub fn prove_account(&self, address: &str) -> AccountProof {
+        self.state.prove(address)
+    }
+
+    // the headers of up to count blocks from a height on, never more than MAX_HEADERS
+    pub fn headers(&self, start: u64, count: u64) -> Result<Vec<Header>, BlockchainError> {
+        let end = start
+            .saturating_add(count.min(MAX_HEADERS))
+            .min(self.blocks.len() as u64);
+        (start..end)
+            .map(|height| {
+                self.storage
+                    .header(height)?
+                    .ok_or(StorageError::MissingBlock(height))
+            })
+            .collect::<Result<_, _>>()
+            .map_err(BlockchainError::Storage)
+    }
+
+    // proofs for the transactions involving the address in the blocks from start up to
+    // before end, the genesis is left out as light clients build it from the spec
+    pub fn proofs_of(
+        &self,
+        address: &str,
+        start: u64,
+        end: u64,
+    ) -> Result<Vec<TransactionProof>, BlockchainError> {
+        let start = start.max(1);
+        let mut locations: BTreeMap<u64, Vec<u32>> = BTreeMap::new();
+        for location in self
+            .storage
+            .history(address)
+            .map_err(BlockchainError::Storage)?
+        {
+            if (start..end).contains(&location.height) {
+                locations
+                    .entry(location.height)
+                    .or_default()
+                    .push(location.index);
+            }
+        }
+
+        let mut proofs = Vec::new();
+        for (height, indexes) in locations {
+            let block = self
+                .block(height)?
+                .ok_or(BlockchainError::Storage(StorageError::MissingBlock(height)))?;
+            for index in indexes {
+                proofs.push(TransactionProof {
+                    height,
+                    index,
+                    transaction: block.transactions()[index as usize].clone(),
+                    siblings: block
+                        .proof(index as usize)
+                        .expect("The index is in the block"),
+                });
+            }
+        }
+
+        Ok(proofs)
+    }
+
+    // the transactions waiting to be included in a block
+    pub fn mempool(&self) -> &Mempool {
+        &self.mempool
+    }
+
+    // a channel that receives everything that happens to the chain from now on
+    pub fn subscribe(&mut self) -> Receiver<Event> {
+        self.events.subscribe()
+    }
+
+    // Calls the callback with everything that happens to the chain from now on. It runs before
+    // the change that caused the event returns, so it should not block.
+    pub fn on_event(&mut self, callback: impl FnMut(&Event) + Send + 'static) {
+        self.events.on_event(callback);
+    }
+
     // getting the last block from the chain
     pub fn latest_block(&self) -> &Block {
         self.blocks
@@ -44,121 +452,665 @@ impl Blockchain {
             .expect("There should always be a latest block")
     }
 
-    pub fn mine_pending_transactions(&mut self, reward_address: impl Into<String>) {
-        let mut transactions = vec![Transaction::new(
-            None,
-            reward_address.into(),
-            REWARD,
-            TransactionKind::Normal,
-        )]; // creating new mempool with reward
-
-        let mut to_remove = Vec::new();
-        for (i, transaction) in self.mempool.iter().enumerate() {
-            let loans = self.all_loans_of(transaction.to());
-            let mut amount_recieved = transaction.amount();
-
-            for (_, (to, amount)) in &loans {
-                if let Some(remaining) = amount_recieved.checked_sub(*amount) {
-                    amount_recieved = remaining;
-                    transactions.push(Transaction::new(
-                        Some(transaction.to().to_owned()),
-                        (*to).to_owned(),
-                        *amount,
-                        TransactionKind::Repayment,
-                    ));
-                } else {
-                    break;
-                }
+    // the address that may seal the next block right now, none if anyone may
+    pub fn expected_sealer(&self) -> Option<String> {
+        self.consensus.sealer(&self.blocks, self.next_timestamp())
+    }
+
+    // mines a block for engines that do not need a signer such as proof of work
+    pub fn mine_pending_transactions(
+        &mut self,
+        reward_address: impl Into<String>,
+    ) -> Result<(), BlockchainError> {
+        self.commit_pending_transactions(reward_address.into(), None)
+    }
+
+    // seals a block for engines where the block must be signed such as proof of stake,
+    // the reward goes to the signer
+    pub fn seal_pending_transactions(
+        &mut self,
+        signer: &SigningKey,
+    ) -> Result<(), BlockchainError> {
+        let reward_address = keygen::address(&VerifyingKey::from(signer));
+        self.commit_pending_transactions(reward_address, Some(signer))
+    }
+
+    fn commit_pending_transactions(
+        &mut self,
+        reward_address: String,
+        signer: Option<&SigningKey>,
+    ) -> Result<(), BlockchainError> {
+        // only the transactions that are mature and still valid once the ones before them have
+        // been applied make it into the block
+        let height = self.blocks.len() as u64;
+        let timestamp = self.next_timestamp();
+        let mut state = self.state.clone();
+        let supply = state.supply();
+        let mut transactions: Vec<_> = self
+            .mempool
+            .iter()
+            .filter(|transaction| {
+                transaction.is_mature(height, timestamp)
+                    && self.verifier.verify_transaction(transaction)
+                    && state.apply_verified(transaction).is_ok()
+            })
+            .cloned()
+            .collect();
+
+        // the sealer gets the reward for the height and all the fees
+        let fees: u64 = transactions
+            .iter()
+            .map(|transaction| transaction.fee())
+            .sum();
+        let reward = self.policy.reward(height, supply);
+        transactions.insert(
+            0,
+            Transaction::new(
+                None,
+                reward_address,
+                reward.saturating_add(fees),
+                TransactionKind::Normal,
+            )
+            .with_nonce(height),
+        );
+
+        // the block commits to the state after it, which the sealer signs with the rest of the
+        // header
+        let block = Block::new(timestamp, transactions, self.latest_block().hash());
+        let mut state = self.state.clone();
+        state.apply_unchecked_block(&block);
+        let mut block = block.with_state_root(state.root());
+
+        // the mempool is only touched once the engine has sealed the block so a failure leaves
+        // it as it was
+        self.consensus
+            .seal(&self.blocks, &mut block, signer)
+            .map_err(BlockchainError::Consensus)?;
+
+        self.push_block(block, state)
+    }
+
+    // fully validates a block from another node and appends it if it follows every rule
+    pub fn import_block(&mut self, block: Block) -> Result<(), BlockchainError> {
+        self.check_header(self.consensus.as_ref(), &self.blocks, &block)
+            .map_err(BlockchainError::InvalidBlock)?;
+
+        self.verifier
+            .verify_block(&block)
+            .map_err(BlockchainError::InvalidBlock)?;
+
+        let mut state = self.state.clone();
+        state
+            .apply_verified_block(&block, &self.policy, self.blocks.len() as u64)
+            .map_err(BlockchainError::InvalidBlock)?;
+        if state.root() != block.state_root() {
+            return Err(BlockchainError::InvalidBlock(BlockError::InvalidStateRoot));
+        }
+
+        self.push_block(block, state)
+    }
+
+    // stores a block and appends it with the state after it, see write_blocks
+    fn push_block(&mut self, block: Block, state: ChainState) -> Result<(), BlockchainError> {
+        let height = self.blocks.len() as u64;
+        self.blocks.push(block);
+        self.write_blocks(height, Vec::new(), state)
+    }
+
+    // Switches to a longer branch from another node that forks off after the block at the
+    // height. Every block of the branch is fully validated before anything changes, and the
+    // transactions of the replaced blocks that the branch does not include go back to the
+    // mempool. Blocks below the window cannot be replaced.
+    pub fn reorg(&mut self, height: u64, branch: Vec<Block>) -> Result<(), BlockchainError> {
+        let tip = self.blocks.len() as u64 - 1;
+        if height < self.window.height || height >= tip || height + branch.len() as u64 <= tip {
+            return Err(BlockchainError::InvalidReorg);
+        }
+
+        // the state at the fork is rebuilt from the window
+        let mut chain = self.blocks[..=height as usize].to_vec();
+        let mut state = self.window.state.clone();
+        for block in &chain[self.window.height as usize + 1..] {
+            state.apply_unchecked_block(block);
+        }
+
+        for block in branch {
+            self.check_header(self.consensus.as_ref(), &chain, &block)
+                .map_err(BlockchainError::InvalidBlock)?;
+            self.verifier
+                .verify_block(&block)
+                .map_err(BlockchainError::InvalidBlock)?;
+            state
+                .apply_verified_block(&block, &self.policy, chain.len() as u64)
+                .map_err(BlockchainError::InvalidBlock)?;
+            if state.root() != block.state_root() {
+                return Err(BlockchainError::InvalidBlock(BlockError::InvalidStateRoot));
+            }
+            chain.push(block);
+        }
+
+        let replaced = std::mem::replace(&mut self.blocks, chain).split_off(height as usize + 1);
+        self.write_blocks(height + 1, replaced, state)
+    }
+
+    // Stores the blocks from a height on, which took the place of the replaced ones, and
+    // moves to the state after them. The blocks, the blocks they let the chain prune and the
+    // mempool without the transactions they included are written as one batch, and nothing
+    // changes if it cannot be. Blocks that fall out of the window only keep their headers in
+    // memory.
+    fn write_blocks(
+        &mut self,
+        from: u64,
+        replaced: Vec<Block>,
+        state: ChainState,
+    ) -> Result<(), BlockchainError> {
+        let mut writes: Vec<_> = (from..)
+            .zip(&self.blocks[from as usize..])
+            .map(|(height, block)| Write::Block(height, Box::new(block.clone())))
+            .collect();
+
+        // the snapshot is written first so the stored blocks after it always have their
+        // transactions
+        let base = match self.next_base() {
+            Ok(base) => base,
+            Err(error) => return self.undo(from, replaced, error),
+        };
+        if let Some(base) = &base {
+            writes.push(Write::Snapshot(Box::new(base.clone())));
+            // the block at the old base was stored without its transactions when the base
+            // moved there, other than the genesis
+            let first = self.base.height + (self.base.height > 0) as u64;
+            for height in first..=base.height {
+                let block = Block::from_header(self.blocks[height as usize].header());
+                writes.push(Write::Block(height, Box::new(block)));
             }
+        }
+        let window = match self.next_window(base.as_ref()) {
+            Ok(window) => window,
+            Err(error) => return self.undo(from, replaced, error),
+        };
 
-            // if there is a loan but it has not been signed by both parties then it must not be
-            // mined
-            if transaction.is_loan() && !transaction.loan_signed() {
-                to_remove.push(i)
+        // only the transactions that were pending before count as evicted
+        let mut mempool = self.mempool.clone();
+        let mut evicted = Vec::new();
+        let orphans = replaced.iter().flat_map(Block::transactions);
+        for transaction in orphans.filter(|transaction| !transaction.is_coinbase()) {
+            if let Ok(insertion) = mempool.insert(transaction.clone(), self.clock.now()) {
+                evicted.extend(insertion.evicted.into_iter().map(|t| (t, Eviction::Full)));
             }
         }
+        let included: HashSet<_> = self.blocks[from as usize..]
+            .iter()
+            .flat_map(Block::transactions)
+            .map(Transaction::hash)
+            .collect();
+        evicted.extend(self.prune_mempool(&mut mempool, &state, &included));
+        evicted.retain(|(transaction, _)| self.mempool.contains(&transaction.hash()));
 
-        // moving transavtions back to mempool
-        for i in to_remove {
-            transactions.push(self.mempool.remove(i))
+        writes.push(Write::Mempool(mempool.iter().cloned().collect()));
+        if let Err(error) = self.write(writes) {
+            return self.undo(from, replaced, error);
         }
 
-        std::mem::swap(&mut transactions, &mut self.mempool);
+        let added = self.blocks[from as usize..].to_vec();
+        self.state = state;
+        self.mempool = mempool;
+        self.verifier.retain(self.mempool.iter());
+        if let Some(window) = window {
+            for block in &mut self.blocks[self.window.height as usize..=window.height as usize] {
+                block.prune();
+            }
+            self.consensus = self.engine(&window.consensus);
+            self.window = window;
+        }
+        if let Some(base) = base {
+            self.base = base;
+        }
 
-        // block is mined when it is created
-        let block = Block::new(transactions, self.latest_block().hash());
-        self.blocks.push(block);
+        let height = self.blocks.len() as u64 - 1;
+        if self
+            .snapshot_interval
+            .is_some_and(|interval| height.is_multiple_of(interval))
+        {
+            self.snapshot = Some(self.snapshot());
+        }
+
+        self.publish_blocks(from, &replaced, added, evicted);
+
+        Ok(())
+    }
+
+    // tells the subscribers about the blocks that were written from a height on
+    fn publish_blocks(
+        &mut self,
+        from: u64,
+        replaced: &[Block],
+        added: Vec<Block>,
+        evicted: Vec<(Transaction, Eviction)>,
+    ) {
+        if !replaced.is_empty() {
+            self.events.publish(Event::Reorg {
+                height: from - 1,
+                removed: replaced.iter().map(Block::hash).collect(),
+                added: added.iter().map(Block::hash).collect(),
+            });
+        }
+
+        for (height, block) in (from..).zip(added) {
+            let repayments: Vec<_> = block
+                .transactions()
+                .iter()
+                .filter(|transaction| transaction.kind() == &TransactionKind::Repayment)
+                .cloned()
+                .collect();
+            self.events.publish(Event::NewBlock { height, block });
+
+            for transaction in repayments {
+                let borrower = transaction.from().clone().unwrap_or_default();
+                let remaining = self
+                    .state
+                    .debt(&borrower, transaction.to(), transaction.asset());
+                self.events.publish(Event::LoanRepaid {
+                    height,
+                    transaction,
+                    remaining,
+                });
+            }
+        }
+
+        for (transaction, reason) in evicted {
+            self.events.publish(Event::TransactionEvicted {
+                transaction,
+                reason,
+            });
+        }
+    }
+
+    // puts the replaced blocks back after the new ones could not be written
+    fn undo(
+        &mut self,
+        from: u64,
+        replaced: Vec<Block>,
+        error: BlockchainError,
+    ) -> Result<(), BlockchainError> {
+        self.blocks.truncate(from as usize);
+        self.blocks.extend(replaced);
+        Err(error)
+    }
+
+    // the base snapshot moved up to the pruning depth below the latest block, if it moves
+    fn next_base(&self) -> Result<Option<Snapshot>, BlockchainError> {
+        let Some(height) = self
+            .pruning
+            .and_then(|depth| (self.blocks.len() as u64 - 1).checked_sub(depth))
+            .filter(|height| *height > self.base.height)
+        else {
+            return Ok(None);
+        };
+
+        self.checkpoint(&self.base, height).map(Some)
+    }
+
+    // the window moved up to the memory depth below the latest block, or to the base if that
+    // is higher, if it moves
+    fn next_window(&self, base: Option<&Snapshot>) -> Result<Option<Snapshot>, BlockchainError> {
+        let height = (self.blocks.len() as u64 - 1)
+            .saturating_sub(self.memory)
+            .max(base.map_or(0, |base| base.height));
+        if height <= self.window.height {
+            return Ok(None);
+        }
+
+        match base {
+            Some(base) if base.height == height => Ok(Some(base.clone())),
+            _ => self.checkpoint(&self.window, height).map(Some),
+        }
+    }
+
+    // the state and engine after the block at a height, replayed from an earlier snapshot
+    fn checkpoint(&self, from: &Snapshot, height: u64) -> Result<Snapshot, BlockchainError> {
+        let chain = self.chain_after(from, height)?;
+        let mut state = from.state.clone();
+        for block in &chain[from.height as usize + 1..] {
+            state.apply_unchecked_block(block);
+        }
+
+        Ok(Snapshot {
+            height,
+            hash: chain[height as usize].hash(),
+            consensus: self.engine(&from.consensus).checkpoint(&chain),
+            state,
+        })
+    }
+
+    // the chain up to the block at a height where the blocks after the snapshot have their
+    // transactions, those only kept as headers in memory are read from the storage
+    fn chain_after(&self, snapshot: &Snapshot, height: u64) -> Result<Vec<Block>, BlockchainError> {
+        let mut chain = self.blocks[..=height as usize].to_vec();
+        for height in snapshot.height + 1..=height {
+            let block = &mut chain[height as usize];
+            if block.is_pruned() {
+                *block = self
+                    .block(height)?
+                    .ok_or(BlockchainError::Storage(StorageError::MissingBlock(height)))?;
+            }
+        }
+
+        Ok(chain)
+    }
+
+    // logs the writes in the storage and carries them out, see Batch
+    fn write(&mut self, writes: impl IntoIterator<Item = Write>) -> Result<(), BlockchainError> {
+        Batch::from_iter(writes)
+            .commit(self.storage.as_mut())
+            .map_err(BlockchainError::Storage)
+    }
+
+    // stores the pending transactions and replaces the mempool with them once they are
+    fn set_mempool(&mut self, mempool: Mempool) -> Result<(), BlockchainError> {
+        self.write([Write::Mempool(mempool.iter().cloned().collect())])?;
+        self.mempool = mempool;
+
+        Ok(())
+    }
+
+    // builds the engine of the spec from a checkpoint
+    fn engine(&self, checkpoint: &ConsensusSpec) -> Box<dyn Consensus> {
+        ChainSpec {
+            consensus: checkpoint.clone(),
+            ..self.spec.clone()
+        }
+        .consensus()
+    }
+
+    // Drops the pending transactions that were included in a block or that can never be
+    // because their nonce was used, as well as those that waited too long. Returns the ones
+    // that were dropped without being included in the new blocks.
+    fn prune_mempool(
+        &self,
+        mempool: &mut Mempool,
+        state: &ChainState,
+        included: &HashSet<Hash>,
+    ) -> Vec<(Transaction, Eviction)> {
+        let removed = mempool.retain(|transaction| {
+            transaction
+                .from()
+                .as_ref()
+                .is_some_and(|from| transaction.nonce() >= state.nonce_of(from))
+                && state.utxos().is_none_or(|utxos| {
+                    transaction
+                        .inputs()
+                        .iter()
+                        .all(|input| utxos.contains(input))
+                })
+        });
+        let expired = mempool.expire(self.clock.now(), self.blocks.len() as u64);
+
+        removed
+            .into_iter()
+            .filter(|transaction| !included.contains(&transaction.hash()))
+            .map(|transaction| (transaction, Eviction::Invalid))
+            .chain(
+                expired
+                    .into_iter()
+                    .map(|transaction| (transaction, Eviction::Expired)),
+            )
+            .collect()
+    }
+
+    // the rules a block must follow that do not depend on its transactions, the engine has to
+    // be the one for the chain
+    fn check_header(
+        &self,
+        consensus: &dyn Consensus,
+        chain: &[Block],
+        block: &Block,
+    ) -> Result<(), BlockError> {
+        let parent = chain.last().expect("There should always be a latest block");
+        if block.prev_hash() != parent.hash() {
+            return Err(BlockError::UnknownParent);
+        }
+
+        if block.timestamp() <= Self::median_time(chain) {
+            return Err(BlockError::TimestampTooOld);
+        }
+
+        if block.timestamp() > self.clock.now() + MAX_FUTURE_DRIFT {
+            return Err(BlockError::TimestampInFuture);
+        }
+
+        if !consensus.verify(chain, block) {
+            return Err(BlockError::InvalidSeal);
+        }
+
+        Ok(())
+    }
+
+    // the median timestamp of the latest blocks, a new block must be later than it
+    fn median_time(chain: &[Block]) -> u64 {
+        median_time(chain.iter().map(Block::timestamp))
+    }
+
+    // the timestamp for the next block, normally the current time but never so early that
+    // the block would be rejected
+    fn next_timestamp(&self) -> u64 {
+        self.clock.now().max(Self::median_time(&self.blocks) + 1)
+    }
+
+    // the balances and debts after every block in the chain
+    pub fn state(&self) -> &ChainState {
+        &self.state
     }
 
     pub fn add_transaction(&mut self, transaction: Transaction) -> Result<(), BlockchainError> {
-        if !transaction.valid() {
+        // a stake can only lock coins for the sender
+        let foreign_stake =
+            transaction.is_stake() && transaction.from().as_deref() != Some(transaction.to());
+        if transaction.is_coinbase() || foreign_stake {
             return Err(BlockchainError::InvalidTransaction);
         }
 
-        if let Ok(balance) = self.balance_of(transaction.from().as_ref().unwrap()) {
-            // making sure user has enough to pay
-            if transaction.amount() > balance {
-                return Err(BlockchainError::BalanceTooSmall);
+        if !self.verifier.verify_transaction(&transaction) {
+            return Err(BlockchainError::InvalidTransaction);
+        }
+
+        // a nonce the sender already used on the chain can never be mined
+        let from = transaction.from().as_deref().unwrap_or_default();
+        if transaction.nonce() < self.state.nonce_of(from) {
+            return Err(BlockchainError::StaleNonce);
+        }
+
+        match self.spec.ledger {
+            Ledger::Account => self.check_balance(&transaction)?,
+            Ledger::Utxo => self.check_inputs(&transaction)?,
+        }
+
+        // a transaction with the nonce of a pending one replaces it if it pays a higher fee
+        let hash = transaction.hash();
+        let mut mempool = self.mempool.clone();
+        let expired = mempool.expire(self.clock.now(), self.blocks.len() as u64);
+        let insertion = mempool
+            .insert(transaction.clone(), self.clock.now())
+            .map_err(BlockchainError::Mempool)?;
+        self.set_mempool(mempool)?;
+
+        let evicted = expired
+            .into_iter()
+            .map(|t| (t, Eviction::Expired))
+            .chain(insertion.evicted.into_iter().map(|t| (t, Eviction::Full)));
+        for (transaction, reason) in evicted {
+            self.events.publish(Event::TransactionEvicted {
+                transaction,
+                reason,
+            });
+        }
+        if let Some(replaced) = insertion.replaced {
+            self.events
+                .publish(Event::TransactionReplaced { replaced, by: hash });
+        }
+        self.events.publish(Event::NewTransaction(transaction));
+
+        Ok(())
+    }
+
+    // the fee is paid in the coin of the chain and the amount in its asset, the amount of a
+    // release or refund comes out of the escrow and is checked when it is mined
+    fn check_balance(&self, transaction: &Transaction) -> Result<(), BlockchainError> {
+        let from = transaction.from().as_deref().unwrap_or_default();
+        let asset = transaction.asset();
+        let pays_amount = transaction.payer().as_deref() == Some(from);
+        let mut cost = Some(transaction.fee());
+        if pays_amount && asset.is_none() {
+            cost = cost.and_then(|cost| cost.checked_add(transaction.amount()));
+        }
+        match (cost, self.balance_of(from, None)) {
+            // making sure user has enough to pay, a cost too large to add up is more than anyone
+            // has
+            (Some(cost), Ok(balance)) if cost <= balance => (),
+            _ => return Err(BlockchainError::BalanceTooSmall),
+        }
+        if pays_amount && asset.is_some() {
+            match self.balance_of(from, asset) {
+                Ok(balance) if balance >= transaction.amount() => (),
+                _ => return Err(BlockchainError::BalanceTooSmall),
             }
-        } else {
-            return Err(BlockchainError::BalanceTooSmall);
         }
 
-        self.mempool.push(transaction);
         Ok(())
     }
 
-    // if balance is negative transaction cannot be made
-    pub fn balance_of(&self, address: &str) -> Result<u64, BlockchainError> {
-        let balance = self
-            .blocks
+    // the inputs have to be unspent on the chain and not be spent by another pending
+    // transaction, unless it is the one this transaction replaces
+    fn check_inputs(&self, transaction: &Transaction) -> Result<(), BlockchainError> {
+        self.state().check_utxo(transaction).map_err(|e| match e {
+            BlockError::DoubleSpend(_) => BlockchainError::DoubleSpend,
+            BlockError::Overdraft(_) => BlockchainError::BalanceTooSmall,
+            _ => BlockchainError::InvalidTransaction,
+        })?;
+
+        let in_use = self.pending_inputs(Some(transaction));
+        if transaction
+            .inputs()
             .iter()
-            .map(|block| {
-                block
-                    .transactions()
-                    .iter()
-                    .filter_map(|transaction| {
-                        if let Some(from) = transaction.from() {
-                            if from == address {
-                                Some(-(transaction.amount() as i64))
-                            } else if transaction.to() == address {
-                                Some(transaction.amount() as i64)
-                            } else {
-                                None
-                            }
-                        } else if transaction.to() == address {
-                            Some(transaction.amount() as i64)
-                        } else {
-                            None
-                        }
-                    })
-                    .sum::<i64>()
+            .any(|input| in_use.contains(input))
+        {
+            return Err(BlockchainError::DoubleSpend);
+        }
+
+        Ok(())
+    }
+
+    // the outputs spent by pending transactions leaving out the one a transaction replaces
+    fn pending_inputs(&self, replacing: Option<&Transaction>) -> HashSet<OutPoint> {
+        self.mempool
+            .iter()
+            .filter(|pending| {
+                replacing.is_none_or(|transaction| {
+                    pending.from() != transaction.from() || pending.nonce() != transaction.nonce()
+                })
             })
-            .sum::<i64>();
+            .flat_map(|pending| pending.inputs().iter().copied())
+            .collect()
+    }
 
-        if balance.is_negative() {
-            Err(BlockchainError::NegativeBalance)
-        } else {
-            Ok(balance as u64)
-        }
+    // the unspent outputs of an address on a chain that uses the UTXO model
+    pub fn utxos_of(&self, address: &str) -> Result<Vec<(OutPoint, Output)>, BlockchainError> {
+        let state = self.state();
+        let utxos = state.utxos().ok_or(BlockchainError::NotUtxo)?;
+
+        Ok(utxos
+            .of(address)
+            .map(|(outpoint, output)| (outpoint, output.clone()))
+            .collect())
     }
 
-    // for all loans returns the address and amount owed to an address
-    // looks in blockchain
-    pub fn all_loans_of(&self, address: &str) -> IndexMap<[u8; 32], (&str, u64)> {
-        let mut loans = IndexMap::new();
+    // picks unspent outputs of the address worth at least the target that no pending
+    // transaction spends, returns them with what they are worth together
+    pub fn select_coins(
+        &self,
+        address: &str,
+        target: u64,
+    ) -> Result<(Vec<OutPoint>, u64), BlockchainError> {
+        let state = self.state();
+        let utxos = state.utxos().ok_or(BlockchainError::NotUtxo)?;
 
-        for block in self.blocks() {
-            for transaction in block.transactions() {
-                if transaction.is_loan() {
-                    if transaction.to() == address {
-                        loans.insert(transaction.hash(), (transaction.to(), transaction.amount()));
-                    }
-                }
-            }
-        }
+        utxos
+            .select(address, target, &self.pending_inputs(None))
+            .ok_or(BlockchainError::BalanceTooSmall)
+    }
 
-        loans
+    // what the address holds of an asset, none is the coin of the chain
+    pub fn balance_of(
+        &self,
+        address: &str,
+        asset: Option<AssetId>,
+    ) -> Result<u64, BlockchainError> {
+        Ok(self.state().balance_of(address, asset))
+    }
+
+    // the nonce the next transaction from the address should use including those that are
+    // still pending
+    pub fn next_nonce(&self, address: &str) -> u64 {
+        let pending = self
+            .mempool
+            .from_sender(address)
+            .last()
+            .map(|transaction| transaction.nonce() + 1)
+            .unwrap_or_default();
+
+        self.state().nonce_of(address).max(pending)
+    }
+
+    // everything the address holds by asset, none is the coin of the chain
+    pub fn balances_of(&self, address: &str) -> BTreeMap<Option<AssetId>, u64> {
+        self.state().balances_of(address)
+    }
+
+    // an issued asset by its id
+    pub fn asset(&self, id: &AssetId) -> Option<Asset> {
+        self.state().asset(id).cloned()
+    }
+
+    // what the borrower still has to repay the lender in an asset
+    pub fn debt(&self, borrower: &str, lender: &str, asset: Option<AssetId>) -> u64 {
+        self.state().debt(borrower, lender, asset)
+    }
+
+    // all the coins that have been created including the genesis allocations
+    pub fn total_supply(&self) -> u64 {
+        self.state().supply()
+    }
+
+    // the coins that can be spent, staked coins are locked
+    pub fn circulating_supply(&self) -> u64 {
+        self.total_supply().saturating_sub(self.state.staked())
+    }
+
+    pub fn monetary_policy(&self) -> &MonetaryPolicy {
+        &self.policy
+    }
+
+    // the coins each address has locked by staking
+    pub fn stake_of(&self, address: &str) -> u64 {
+        self.state.stake_of(address)
+    }
+
+    // the loans to the address on the chain
+    pub fn all_loans_of(
+        &self,
+        address: &str,
+    ) -> Result<IndexMap<[u8; 32], (String, u64)>, BlockchainError> {
+        Ok(self
+            .history_of(address)?
+            .into_iter()
+            .filter(|(_, transaction)| transaction.is_loan() && transaction.to() == address)
+            .map(|(_, transaction)| {
+                (
+                    transaction.hash(),
+                    (transaction.to().to_owned(), transaction.amount()),
+                )
+            })
+            .collect())
     }
 
     // returns the loans of a user
@@ -166,11 +1118,12 @@ impl Blockchain {
     pub fn loans_of(&self, address: &str, valid: bool) -> IndexMap<[u8; 32], (&str, u64)> {
         let mut loans = IndexMap::new();
 
-        for transaction in &self.mempool {
-            if transaction.is_loan() && transaction.loan_signed() == valid {
-                if transaction.to() == address {
-                    loans.insert(transaction.hash(), (transaction.to(), transaction.amount()));
-                }
+        for transaction in self.mempool.iter() {
+            if transaction.is_loan()
+                && transaction.loan_signed() == valid
+                && transaction.to() == address
+            {
+                loans.insert(transaction.hash(), (transaction.to(), transaction.amount()));
             }
         }
 
@@ -182,86 +1135,151 @@ impl Blockchain {
         payee: &SigningKey,
         transaction_hash: [u8; 32],
     ) -> Result<(), BlockchainError> {
-        let Some(transaction) = self
-            .mempool
-            .iter_mut()
-            .find(|transaction| transaction.hash() == transaction_hash) else {
-                return Err(BlockchainError::NoTransactionFound); 
-            };
+        let mut mempool = self.mempool.clone();
+        let (signed, evicted) = mempool
+            .update(&transaction_hash, |transaction| {
+                transaction.sign_loan_transaction(payee)
+            })
+            .ok_or(BlockchainError::NoTransactionFound)?
+            .map_err(BlockchainError::Mempool)?;
+        signed.map_err(|_| BlockchainError::InvalidSigner)?;
+        let signed = mempool.get(&transaction_hash).cloned();
+        self.set_mempool(mempool)?;
 
-        transaction.sign_loan_transaction(payee);
+        self.publish_evicted(evicted);
+        if let Some(signed) = signed {
+            self.events.publish(Event::LoanSigned(signed));
+        }
 
         Ok(())
     }
 
-    pub fn paid_to(&self, from: &str, to: &str) -> u64 {
-        self.blocks()
-            .iter()
-            .map(|block| {
-                block
-                    .transactions()
+    // the open escrows the address is the buyer, seller or arbiter of
+    pub fn escrows_of(&self, address: &str) -> Vec<Escrow> {
+        let mut escrows: Vec<_> = self
+            .state()
+            .escrows()
+            .filter(|escrow| {
+                [&escrow.buyer, &escrow.seller, &escrow.arbiter]
                     .iter()
-                    .filter_map(|transaction| {
-                        if let Some(f) = transaction.from().as_ref() {
-                            if f == from && transaction.to() == to {
-                                Some(transaction.amount())
-                            } else {
-                                None
-                            }
-                        } else {
-                            None
-                        }
-                    })
-                    .sum::<u64>()
+                    .any(|party| party.as_str() == address)
+            })
+            .cloned()
+            .collect();
+        escrows.sort_by_key(|escrow| escrow.hash);
+
+        escrows
+    }
+
+    // the pending releases of escrows to a seller
+    // looks in mempool
+    pub fn releases_of(&self, address: &str, signed: bool) -> IndexMap<[u8; 32], (&str, u64)> {
+        let mut releases = IndexMap::new();
+
+        for transaction in self.mempool.iter() {
+            if matches!(transaction.kind(), TransactionKind::Release { .. })
+                && transaction.release_signed() == signed
+                && transaction.to() == address
+            {
+                releases.insert(transaction.hash(), (transaction.to(), transaction.amount()));
+            }
+        }
+
+        releases
+    }
+
+    pub fn sign_release(
+        &mut self,
+        seller: &SigningKey,
+        transaction_hash: [u8; 32],
+    ) -> Result<(), BlockchainError> {
+        let mut mempool = self.mempool.clone();
+        let (signed, evicted) = mempool
+            .update(&transaction_hash, |transaction| {
+                transaction.sign_release_transaction(seller)
+            })
+            .ok_or(BlockchainError::NoTransactionFound)?
+            .map_err(BlockchainError::Mempool)?;
+        signed.map_err(|_| BlockchainError::InvalidSigner)?;
+        self.set_mempool(mempool)?;
+
+        self.publish_evicted(evicted);
+        Ok(())
+    }
+
+    // tells the subscribers about the transactions a larger one pushed out of the mempool
+    fn publish_evicted(&mut self, evicted: Vec<Transaction>) {
+        for transaction in evicted {
+            self.events.publish(Event::TransactionEvicted {
+                transaction,
+                reason: Eviction::Full,
+            });
+        }
+    }
+
+    pub fn paid_to(&self, from: &str, to: &str) -> Result<u64, BlockchainError> {
+        Ok(self
+            .history_of(from)?
+            .iter()
+            .filter(|(_, transaction)| {
+                transaction.from().as_deref() == Some(from) && transaction.to() == to
             })
-            .sum()
+            .map(|(_, transaction)| transaction.amount())
+            .sum())
     }
 
-    pub fn total_loan_cost(&self, from: &str, to: &str) -> u64 {
-        self.blocks()
+    pub fn total_loan_cost(&self, from: &str, to: &str) -> Result<u64, BlockchainError> {
+        Ok(self
+            .history_of(from)?
             .iter()
-            .map(|block| {
-                block
-                    .transactions()
-                    .iter()
-                    .filter(|transaction| transaction.is_loan())
-                    .filter_map(|transaction| {
-                        if let Some(f) = transaction.from().as_ref() {
-                            if f == from && transaction.to() == to {
-                                Some(transaction.amount())
-                            } else {
-                                None
-                            }
-                        } else {
-                            None
-                        }
-                    })
-                    .sum::<u64>()
+            .filter(|(_, transaction)| {
+                transaction.is_loan()
+                    && transaction.from().as_deref() == Some(from)
+                    && transaction.to() == to
             })
-            .sum()
+            .map(|(_, transaction)| transaction.amount())
+            .sum())
     }
 
-    fn valid(&self) -> bool {
-        for i in 1..self.blocks.len() {
-            let cur_block = &self.blocks[i];
-            let prev_block = &self.blocks[i - 1];
+    // checks the whole chain by checking every block as if it was imported, how blocks are
+    // sealed is left to the consensus engine. The blocks outside the window are read from the
+    // storage. The signitures do not depend on the blocks before them so they are all checked
+    // in parallel first.
+    pub fn valid(&self) -> bool {
+        let Ok(chain) = self.chain_after(&self.base, self.blocks.len() as u64 - 1) else {
+            return false;
+        };
+        let start = self.base.height as usize + 1;
+        if self.verifier.verify_blocks(&chain[start..]).is_err() {
+            return false;
+        }
 
-            if !cur_block.valid_transactions() {
-                return false;
-            }
+        // only the links of the pruned blocks are left to check
+        if chain[..start]
+            .windows(2)
+            .any(|pair| pair[1].prev_hash() != pair[0].hash())
+        {
+            return false;
+        }
 
-            if cur_block.hash()
-                != Block::calculate_hash(
-                    cur_block.timestamp(),
-                    cur_block.nonce(),
-                    cur_block.transactions(),
-                    &cur_block.prev_hash(),
-                )
+        let consensus = self.engine(&self.base.consensus);
+        let mut state = self.base.state.clone();
+
+        for i in start..chain.len() {
+            let cur_block = &chain[i];
+
+            if self
+                .check_header(consensus.as_ref(), &chain[..i], cur_block)
+                .is_err()
             {
                 return false;
             }
 
-            if cur_block.prev_hash() != prev_block.hash() {
+            if state
+                .apply_verified_block(cur_block, &self.policy, i as u64)
+                .is_err()
+                || state.root() != cur_block.state_root()
+            {
                 return false;
             }
         }
@@ -269,3 +1287,11 @@ impl Blockchain {
         true
     }
 }
+
+// the median of the latest timestamps of a chain, from oldest to newest
+pub(crate) fn median_time(timestamps: impl DoubleEndedIterator<Item = u64>) -> u64 {
+    let mut timestamps: Vec<_> = timestamps.rev().take(MEDIAN_TIME_SPAN).collect();
+    timestamps.sort_unstable();
+
+    timestamps[timestamps.len() / 2]
+}
@@ -0,0 +1,242 @@
+use crate::{
+    block::{Block, Hash},
+    consensus::{Consensus, ProofOfAuthority, ProofOfStake, ProofOfWork},
+    encoding::{Decode, DecodeError, Encode, Reader},
+    monetary::{MonetaryPolicy, RewardSchedule},
+    state::ChainState,
+    transaction::{Transaction, TransactionKind},
+};
+use serde::{Deserialize, Serialize};
+use sha2::Digest;
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fs,
+    path::Path,
+};
+
+const DIFFICULTY: usize = 2; // how many bytes need to be 0
+pub const MAX_DIFFICULTY: usize = 32; // a hash has no more bytes that could be 0
+const REWARD: u64 = 1000;
+pub const SLOT_DURATION: u64 = 5; // seconds a proof of stake validator has to seal a block
+
+#[derive(Debug)]
+pub enum ChainSpecError {
+    Io(std::io::Error),
+    Parse(serde_json::Error),
+    InvalidDifficulty(usize), // more zero bytes than a hash has
+}
+
+// The engine the chain uses and its genesis configuration
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ConsensusSpec {
+    ProofOfWork,
+    ProofOfStake {
+        stakes: BTreeMap<String, u64>,
+        #[serde(default = "slot_duration")]
+        slot_duration: u64,
+    },
+    ProofOfAuthority {
+        authorities: Vec<String>,
+        // votes still short of a majority, only set when the spec is a checkpoint of a chain
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        votes: Vec<Vote>,
+    },
+}
+
+// the engine is part of a snapshot, maps are ordered so the encoding is always the same
+impl Encode for ConsensusSpec {
+    fn encode(&self, out: &mut Vec<u8>) {
+        match self {
+            ConsensusSpec::ProofOfWork => 0u8.encode(out),
+            ConsensusSpec::ProofOfStake {
+                stakes,
+                slot_duration,
+            } => {
+                1u8.encode(out);
+                (stakes.len() as u32).encode(out);
+                for (address, stake) in stakes {
+                    address.encode(out);
+                    stake.encode(out);
+                }
+                slot_duration.encode(out);
+            }
+            ConsensusSpec::ProofOfAuthority { authorities, votes } => {
+                2u8.encode(out);
+                (authorities.len() as u32).encode(out);
+                for authority in authorities {
+                    authority.encode(out);
+                }
+                (votes.len() as u32).encode(out);
+                for vote in votes {
+                    vote.candidate.encode(out);
+                    (vote.add as u8).encode(out);
+                    (vote.voters.len() as u32).encode(out);
+                    for voter in &vote.voters {
+                        voter.encode(out);
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl Decode for ConsensusSpec {
+    fn decode(reader: &mut Reader) -> Result<Self, DecodeError> {
+        match reader.u8()? {
+            0 => Ok(ConsensusSpec::ProofOfWork),
+            1 => Ok(ConsensusSpec::ProofOfStake {
+                stakes: reader
+                    .list_with(|reader| Ok((reader.string()?, reader.u64()?)))?
+                    .into_iter()
+                    .collect(),
+                slot_duration: reader.u64()?,
+            }),
+            2 => Ok(ConsensusSpec::ProofOfAuthority {
+                authorities: reader.list_with(Reader::string)?,
+                votes: reader.list_with(|reader| {
+                    Ok(Vote {
+                        candidate: reader.string()?,
+                        add: match reader.u8()? {
+                            0 => false,
+                            1 => true,
+                            tag => return Err(DecodeError::InvalidTag(tag)),
+                        },
+                        voters: reader.list_with(Reader::string)?.into_iter().collect(),
+                    })
+                })?,
+            }),
+            tag => Err(DecodeError::InvalidTag(tag)),
+        }
+    }
+}
+
+// the slot duration of specs written before it could be set
+fn slot_duration() -> u64 {
+    SLOT_DURATION
+}
+
+// The authorities that voted to add or remove a candidate
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Vote {
+    pub candidate: String,
+    pub add: bool,
+    pub voters: BTreeSet<String>,
+}
+
+// How the chain keeps track of who owns what
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Ledger {
+    #[default]
+    Account, // a balance for every address
+    Utxo, // transactions spend the outputs of earlier transactions and create new ones
+}
+
+// Everything nodes need to agree on before they can share a chain
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ChainSpec {
+    pub chain_id: u64,
+    pub genesis_timestamp: u64,
+    #[serde(default)]
+    pub allocations: BTreeMap<String, u64>, // coins given to addresses in the genesis block
+    pub difficulty: usize,
+    pub block_reward: u64, // the reward of the first block, later rewards follow the schedule
+    #[serde(default)]
+    pub reward_schedule: RewardSchedule,
+    #[serde(default)]
+    pub max_supply: Option<u64>,
+    #[serde(default = "default_consensus")]
+    pub consensus: ConsensusSpec,
+    #[serde(default)]
+    pub ledger: Ledger,
+}
+
+fn default_consensus() -> ConsensusSpec {
+    ConsensusSpec::ProofOfWork
+}
+
+impl Default for ChainSpec {
+    fn default() -> Self {
+        Self {
+            chain_id: 0,
+            genesis_timestamp: 0,
+            allocations: BTreeMap::new(),
+            difficulty: DIFFICULTY,
+            block_reward: REWARD,
+            reward_schedule: RewardSchedule::Constant,
+            max_supply: None,
+            consensus: ConsensusSpec::ProofOfWork,
+            ledger: Ledger::Account,
+        }
+    }
+}
+
+impl ChainSpec {
+    // reads a spec from a json file
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, ChainSpecError> {
+        let contents = fs::read_to_string(path).map_err(ChainSpecError::Io)?;
+        let spec: Self = serde_json::from_str(&contents).map_err(ChainSpecError::Parse)?;
+        spec.validate()?;
+
+        Ok(spec)
+    }
+
+    // checks the parameters that would make every block invalid
+    pub fn validate(&self) -> Result<(), ChainSpecError> {
+        if self.difficulty > MAX_DIFFICULTY {
+            return Err(ChainSpecError::InvalidDifficulty(self.difficulty));
+        }
+
+        Ok(())
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), ChainSpecError> {
+        let contents = serde_json::to_string_pretty(self).map_err(ChainSpecError::Parse)?;
+        fs::write(path, contents).map_err(ChainSpecError::Io)
+    }
+
+    // hash of all the parameters, maps are ordered so the encoding is always the same
+    pub fn hash(&self) -> Hash {
+        let bytes = serde_json::to_vec(self).expect("A chain spec is always serializable");
+        sha2::Sha256::digest(bytes).into()
+    }
+
+    // the genesis block pays out the allocations and points to the hash of the spec instead of
+    // a previous block so that it commits to every parameter
+    pub fn genesis(&self) -> Block {
+        let transactions: Vec<_> = self
+            .allocations
+            .iter()
+            .map(|(address, amount)| {
+                Transaction::new(None, address.clone(), *amount, TransactionKind::Normal)
+            })
+            .collect();
+
+        let block = Block::new(self.genesis_timestamp, transactions, self.hash());
+        let state = ChainState::genesis(&block, self.ledger);
+        block.with_state_root(state.root())
+    }
+
+    pub fn monetary_policy(&self) -> MonetaryPolicy {
+        MonetaryPolicy::new(
+            self.block_reward,
+            self.reward_schedule.clone(),
+            self.max_supply,
+        )
+    }
+
+    // builds the engine described by the spec
+    pub fn consensus(&self) -> Box<dyn Consensus> {
+        match &self.consensus {
+            ConsensusSpec::ProofOfWork => Box::new(ProofOfWork::new(self.difficulty)),
+            ConsensusSpec::ProofOfStake {
+                stakes,
+                slot_duration,
+            } => Box::new(ProofOfStake::new(stakes.clone()).with_slot_duration(*slot_duration)),
+            ConsensusSpec::ProofOfAuthority { authorities, votes } => {
+                Box::new(ProofOfAuthority::new(authorities.clone()).with_votes(votes.clone()))
+            }
+        }
+    }
+}
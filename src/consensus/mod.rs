@@ -0,0 +1,49 @@
+mod poa;
+mod pos;
+mod pow;
+
+pub use poa::ProofOfAuthority;
+pub use pos::ProofOfStake;
+pub use pow::ProofOfWork;
+
+use crate::{
+    block::{Block, Header},
+    chain_spec::ConsensusSpec,
+};
+use k256::ecdsa::SigningKey;
+
+#[derive(Debug)]
+pub enum ConsensusError {
+    SignerRequired,
+    NotSealer,
+    NoValidators,
+    SlotTaken,
+    InvalidDifficulty, // more zero bytes than a hash has
+}
+
+// Decides who may append the next block and how that block is sealed.
+// `chain` is always the chain up to and including the parent of the block.
+pub trait Consensus: Send {
+    // the address that is allowed to seal a block at the given time, none if anyone may
+    fn sealer(&self, chain: &[Block], timestamp: u64) -> Option<String>;
+
+    // seals a block so that it will be accepted by verify
+    fn seal(
+        &self,
+        chain: &[Block],
+        block: &mut Block,
+        signer: Option<&SigningKey>,
+    ) -> Result<(), ConsensusError>;
+
+    // checks that a block was sealed according to the rules of the engine
+    fn verify(&self, chain: &[Block], block: &Block) -> bool;
+
+    // checks the seal of a header with only the headers before it, which is all a light
+    // client has. Engines that pick the sealer from the transactions of the chain can only
+    // check that it was signed by a sealer known from the genesis.
+    fn verify_header(&self, headers: &[Header], header: &Header) -> bool;
+
+    // the engine as it is after the chain, described as a spec it can be built from again.
+    // A snapshot keeps it so the transactions of the chain can be pruned.
+    fn checkpoint(&self, chain: &[Block]) -> ConsensusSpec;
+}
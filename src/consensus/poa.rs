@@ -0,0 +1,162 @@
+use super::{Consensus, ConsensusError};
+use crate::{
+    block::{Block, Header},
+    chain_spec::{ConsensusSpec, Vote},
+    keygen,
+    transaction::{Transaction, TransactionKind},
+};
+use k256::ecdsa::{SigningKey, VerifyingKey};
+use std::collections::{BTreeSet, HashMap};
+
+// for every proposal to add or remove an authority the authorities that voted for it
+type Votes = HashMap<(String, bool), BTreeSet<String>>;
+
+// Proof of authority where a known set of authorities take turns signing blocks.
+// Authorities are added or removed once more than half of them have voted for it with a
+// governance transaction.
+pub struct ProofOfAuthority {
+    genesis_authorities: BTreeSet<String>,
+    genesis_votes: Vec<Vote>, // votes cast in blocks that were pruned
+}
+
+impl ProofOfAuthority {
+    pub fn new(authorities: impl IntoIterator<Item = String>) -> Self {
+        Self {
+            genesis_authorities: authorities.into_iter().collect(),
+            genesis_votes: Vec::new(),
+        }
+    }
+
+    pub fn with_votes(mut self, votes: Vec<Vote>) -> Self {
+        self.genesis_votes = votes;
+        self
+    }
+
+    // the authorities after applying all the governance transactions in the chain
+    pub fn authorities(&self, chain: &[Block]) -> BTreeSet<String> {
+        self.governance(chain).0
+    }
+
+    // the authorities and the votes that have not reached a majority yet
+    fn governance(&self, chain: &[Block]) -> (BTreeSet<String>, Votes) {
+        let mut authorities = self.genesis_authorities.clone();
+        let mut votes: Votes = self
+            .genesis_votes
+            .iter()
+            .map(|vote| ((vote.candidate.clone(), vote.add), vote.voters.clone()))
+            .collect();
+
+        for transaction in chain.iter().flat_map(|block| block.transactions()) {
+            let Some(add) = Self::proposal(transaction) else {
+                continue;
+            };
+            let Some(voter) = transaction.from() else {
+                continue;
+            };
+
+            // only authorities get a say and proposals that change nothing are ignored
+            if !authorities.contains(voter) || authorities.contains(transaction.to()) == add {
+                continue;
+            }
+
+            let proposal = (transaction.to().to_owned(), add);
+            let voters = votes.entry(proposal.clone()).or_default();
+            voters.insert(voter.to_owned());
+
+            if voters.len() > authorities.len() / 2 {
+                votes.remove(&proposal);
+                if add {
+                    authorities.insert(proposal.0);
+                } else if authorities.len() > 1 {
+                    // the last authority can never be removed otherwise the chain would halt
+                    authorities.remove(&proposal.0);
+                }
+
+                // votes from removed authorities no longer count
+                for voters in votes.values_mut() {
+                    voters.retain(|voter| authorities.contains(voter));
+                }
+            }
+        }
+
+        (authorities, votes)
+    }
+
+    // if the transaction is a vote returns whether it is to add or remove an authority
+    fn proposal(transaction: &Transaction) -> Option<bool> {
+        match transaction.kind() {
+            TransactionKind::AddAuthority => Some(true),
+            TransactionKind::RemoveAuthority => Some(false),
+            _ => None,
+        }
+    }
+}
+
+impl Consensus for ProofOfAuthority {
+    // authorities take turns in order of their address by block height
+    fn sealer(&self, chain: &[Block], _timestamp: u64) -> Option<String> {
+        let authorities = self.authorities(chain);
+        if authorities.is_empty() {
+            return None;
+        }
+
+        let turn = chain.len() % authorities.len();
+        authorities.into_iter().nth(turn)
+    }
+
+    fn seal(
+        &self,
+        chain: &[Block],
+        block: &mut Block,
+        signer: Option<&SigningKey>,
+    ) -> Result<(), ConsensusError> {
+        let Some(signer) = signer else {
+            return Err(ConsensusError::SignerRequired);
+        };
+
+        let Some(authority) = self.sealer(chain, block.timestamp()) else {
+            return Err(ConsensusError::NoValidators);
+        };
+
+        if authority != keygen::address(&VerifyingKey::from(signer)) {
+            return Err(ConsensusError::NotSealer);
+        }
+
+        block.sign(signer);
+        Ok(())
+    }
+
+    fn verify(&self, chain: &[Block], block: &Block) -> bool {
+        block.valid_hash()
+            && block.valid_signiture()
+            && block.sealer().map(str::to_owned) == self.sealer(chain, block.timestamp())
+    }
+
+    // the votes are in the transactions so only the genesis authorities are known
+    fn verify_header(&self, _headers: &[Header], header: &Header) -> bool {
+        header.valid_signiture()
+            && header
+                .sealer
+                .as_ref()
+                .is_some_and(|sealer| self.genesis_authorities.contains(sealer))
+    }
+
+    fn checkpoint(&self, chain: &[Block]) -> ConsensusSpec {
+        let (authorities, votes) = self.governance(chain);
+        let mut votes: Vec<_> = votes
+            .into_iter()
+            .filter(|(_, voters)| !voters.is_empty())
+            .map(|((candidate, add), voters)| Vote {
+                candidate,
+                add,
+                voters,
+            })
+            .collect();
+        votes.sort_by(|a, b| (&a.candidate, a.add).cmp(&(&b.candidate, b.add)));
+
+        ConsensusSpec::ProofOfAuthority {
+            authorities: authorities.into_iter().collect(),
+            votes,
+        }
+    }
+}
@@ -0,0 +1,145 @@
+use super::{Consensus, ConsensusError};
+use crate::{
+    block::{Block, Header},
+    chain_spec::{ConsensusSpec, SLOT_DURATION},
+    keygen,
+};
+use k256::ecdsa::{SigningKey, VerifyingKey};
+use sha2::Digest;
+use std::collections::BTreeMap;
+
+// Proof of stake where time is divided into slots and every slot has one validator.
+// The validator is picked from the stakers weighted by their stake using the hash of
+// the parent block and the slot number, so every node picks the same one.
+pub struct ProofOfStake {
+    initial_stakes: BTreeMap<String, u64>, // needed so that the first blocks can be signed
+    slot_duration: u64,
+}
+
+impl ProofOfStake {
+    pub fn new(initial_stakes: impl IntoIterator<Item = (String, u64)>) -> Self {
+        Self {
+            initial_stakes: initial_stakes.into_iter().collect(),
+            slot_duration: SLOT_DURATION,
+        }
+    }
+
+    pub fn with_slot_duration(mut self, slot_duration: u64) -> Self {
+        self.slot_duration = slot_duration.max(1);
+        self
+    }
+
+    pub fn slot(&self, timestamp: u64) -> u64 {
+        timestamp / self.slot_duration
+    }
+
+    // all the stake locked by each address in the chain
+    pub fn stakes(&self, chain: &[Block]) -> BTreeMap<String, u64> {
+        let mut stakes = self.initial_stakes.clone();
+
+        for block in chain {
+            for transaction in block.transactions() {
+                // a stake to anyone else is not valid and gives no weight
+                match transaction.from() {
+                    Some(from) if transaction.is_stake() && from == transaction.to() => {
+                        *stakes.entry(from.to_owned()).or_default() += transaction.amount();
+                    }
+                    _ => {}
+                }
+            }
+        }
+
+        stakes
+    }
+
+    // picks the validator for a slot
+    pub fn validator(&self, chain: &[Block], slot: u64) -> Option<String> {
+        let stakes = self.stakes(chain);
+        let total: u64 = stakes.values().sum();
+        if total == 0 {
+            return None;
+        }
+
+        let prev_hash = chain.last().map(|block| block.hash()).unwrap_or_default();
+        let seed: [u8; 32] =
+            sha2::Sha256::digest([&prev_hash[..], &slot.to_be_bytes()].concat()).into();
+        let mut pick = u64::from_be_bytes(seed[..8].try_into().unwrap()) % total;
+
+        for (address, stake) in stakes {
+            if pick < stake {
+                return Some(address);
+            }
+            pick -= stake;
+        }
+
+        None
+    }
+}
+
+impl Consensus for ProofOfStake {
+    fn sealer(&self, chain: &[Block], timestamp: u64) -> Option<String> {
+        self.validator(chain, self.slot(timestamp))
+    }
+
+    fn seal(
+        &self,
+        chain: &[Block],
+        block: &mut Block,
+        signer: Option<&SigningKey>,
+    ) -> Result<(), ConsensusError> {
+        let Some(signer) = signer else {
+            return Err(ConsensusError::SignerRequired);
+        };
+
+        if let Some(parent) = chain.last() {
+            if self.slot(block.timestamp()) <= self.slot(parent.timestamp()) {
+                return Err(ConsensusError::SlotTaken);
+            }
+        }
+
+        let Some(validator) = self.sealer(chain, block.timestamp()) else {
+            return Err(ConsensusError::NoValidators);
+        };
+
+        if validator != keygen::address(&VerifyingKey::from(signer)) {
+            return Err(ConsensusError::NotSealer);
+        }
+
+        block.sign(signer);
+        Ok(())
+    }
+
+    fn verify(&self, chain: &[Block], block: &Block) -> bool {
+        if let Some(parent) = chain.last() {
+            if self.slot(block.timestamp()) <= self.slot(parent.timestamp()) {
+                return false;
+            }
+        }
+
+        block.valid_hash()
+            && block.valid_signiture()
+            && block.sealer().map(str::to_owned) == self.sealer(chain, block.timestamp())
+    }
+
+    // the stakes are in the transactions so only the initial stakers are known
+    fn verify_header(&self, headers: &[Header], header: &Header) -> bool {
+        if let Some(parent) = headers.last() {
+            if self.slot(header.timestamp) <= self.slot(parent.timestamp) {
+                return false;
+            }
+        }
+
+        header.valid_signiture()
+            && header
+                .sealer
+                .as_ref()
+                .is_some_and(|sealer| self.initial_stakes.contains_key(sealer))
+    }
+
+    fn checkpoint(&self, chain: &[Block]) -> ConsensusSpec {
+        ConsensusSpec::ProofOfStake {
+            stakes: self.stakes(chain),
+            slot_duration: self.slot_duration,
+        }
+    }
+}
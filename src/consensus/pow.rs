@@ -0,0 +1,67 @@
+use super::{Consensus, ConsensusError};
+use crate::{
+    block::{Block, Hash, Header},
+    chain_spec::{ConsensusSpec, MAX_DIFFICULTY},
+};
+use k256::ecdsa::SigningKey;
+
+const DIFFICULTY: usize = 2; // how many bytes need to be 0
+
+// Proof of work where the hash of a block must start with a number of zero bytes
+pub struct ProofOfWork {
+    difficulty: usize,
+}
+
+impl Default for ProofOfWork {
+    fn default() -> Self {
+        Self::new(DIFFICULTY)
+    }
+}
+
+impl ProofOfWork {
+    pub fn new(difficulty: usize) -> Self {
+        Self { difficulty }
+    }
+
+    pub fn difficulty(&self) -> usize {
+        self.difficulty
+    }
+
+    // a difficulty above what a hash can meet accepts no block
+    fn meets(&self, hash: Hash) -> bool {
+        hash.get(..self.difficulty)
+            .is_some_and(|prefix| prefix.iter().all(|byte| *byte == 0))
+    }
+}
+
+impl Consensus for ProofOfWork {
+    fn sealer(&self, _chain: &[Block], _timestamp: u64) -> Option<String> {
+        None
+    }
+
+    fn seal(
+        &self,
+        _chain: &[Block],
+        block: &mut Block,
+        _signer: Option<&SigningKey>,
+    ) -> Result<(), ConsensusError> {
+        if self.difficulty > MAX_DIFFICULTY {
+            return Err(ConsensusError::InvalidDifficulty);
+        }
+
+        block.mine(self.difficulty);
+        Ok(())
+    }
+
+    fn verify(&self, _chain: &[Block], block: &Block) -> bool {
+        block.valid_hash() && self.meets(block.hash())
+    }
+
+    fn verify_header(&self, _headers: &[Header], header: &Header) -> bool {
+        self.meets(header.hash())
+    }
+
+    fn checkpoint(&self, _chain: &[Block]) -> ConsensusSpec {
+        ConsensusSpec::ProofOfWork
+    }
+}
@@ -0,0 +1,108 @@
+use crate::{
+    asset::AssetId,
+    block::Hash,
+    encoding::{hex, Decode, DecodeError, Encode, Reader},
+    transaction::{Lock, Transaction, TransactionKind},
+};
+
+pub const ESCROW_PREFIX: &str = "escrow:";
+
+// Coins a buyer locked for a seller. They are held by the address of the escrow until they
+// are released to the seller, either by the buyer with the countersigniture of the seller or
+// by the arbiter on their own, or refunded to the buyer once the timeout height is reached.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Escrow {
+    pub hash: Hash, // the hash of the transaction that opened it
+    pub buyer: String,
+    pub seller: String,
+    pub arbiter: String,
+    pub amount: u64,
+    pub asset: Option<AssetId>,
+    pub timeout: u64,
+}
+
+impl Escrow {
+    // the escrow a transaction opens if it is one
+    pub fn of(transaction: &Transaction) -> Option<Self> {
+        let TransactionKind::Escrow { arbiter, timeout } = transaction.kind() else {
+            return None;
+        };
+
+        Some(Self {
+            hash: transaction.hash(),
+            buyer: transaction.from().clone()?,
+            seller: transaction.to().to_owned(),
+            arbiter: arbiter.clone(),
+            amount: transaction.amount(),
+            asset: transaction.asset(),
+            timeout: *timeout,
+        })
+    }
+
+    // the address that holds the coins of the escrow opened by the transaction with the hash
+    pub fn address(hash: &Hash) -> String {
+        format!("{}{}", ESCROW_PREFIX, hex(hash))
+    }
+
+    // pays the seller, it has to be signed by the buyer and countersigned by the seller
+    pub fn release(&self) -> Transaction {
+        self.payout(&self.buyer, &self.seller, self.release_kind())
+    }
+
+    // pays the seller in a dispute, it only has to be signed by the arbiter
+    pub fn arbitrate(&self) -> Transaction {
+        self.payout(&self.arbiter, &self.seller, self.release_kind())
+    }
+
+    // pays the buyer back, it is signed by the buyer and can not be mined before the timeout
+    pub fn refund(&self) -> Transaction {
+        self.payout(
+            &self.buyer,
+            &self.buyer,
+            TransactionKind::Refund { escrow: self.hash },
+        )
+        .with_lock(Lock::Height(self.timeout))
+    }
+
+    fn release_kind(&self) -> TransactionKind {
+        TransactionKind::Release {
+            escrow: self.hash,
+            signiture: None,
+        }
+    }
+
+    // the whole escrow in the asset it holds
+    fn payout(&self, from: &str, to: &str, kind: TransactionKind) -> Transaction {
+        let transaction = Transaction::new(Some(from.to_owned()), to.to_owned(), self.amount, kind);
+        match self.asset {
+            Some(asset) => transaction.with_asset(asset),
+            None => transaction,
+        }
+    }
+}
+
+impl Encode for Escrow {
+    fn encode(&self, out: &mut Vec<u8>) {
+        self.hash.encode(out);
+        self.buyer.encode(out);
+        self.seller.encode(out);
+        self.arbiter.encode(out);
+        self.amount.encode(out);
+        self.asset.as_ref().encode(out);
+        self.timeout.encode(out);
+    }
+}
+
+impl Decode for Escrow {
+    fn decode(reader: &mut Reader) -> Result<Self, DecodeError> {
+        Ok(Self {
+            hash: reader.hash()?,
+            buyer: reader.string()?,
+            seller: reader.string()?,
+            arbiter: reader.string()?,
+            amount: reader.u64()?,
+            asset: reader.option(Reader::hash)?,
+            timeout: reader.u64()?,
+        })
+    }
+}
@@ -0,0 +1,167 @@
+use crate::{
+    block::{Block, Hash},
+    transaction::Transaction,
+};
+use std::{
+    collections::BTreeSet,
+    fmt,
+    sync::mpsc::{self, Receiver, Sender},
+};
+
+// Something that happened to a blockchain that subscribers are told about
+#[derive(Debug, Clone)]
+pub enum Event {
+    // a block was mined or imported, including the blocks of a branch the chain switched to
+    NewBlock {
+        height: u64,
+        block: Block,
+    },
+    // a transaction was added to the mempool
+    NewTransaction(Transaction),
+    // a pending loan was signed by its payee and can be included in a block
+    LoanSigned(Transaction),
+    // a repayment was included in a block, remaining is what the borrower still owes after it
+    LoanRepaid {
+        height: u64,
+        transaction: Transaction,
+        remaining: u64,
+    },
+    // the chain switched to a longer branch that forks off after the block at the height
+    Reorg {
+        height: u64,
+        removed: Vec<Hash>,
+        added: Vec<Hash>,
+    },
+    // a pending transaction left the mempool without being included in a block
+    TransactionEvicted {
+        transaction: Transaction,
+        reason: Eviction,
+    },
+    // a pending transaction was replaced by one from the same sender with the same nonce and a
+    // higher fee, which may be a cancellation
+    TransactionReplaced {
+        replaced: Transaction,
+        by: Hash,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Eviction {
+    Full,    // the mempool was full and it paid the lowest fee
+    Expired, // it waited longer than the mempool keeps transactions
+    Invalid, // a block made it invalid, for example by using its nonce or spending its inputs
+}
+
+impl Eviction {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Eviction::Full => "full",
+            Eviction::Expired => "expired",
+            Eviction::Invalid => "invalid",
+        }
+    }
+}
+
+impl Event {
+    // the name clients filter by
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Event::NewBlock { .. } => "new_block",
+            Event::NewTransaction(_) => "new_transaction",
+            Event::LoanSigned(_) => "loan_signed",
+            Event::LoanRepaid { .. } => "loan_repaid",
+            Event::Reorg { .. } => "reorg",
+            Event::TransactionEvicted { .. } => "transaction_evicted",
+            Event::TransactionReplaced { .. } => "transaction_replaced",
+        }
+    }
+
+    // the addresses the event involves, none for a reorg as it may change any of them
+    pub fn addresses(&self) -> Option<BTreeSet<String>> {
+        match self {
+            Event::NewBlock { block, .. } => Some(
+                block
+                    .transactions()
+                    .iter()
+                    .flat_map(Transaction::addresses)
+                    .chain(block.sealer().map(str::to_owned))
+                    .collect(),
+            ),
+            Event::NewTransaction(transaction)
+            | Event::LoanSigned(transaction)
+            | Event::LoanRepaid { transaction, .. }
+            | Event::TransactionEvicted { transaction, .. }
+            | Event::TransactionReplaced {
+                replaced: transaction,
+                ..
+            } => Some(transaction.addresses()),
+            Event::Reorg { .. } => None,
+        }
+    }
+}
+
+// Which events a subscriber wants, an empty set lets everything through
+#[derive(Debug, Clone, Default)]
+pub struct Filter {
+    pub kinds: BTreeSet<String>,
+    pub addresses: BTreeSet<String>,
+}
+
+impl Filter {
+    pub fn with_kind(mut self, kind: impl Into<String>) -> Self {
+        self.kinds.insert(kind.into());
+        self
+    }
+
+    pub fn with_address(mut self, address: impl Into<String>) -> Self {
+        self.addresses.insert(address.into());
+        self
+    }
+
+    pub fn matches(&self, event: &Event) -> bool {
+        (self.kinds.is_empty() || self.kinds.contains(event.kind()))
+            && (self.addresses.is_empty()
+                || event
+                    .addresses()
+                    .is_none_or(|addresses| !addresses.is_disjoint(&self.addresses)))
+    }
+}
+
+type Callback = Box<dyn FnMut(&Event) + Send>;
+
+// Hands every event to each subscriber and callback, subscribers that have gone away are
+// dropped. Callbacks run on the thread that changed the chain before the change returns.
+#[derive(Default)]
+pub struct EventBus {
+    subscribers: Vec<Sender<Event>>,
+    callbacks: Vec<Callback>,
+}
+
+impl EventBus {
+    pub fn subscribe(&mut self) -> Receiver<Event> {
+        let (sender, receiver) = mpsc::channel();
+        self.subscribers.push(sender);
+        receiver
+    }
+
+    pub fn on_event(&mut self, callback: impl FnMut(&Event) + Send + 'static) {
+        self.callbacks.push(Box::new(callback));
+    }
+
+    pub fn publish(&mut self, event: Event) {
+        for callback in &mut self.callbacks {
+            callback(&event);
+        }
+        self.subscribers
+            .retain(|subscriber| subscriber.send(event.clone()).is_ok());
+    }
+}
+
+impl fmt::Debug for EventBus {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("EventBus")
+            .field("subscribers", &self.subscribers.len())
+            .field("callbacks", &self.callbacks.len())
+            .finish()
+    }
+}
@@ -0,0 +1,800 @@
+use crate::{
+    block::{Block, Hash},
+    blockchain::{Blockchain, BlockchainError},
+    encoding::{hex, parse_hex},
+    events::{Event, Filter},
+    storage::Location,
+    transaction::{Lock, Transaction},
+};
+use serde_json::{json, Value};
+use std::{
+    fmt::Write,
+    sync::{Arc, Mutex},
+    thread,
+};
+use tiny_http::{Header, Method, Request, Server};
+use tungstenite::{handshake::derive_accept_key, protocol::Role, Message, WebSocket};
+
+const PAGE: u64 = 20; // how many blocks a list shows at once
+
+#[derive(Debug)]
+pub enum ExplorerError {
+    Bind(String), // what the server reported
+}
+
+// What the explorer answers a request with
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Response {
+    pub status: u16,
+    pub content_type: &'static str,
+    pub location: Option<String>, // where a redirect points
+    pub body: String,
+}
+
+impl Response {
+    fn json(status: u16, body: Value) -> Self {
+        Self {
+            status,
+            content_type: "application/json",
+            location: None,
+            body: body.to_string(),
+        }
+    }
+
+    fn html(status: u16, title: &str, body: &str) -> Self {
+        Self {
+            status,
+            content_type: "text/html; charset=utf-8",
+            location: None,
+            body: layout(title, body),
+        }
+    }
+
+    fn redirect(location: String) -> Self {
+        Self {
+            status: 303,
+            content_type: "text/plain",
+            location: Some(location),
+            body: String::new(),
+        }
+    }
+}
+
+// why a request could not be answered
+enum Error {
+    NotFound,
+    BadRequest(&'static str),
+    Chain(BlockchainError),
+}
+
+impl Error {
+    fn status(&self) -> u16 {
+        match self {
+            Error::NotFound => 404,
+            Error::BadRequest(_) => 400,
+            Error::Chain(_) => 500,
+        }
+    }
+
+    fn message(&self) -> String {
+        match self {
+            Error::NotFound => "Not found".into(),
+            Error::BadRequest(message) => (*message).into(),
+            Error::Chain(error) => format!("{:?}", error),
+        }
+    }
+}
+
+impl From<BlockchainError> for Error {
+    fn from(error: BlockchainError) -> Self {
+        Error::Chain(error)
+    }
+}
+
+// A block explorer served over HTTP. The REST API is under /api and answers with JSON, every
+// other path is a page of the web UI rendered on the server.
+//
+// GET /api                               the latest block, the supply and the mempool size
+// GET /api/blocks?before=<height>        the blocks below the height, newest first
+// GET /api/blocks/<height or hash>       a block with its transactions
+// GET /api/transactions/<hash>           a transaction on the chain or in the mempool
+// GET /api/addresses/<address>           the balances, stake, debts and history of an address
+// GET /api/addresses/<address>/loans     the loans to an address on the chain and pending
+// GET /api/mempool                       the pending transactions
+// GET /api/events?kind=..&address=..     a WebSocket that streams the events of the chain
+pub struct Explorer {
+    server: Server,
+}
+
+impl Explorer {
+    pub fn bind(address: &str) -> Result<Self, ExplorerError> {
+        let server = Server::http(address).map_err(|e| ExplorerError::Bind(e.to_string()))?;
+        Ok(Self { server })
+    }
+
+    // the address it listens on, which has the port that was picked if it was bound to 0
+    pub fn address(&self) -> String {
+        self.server.server_addr().to_string()
+    }
+
+    // answers requests until the server fails, the chain is only locked while one is answered
+    pub fn run(self, chain: Arc<Mutex<Blockchain>>) {
+        for request in self.server.incoming_requests() {
+            if is_upgrade(&request) {
+                stream_events(request, &chain);
+                continue;
+            }
+
+            let response = match request.method() {
+                Method::Get => handle(&chain.lock().unwrap(), request.url()),
+                _ => Response::json(405, json!({ "error": "Only GET is supported" })),
+            };
+
+            let mut reply = tiny_http::Response::from_string(response.body)
+                .with_status_code(response.status)
+                .with_header(header("Content-Type", response.content_type));
+            if let Some(location) = &response.location {
+                reply.add_header(header("Location", location));
+            }
+            let _ = request.respond(reply);
+        }
+    }
+}
+
+fn is_upgrade(request: &Request) -> bool {
+    request.method() == &Method::Get
+        && request.url().split('?').next() == Some("/api/events")
+        && request
+            .headers()
+            .iter()
+            .any(|header| header.field.equiv("Upgrade") && header.value == "websocket")
+}
+
+// Accepts a WebSocket and sends it the events that match the filter of its query as JSON
+// text messages from its own thread. A client that went away is noticed at the next event.
+fn stream_events(request: Request, chain: &Mutex<Blockchain>) {
+    let key = request
+        .headers()
+        .iter()
+        .find(|header| header.field.equiv("Sec-WebSocket-Key"))
+        .map(|header| derive_accept_key(header.value.as_bytes()));
+    let Some(accept) = key else {
+        let reply =
+            tiny_http::Response::from_string("Missing Sec-WebSocket-Key").with_status_code(400);
+        let _ = request.respond(reply);
+        return;
+    };
+
+    let query = request.url().split_once('?').map_or("", |(_, query)| query);
+    let filter = event_filter(query);
+    let events = chain.lock().unwrap().subscribe();
+    let reply =
+        tiny_http::Response::empty(101).with_header(header("Sec-WebSocket-Accept", &accept));
+    let stream = request.upgrade("websocket", reply);
+
+    thread::spawn(move || {
+        let mut socket = WebSocket::from_raw_socket(stream, Role::Server, None);
+        for event in events.iter().filter(|event| filter.matches(event)) {
+            if socket
+                .send(Message::text(event_json(&event).to_string()))
+                .is_err()
+            {
+                break;
+            }
+        }
+    });
+}
+
+// the kinds and addresses to filter by, each may be given more than once or separated by
+// commas
+pub fn event_filter(query: &str) -> Filter {
+    let values = |key| {
+        query_values(query, key)
+            .into_iter()
+            .flat_map(|value| value.split(',').map(str::to_owned).collect::<Vec<_>>())
+            .filter(|value| !value.is_empty())
+            .collect()
+    };
+
+    Filter {
+        kinds: values("kind"),
+        addresses: values("address"),
+    }
+}
+
+pub fn event_json(event: &Event) -> Value {
+    let hashes = |hashes: &[Hash]| hashes.iter().map(|hash| hex(hash)).collect::<Vec<_>>();
+    let mut json = match event {
+        Event::NewBlock { height, block } => json!({ "block": block_json(*height, block, true) }),
+        Event::NewTransaction(transaction) | Event::LoanSigned(transaction) => {
+            json!({ "transaction": transaction_json(transaction) })
+        }
+        Event::LoanRepaid {
+            height,
+            transaction,
+            remaining,
+        } => json!({
+            "height": height,
+            "transaction": transaction_json(transaction),
+            "remaining": remaining,
+        }),
+        Event::Reorg {
+            height,
+            removed,
+            added,
+        } => json!({ "height": height, "removed": hashes(removed), "added": hashes(added) }),
+        Event::TransactionEvicted {
+            transaction,
+            reason,
+        } => json!({ "transaction": transaction_json(transaction), "reason": reason.name() }),
+        Event::TransactionReplaced { replaced, by } => {
+            json!({ "transaction": transaction_json(replaced), "by": hex(by) })
+        }
+    };
+    json["type"] = event.kind().into();
+
+    json
+}
+
+fn header(name: &str, value: &str) -> Header {
+    Header::from_bytes(name, value).expect("Header values should be ascii")
+}
+
+// answers a GET request for a url, which is the path with an optional query
+pub fn handle(chain: &Blockchain, url: &str) -> Response {
+    let (path, query) = url.split_once('?').unwrap_or((url, ""));
+    let segments: Vec<String> = path
+        .split('/')
+        .filter(|segment| !segment.is_empty())
+        .map(percent_decode)
+        .collect();
+    let segments: Vec<&str> = segments.iter().map(String::as_str).collect();
+
+    match segments.as_slice() {
+        ["api", route @ ..] => match api(chain, route, query) {
+            Ok(body) => Response::json(200, body),
+            Err(error) => Response::json(error.status(), json!({ "error": error.message() })),
+        },
+        route => page(chain, route, query).unwrap_or_else(|error| {
+            let body = format!("<p>{}</p>", escape(&error.message()));
+            Response::html(error.status(), "Error", &body)
+        }),
+    }
+}
+
+fn api(chain: &Blockchain, route: &[&str], query: &str) -> Result<Value, Error> {
+    match route {
+        [] => Ok(status_json(chain)),
+        ["blocks"] => {
+            let blocks = page_of_blocks(chain, query)?;
+            let blocks = blocks
+                .iter()
+                .map(|(height, block)| block_json(*height, block, false));
+            Ok(json!({ "blocks": blocks.collect::<Vec<_>>() }))
+        }
+        ["blocks", id] => {
+            let (height, block) = find_block(chain, id)?;
+            Ok(block_json(height, &block, true))
+        }
+        ["transactions", hash] => {
+            let (location, transaction) = find_transaction(chain, hash)?;
+            let mut json = transaction_json(&transaction);
+            json["location"] = location_json(location.as_ref());
+            Ok(json)
+        }
+        ["addresses", address] => address_json(chain, address),
+        ["addresses", address, "loans"] => loans_json(chain, address),
+        ["events"] => Err(Error::BadRequest("Events are streamed over a WebSocket")),
+        ["mempool"] => Ok(json!({
+            "count": chain.mempool().len(),
+            "bytes": chain.mempool().bytes(),
+            "transactions": chain.mempool().iter().map(transaction_json).collect::<Vec<_>>(),
+        })),
+        _ => Err(Error::NotFound),
+    }
+}
+
+fn status_json(chain: &Blockchain) -> Value {
+    let latest = chain.latest_block();
+    json!({
+        "chain_id": chain.spec().chain_id,
+        "height": chain.blocks().len() - 1,
+        "latest": hex(&latest.hash()),
+        "state_root": hex(&latest.state_root()),
+        "pruned_height": chain.pruned_height(),
+        "total_supply": chain.total_supply(),
+        "circulating_supply": chain.circulating_supply(),
+        "pending": chain.mempool().len(),
+    })
+}
+
+fn block_json(height: u64, block: &Block, transactions: bool) -> Value {
+    let mut json = json!({
+        "height": height,
+        "hash": hex(&block.hash()),
+        "prev_hash": hex(&block.prev_hash()),
+        "timestamp": block.timestamp(),
+        "transactions_root": hex(&block.transactions_root()),
+        "state_root": hex(&block.state_root()),
+        "nonce": block.nonce(),
+        "sealer": block.sealer(),
+        "pruned": block.is_pruned(),
+        "transaction_count": block.transactions().len(),
+    });
+    if transactions {
+        json["transactions"] = block.transactions().iter().map(transaction_json).collect();
+    }
+
+    json
+}
+
+fn transaction_json(transaction: &Transaction) -> Value {
+    json!({
+        "hash": hex(&transaction.hash()),
+        "kind": transaction.kind().name(),
+        "from": transaction.from(),
+        "to": transaction.to(),
+        "amount": transaction.amount(),
+        "fee": transaction.fee(),
+        "nonce": transaction.nonce(),
+        "asset": transaction.asset().map(|asset| hex(&asset)),
+        "lock": transaction.lock().map(|lock| match lock {
+            Lock::Height(height) => json!({ "height": height }),
+            Lock::Time(time) => json!({ "time": time }),
+        }),
+        "loan_signed": transaction.is_loan().then(|| transaction.loan_signed()),
+    })
+}
+
+// none is a pending transaction
+fn location_json(location: Option<&Location>) -> Value {
+    match location {
+        Some(location) => json!({ "height": location.height, "index": location.index }),
+        None => Value::Null,
+    }
+}
+
+fn address_json(chain: &Blockchain, address: &str) -> Result<Value, Error> {
+    let account = chain.state().account(address).unwrap_or_default();
+    let history = chain.history_of(address)?;
+
+    Ok(json!({
+        "address": address,
+        "balances": account.balances.iter().map(|(asset, amount)| json!({
+            "asset": asset.map(|asset| hex(&asset)),
+            "amount": amount,
+        })).collect::<Vec<_>>(),
+        "nonce": account.nonce,
+        "next_nonce": chain.next_nonce(address),
+        "stake": account.stake,
+        "debts": account.debts.iter().map(|((lender, asset), amount)| json!({
+            "lender": lender,
+            "asset": asset.map(|asset| hex(&asset)),
+            "amount": amount,
+        })).collect::<Vec<_>>(),
+        "transactions": history.iter().map(|(location, transaction)| {
+            let mut json = transaction_json(transaction);
+            json["location"] = location_json(Some(location));
+            json
+        }).collect::<Vec<_>>(),
+        "pending": chain.mempool().iter()
+            .filter(|transaction| transaction.involves(address))
+            .map(transaction_json)
+            .collect::<Vec<_>>(),
+    }))
+}
+
+fn loans_json(chain: &Blockchain, address: &str) -> Result<Value, Error> {
+    Ok(json!({
+        "address": address,
+        "loans": chain.all_loans_of(address)?.iter()
+            .map(|(hash, (to, amount))| loan_json(hash, to, *amount))
+            .collect::<Vec<_>>(),
+        "signed": chain.loans_of(address, true).iter()
+            .map(|(hash, (to, amount))| loan_json(hash, to, *amount))
+            .collect::<Vec<_>>(),
+        "unsigned": chain.loans_of(address, false).iter()
+            .map(|(hash, (to, amount))| loan_json(hash, to, *amount))
+            .collect::<Vec<_>>(),
+    }))
+}
+
+fn loan_json(hash: &Hash, to: &str, amount: u64) -> Value {
+    json!({ "hash": hex(hash), "to": to, "amount": amount })
+}
+
+// the blocks below the height in ?before=, or the latest ones, newest first
+fn page_of_blocks(chain: &Blockchain, query: &str) -> Result<Vec<(u64, Block)>, Error> {
+    let next = chain.blocks().len() as u64;
+    let before = match query_value(query, "before") {
+        Some(before) => before
+            .parse::<u64>()
+            .map_err(|_| Error::BadRequest("The height should be a number"))?
+            .min(next),
+        None => next,
+    };
+
+    let mut blocks = Vec::new();
+    for height in (before.saturating_sub(PAGE)..before).rev() {
+        let block = chain.block(height)?.ok_or(Error::NotFound)?;
+        blocks.push((height, block));
+    }
+
+    Ok(blocks)
+}
+
+// a block by its height or the hex of its hash
+fn find_block(chain: &Blockchain, id: &str) -> Result<(u64, Block), Error> {
+    let height = match id.parse::<u64>() {
+        Ok(height) => height,
+        Err(_) => chain.height_of(&parse_hash(id)?)?.ok_or(Error::NotFound)?,
+    };
+    let block = chain.block(height)?.ok_or(Error::NotFound)?;
+
+    Ok((height, block))
+}
+
+// a transaction on the chain with where it is, or one that is pending
+fn find_transaction(
+    chain: &Blockchain,
+    hash: &str,
+) -> Result<(Option<Location>, Transaction), Error> {
+    let hash = parse_hash(hash)?;
+    if let Some((location, transaction)) = chain.transaction(&hash)? {
+        return Ok((Some(location), transaction));
+    }
+
+    chain
+        .mempool()
+        .get(&hash)
+        .map(|transaction| (None, transaction.clone()))
+        .ok_or(Error::NotFound)
+}
+
+fn parse_hash(hex: &str) -> Result<Hash, Error> {
+    parse_hex(hex)
+        .and_then(|bytes| bytes.try_into().ok())
+        .ok_or(Error::BadRequest("A hash should be 64 hex digits"))
+}
+
+fn page(chain: &Blockchain, route: &[&str], query: &str) -> Result<Response, Error> {
+    match route {
+        [] => {
+            let mut body = String::new();
+            let status = status_json(chain);
+            let _ = write!(
+                body,
+                "<p>Height {} &middot; Total supply {} &middot; Circulating {} &middot; \
+                 <a href=\"/mempool\">{} pending</a></p>",
+                status["height"],
+                status["total_supply"],
+                status["circulating_supply"],
+                status["pending"],
+            );
+            let blocks = page_of_blocks(chain, query)?;
+            body += &blocks_table(&blocks);
+            if let Some((height, _)) = blocks.last().filter(|(height, _)| *height > 0) {
+                let _ = write!(
+                    body,
+                    "<p><a href=\"/?before={}\">Older blocks</a></p>",
+                    height
+                );
+            }
+            Ok(Response::html(200, "Latest blocks", &body))
+        }
+        ["blocks", id] => {
+            let (height, block) = find_block(chain, id)?;
+            let mut body = String::new();
+            let _ = write!(
+                body,
+                "<table>{}{}{}{}{}{}{}</table>",
+                row("Hash", &hex(&block.hash())),
+                row_html(
+                    "Previous",
+                    &block_link(height.saturating_sub(1), &hex(&block.prev_hash()))
+                ),
+                row("Timestamp", &block.timestamp().to_string()),
+                row("Transactions root", &hex(&block.transactions_root())),
+                row("State root", &hex(&block.state_root())),
+                row("Nonce", &block.nonce().to_string()),
+                row_html(
+                    "Sealer",
+                    &block.sealer().map(address_link).unwrap_or_default()
+                ),
+            );
+            if block.is_pruned() {
+                body += "<p>The transactions of this block were pruned.</p>";
+            } else {
+                body += &transactions_table(block.transactions().iter().map(|t| (None, t)));
+            }
+            Ok(Response::html(200, &format!("Block {}", height), &body))
+        }
+        ["transactions", hash] => {
+            let (location, transaction) = find_transaction(chain, hash)?;
+            let status = match location {
+                Some(location) => format!(
+                    "{} at index {}",
+                    block_link(location.height, &location.height.to_string()),
+                    location.index
+                ),
+                None => "Pending".into(),
+            };
+            let body = format!(
+                "<table>{}{}{}{}{}{}{}{}{}</table>",
+                row("Hash", &hex(&transaction.hash())),
+                row_html("Status", &status),
+                row("Kind", transaction.kind().name()),
+                row_html(
+                    "From",
+                    &transaction
+                        .from()
+                        .as_deref()
+                        .map(address_link)
+                        .unwrap_or_else(|| "Coinbase".into())
+                ),
+                row_html("To", &address_link(transaction.to())),
+                row("Amount", &transaction.amount().to_string()),
+                row(
+                    "Asset",
+                    &transaction
+                        .asset()
+                        .map(|asset| hex(&asset))
+                        .unwrap_or_else(|| "Coin".into())
+                ),
+                row("Fee", &transaction.fee().to_string()),
+                row("Nonce", &transaction.nonce().to_string()),
+            );
+            Ok(Response::html(200, "Transaction", &body))
+        }
+        ["addresses", address] => {
+            let json = address_json(chain, address)?;
+            let loans = loans_json(chain, address)?;
+            let mut body = format!("<p class=\"hash\">{}</p><table>", escape(address));
+            for balance in json["balances"].as_array().into_iter().flatten() {
+                let asset = balance["asset"].as_str().unwrap_or("Coin");
+                body += &row(
+                    &format!("Balance ({})", asset),
+                    &balance["amount"].to_string(),
+                );
+            }
+            body += &row("Nonce", &json["nonce"].to_string());
+            body += &row("Stake", &json["stake"].to_string());
+            for debt in json["debts"].as_array().into_iter().flatten() {
+                let lender = debt["lender"].as_str().unwrap_or_default();
+                body += &row_html(
+                    "Owes",
+                    &format!("{} to {}", debt["amount"], address_link(lender)),
+                );
+            }
+            body +=
+                "</table><h2>Loans</h2><table><tr><th>Hash</th><th>Amount</th><th>Status</th></tr>";
+            for (key, status) in [
+                ("loans", "Mined"),
+                ("signed", "Signed"),
+                ("unsigned", "Unsigned"),
+            ] {
+                for loan in loans[key].as_array().into_iter().flatten() {
+                    let hash = loan["hash"].as_str().unwrap_or_default();
+                    let _ = write!(
+                        body,
+                        "<tr><td>{}</td><td>{}</td><td>{}</td></tr>",
+                        transaction_link(hash),
+                        loan["amount"],
+                        status
+                    );
+                }
+            }
+            body += "</table><h2>Transactions</h2>";
+            let history = chain.history_of(address)?;
+            body += &transactions_table(history.iter().map(|(location, t)| (Some(location), t)));
+            let pending: Vec<_> = chain
+                .mempool()
+                .iter()
+                .filter(|t| t.involves(address))
+                .collect();
+            if !pending.is_empty() {
+                body += "<h2>Pending</h2>";
+                body += &transactions_table(pending.into_iter().map(|t| (None, t)));
+            }
+            Ok(Response::html(200, "Address", &body))
+        }
+        ["mempool"] => {
+            let body = format!(
+                "<p>{} transactions, {} bytes</p>{}",
+                chain.mempool().len(),
+                chain.mempool().bytes(),
+                transactions_table(chain.mempool().iter().map(|t| (None, t)))
+            );
+            Ok(Response::html(200, "Mempool", &body))
+        }
+        // a height or hash goes to its block or transaction, anything else is an address
+        ["search"] => {
+            let query = query_value(query, "q").unwrap_or_default();
+            let query = query.trim();
+            if query.is_empty() {
+                return Err(Error::BadRequest("Enter a height, hash or address"));
+            }
+            let location = if query.parse::<u64>().is_ok() {
+                format!("/blocks/{}", query)
+            } else if let Ok(hash) = parse_hash(query) {
+                match chain.height_of(&hash)? {
+                    Some(height) => format!("/blocks/{}", height),
+                    None => format!("/transactions/{}", query),
+                }
+            } else {
+                format!("/addresses/{}", percent_encode(query))
+            };
+            Ok(Response::redirect(location))
+        }
+        _ => Err(Error::NotFound),
+    }
+}
+
+fn layout(title: &str, body: &str) -> String {
+    format!(
+        "<!DOCTYPE html>
+<html>
+<head>
+<meta charset=\"utf-8\">
+<title>{title} - Explorer</title>
+<style>
+body {{ font-family: sans-serif; margin: 2em auto; max-width: 64em; padding: 0 1em; }}
+table {{ border-collapse: collapse; width: 100%; }}
+th, td {{ border-bottom: 1px solid #ddd; padding: 0.3em; text-align: left; }}
+td, .hash {{ font-family: monospace; word-break: break-all; }}
+nav {{ display: flex; gap: 1em; align-items: center; }}
+</style>
+</head>
+<body>
+<nav><a href=\"/\">Blocks</a><a href=\"/mempool\">Mempool</a>
+<form action=\"/search\"><input name=\"q\" size=\"40\" placeholder=\"Height, hash or address\"></form></nav>
+<h1>{title}</h1>
+{body}
+</body>
+</html>
+",
+        title = escape(title),
+    )
+}
+
+fn blocks_table(blocks: &[(u64, Block)]) -> String {
+    let mut table = String::from(
+        "<table><tr><th>Height</th><th>Hash</th><th>Time</th><th>Transactions</th></tr>",
+    );
+    for (height, block) in blocks {
+        let _ = write!(
+            table,
+            "<tr><td>{}</td><td>{}</td><td>{}</td><td>{}</td></tr>",
+            block_link(*height, &height.to_string()),
+            block_link(*height, &hex(&block.hash())),
+            block.timestamp(),
+            block.transactions().len()
+        );
+    }
+
+    table + "</table>"
+}
+
+fn transactions_table<'a>(
+    transactions: impl Iterator<Item = (Option<&'a Location>, &'a Transaction)>,
+) -> String {
+    let mut table = String::from(
+        "<table><tr><th>Hash</th><th>Block</th><th>Kind</th><th>From</th><th>To</th><th>Amount</th></tr>",
+    );
+    for (location, transaction) in transactions {
+        let _ = write!(
+            table,
+            "<tr><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td></tr>",
+            transaction_link(&hex(&transaction.hash())),
+            location
+                .map(|location| block_link(location.height, &location.height.to_string()))
+                .unwrap_or_default(),
+            transaction.kind().name(),
+            transaction
+                .from()
+                .as_deref()
+                .map(address_link)
+                .unwrap_or_default(),
+            address_link(transaction.to()),
+            transaction.amount()
+        );
+    }
+
+    table + "</table>"
+}
+
+fn row(name: &str, value: &str) -> String {
+    row_html(name, &escape(value))
+}
+
+fn row_html(name: &str, value: &str) -> String {
+    format!("<tr><th>{}</th><td>{}</td></tr>", escape(name), value)
+}
+
+fn block_link(height: u64, text: &str) -> String {
+    format!("<a href=\"/blocks/{}\">{}</a>", height, escape(text))
+}
+
+fn transaction_link(hash: &str) -> String {
+    format!("<a href=\"/transactions/{}\">{}</a>", hash, escape(hash))
+}
+
+// addresses are json encoded keys so they are shortened and escaped
+fn address_link(address: &str) -> String {
+    let short: String = address.chars().take(16).collect();
+    let short = if short.len() < address.len() {
+        short + "…"
+    } else {
+        short
+    };
+
+    format!(
+        "<a href=\"/addresses/{}\" title=\"{}\">{}</a>",
+        percent_encode(address),
+        escape(address),
+        escape(&short)
+    )
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&#39;")
+}
+
+// the decoded value of a key in a query like a=1&b=2
+fn query_value(query: &str, key: &str) -> Option<String> {
+    query
+        .split('&')
+        .filter_map(|pair| pair.split_once('='))
+        .find(|(name, _)| *name == key)
+        .map(|(_, value)| percent_decode(&value.replace('+', " ")))
+}
+
+// every decoded value of a key that may be repeated
+fn query_values(query: &str, key: &str) -> Vec<String> {
+    query
+        .split('&')
+        .filter_map(|pair| pair.split_once('='))
+        .filter(|(name, _)| *name == key)
+        .map(|(_, value)| percent_decode(&value.replace('+', " ")))
+        .collect()
+}
+
+fn percent_encode(text: &str) -> String {
+    text.bytes()
+        .map(|byte| match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                (byte as char).to_string()
+            }
+            _ => format!("%{:02X}", byte),
+        })
+        .collect()
+}
+
+fn percent_decode(text: &str) -> String {
+    let bytes = text.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let escaped = (bytes[i] == b'%')
+            .then(|| text.get(i + 1..i + 3))
+            .flatten()
+            .and_then(parse_hex)
+            .map(|bytes| bytes[0]);
+        match escaped {
+            Some(byte) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            None => {
+                decoded.push(bytes[i]);
+                i += 1;
+            }
+        }
+    }
+
+    String::from_utf8_lossy(&decoded).into_owned()
+}
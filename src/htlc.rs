@@ -0,0 +1,127 @@
+use crate::{
+    asset::AssetId,
+    block::Hash,
+    script::{Op, Script},
+    transaction::{Lock, Transaction, TransactionKind},
+};
+use k256::ecdsa::SigningKey;
+use sha2::Digest;
+
+// A hash time locked contract. The amount of the transaction that opens it is held by the
+// address of its script. The recipient can claim it by revealing the secret whose SHA-256 is
+// the hash and the sender can take it back once the chain reaches the timeout height.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Htlc {
+    pub hash: Hash,
+    pub recipient: String,
+    pub sender: String,
+    pub asset: Option<AssetId>,
+    pub timeout: u64,
+}
+
+impl Htlc {
+    // the contract a transaction opens if it is an HTLC
+    pub fn of(transaction: &Transaction) -> Option<Self> {
+        let TransactionKind::Htlc { hash, timeout } = transaction.kind() else {
+            return None;
+        };
+
+        Some(Self {
+            hash: *hash,
+            recipient: transaction.to().to_owned(),
+            sender: transaction.from().clone()?,
+            asset: transaction.asset(),
+            timeout: *timeout,
+        })
+    }
+
+    // the recipient signs with the secret or the sender signs once the timeout is reached
+    pub fn script(&self) -> Script {
+        Script::new(vec![
+            Op::If,
+            Op::Sha256,
+            Op::Push(self.hash.to_vec()),
+            Op::EqualVerify,
+            Op::Push(self.recipient.as_bytes().to_vec()),
+            Op::CheckSig,
+            Op::Else,
+            Op::number(self.timeout),
+            Op::CheckLockHeight,
+            Op::Drop,
+            Op::Push(self.sender.as_bytes().to_vec()),
+            Op::CheckSig,
+            Op::EndIf,
+        ])
+    }
+
+    // the address that holds the coins while the contract is open
+    pub fn address(&self) -> String {
+        self.script().address()
+    }
+
+    // pays the amount to the recipient by revealing the secret, the nonce is the next nonce of
+    // the address of the contract
+    pub fn claim(
+        &self,
+        secret: &[u8],
+        amount: u64,
+        nonce: u64,
+        private_key: &SigningKey,
+    ) -> Transaction {
+        let transaction = Transaction::new(
+            Some(self.address()),
+            self.recipient.clone(),
+            amount,
+            TransactionKind::Normal,
+        )
+        .with_nonce(nonce);
+        let mut transaction = self.with_asset(transaction).with_script(self.script());
+
+        let signiture = transaction.witness_signiture(private_key);
+        transaction.set_witness(vec![signiture, secret.to_vec(), vec![1]]);
+
+        transaction
+    }
+
+    // pays the amount back to the sender, the transaction can not be mined before the timeout
+    pub fn refund(&self, amount: u64, nonce: u64, private_key: &SigningKey) -> Transaction {
+        let transaction = Transaction::new(
+            Some(self.address()),
+            self.sender.clone(),
+            amount,
+            TransactionKind::Normal,
+        )
+        .with_nonce(nonce)
+        .with_lock(Lock::Height(self.timeout));
+        let mut transaction = self.with_asset(transaction).with_script(self.script());
+
+        let signiture = transaction.witness_signiture(private_key);
+        transaction.set_witness(vec![signiture, Vec::new()]);
+
+        transaction
+    }
+
+    // claims and refunds pay in the asset that was locked
+    fn with_asset(&self, transaction: Transaction) -> Transaction {
+        match self.asset {
+            Some(asset) => transaction.with_asset(asset),
+            None => transaction,
+        }
+    }
+
+    // the secret revealed by a transaction that claimed the contract, this is how the other
+    // side of a swap learns it
+    pub fn secret(&self, transaction: &Transaction) -> Option<Vec<u8>> {
+        if transaction.from().as_deref() != Some(self.address().as_str()) {
+            return None;
+        }
+
+        match transaction.witness() {
+            [_, secret, branch] if branch.as_slice() == [1] => {
+                let hash: Hash = sha2::Sha256::digest(secret).into();
+                (hash == self.hash).then(|| secret.clone())
+            }
+            _ => None,
+        }
+    }
+}
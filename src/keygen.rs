@@ -1,5 +1,9 @@
+use crate::encoding::hex;
 use elliptic_curve::rand_core::OsRng;
-use k256::ecdsa::{SigningKey, VerifyingKey};
+use k256::{
+    ecdsa::{SigningKey, VerifyingKey},
+    schnorr,
+};
 
 pub fn gen_key_pair() -> (SigningKey, VerifyingKey) {
     // Generate a random private key
@@ -9,3 +13,21 @@ pub fn gen_key_pair() -> (SigningKey, VerifyingKey) {
 
     (signing_key, verifying_key)
 }
+
+// the address of a key is its json encoded public key
+pub fn address(public_key: &VerifyingKey) -> String {
+    serde_json::to_string(public_key).unwrap()
+}
+
+pub fn gen_schnorr_key_pair() -> (schnorr::SigningKey, schnorr::VerifyingKey) {
+    let mut rng = OsRng;
+    let signing_key = schnorr::SigningKey::random(&mut rng);
+    let verifying_key = *signing_key.verifying_key();
+
+    (signing_key, verifying_key)
+}
+
+// the address of a schnorr key is the hex of its x-only public key
+pub fn schnorr_address(public_key: &schnorr::VerifyingKey) -> String {
+    hex(&public_key.to_bytes())
+}
@@ -0,0 +1,27 @@
+pub mod account;
+pub mod asset;
+pub mod backup;
+pub mod block;
+pub mod blockchain;
+pub mod chain_spec;
+pub mod clock;
+pub mod consensus;
+pub mod encoding;
+pub mod escrow;
+pub mod events;
+pub mod explorer;
+pub mod htlc;
+pub mod keygen;
+pub mod light;
+pub mod mempool;
+pub mod merkle;
+pub mod monetary;
+pub mod script;
+pub mod signature;
+pub mod snapshot;
+pub mod sparse_merkle;
+pub mod state;
+pub mod storage;
+pub mod transaction;
+pub mod utxo;
+pub mod verifier;
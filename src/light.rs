@@ -0,0 +1,364 @@
+use crate::{
+    account::{Account, AccountProof},
+    asset::AssetId,
+    block::{Block, BlockError, Hash, Header},
+    blockchain::{self, Blockchain, MAX_FUTURE_DRIFT},
+    chain_spec::ChainSpec,
+    clock::{Clock, SystemClock},
+    consensus::Consensus,
+    encoding::{encode_list, Decode, DecodeError, Encode, Reader},
+    merkle::{self, Sibling},
+    state::ChainState,
+    transaction::Transaction,
+};
+use std::collections::{BTreeMap, BTreeSet};
+
+pub const MAX_HEADERS: u64 = 2000; // how many headers a node sends for one request
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LightClientError {
+    ForeignChain,
+    InvalidHeader(u64, BlockError), // the height of the header and why it was rejected
+    UnknownHeight(u64),             // a proof for a block whose header was not synced
+    InvalidProof(Hash),             // a transaction the proof does not lead to the root for
+    NotWatched(Hash),               // a transaction that does not involve a watched address
+    InvalidAccountProof(String),    // an account the proof does not lead to the state root for
+    Unavailable,                    // the node could not answer a request
+}
+
+// What a light client asks a full node for, see Blockchain::serve
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Request {
+    Headers {
+        start: u64,
+        count: u64,
+    },
+    // the transactions involving the address in the blocks from start up to before end
+    Proofs {
+        address: String,
+        start: u64,
+        end: u64,
+    },
+    // the account of the address in the state after the latest block
+    Account {
+        address: String,
+    },
+}
+
+#[derive(Debug, Clone)]
+pub enum Response {
+    Headers(Vec<Header>),
+    Proofs(Vec<TransactionProof>),
+    Account {
+        address: String,
+        height: u64, // the block whose state root the proof is for
+        proof: Box<AccountProof>,
+    },
+}
+
+// Proves that a transaction is in the block at a height
+#[derive(Debug, Clone)]
+pub struct TransactionProof {
+    pub height: u64,
+    pub index: u32, // the position in the block, transactions are applied in this order
+    pub transaction: Transaction,
+    pub siblings: Vec<Sibling>,
+}
+
+impl TransactionProof {
+    pub fn verify(&self, header: &Header) -> bool {
+        merkle::verify(
+            &header.transactions_root,
+            &self.transaction.id(),
+            &self.siblings,
+        )
+    }
+}
+
+impl Encode for TransactionProof {
+    fn encode(&self, out: &mut Vec<u8>) {
+        self.height.encode(out);
+        self.index.encode(out);
+        self.transaction.encode(out);
+        encode_list(&self.siblings, out);
+    }
+}
+
+impl Decode for TransactionProof {
+    fn decode(reader: &mut Reader) -> Result<Self, DecodeError> {
+        Ok(Self {
+            height: reader.u64()?,
+            index: reader.u32()?,
+            transaction: Transaction::decode(reader)?,
+            siblings: reader.list()?,
+        })
+    }
+}
+
+// A client that only downloads the headers of a chain and the transactions involving the
+// addresses it watches, together with proofs that they are in those headers. The genesis
+// comes from the spec so it is trusted. Balances are verified in that every transaction
+// counted is in the chain, a node can still leave transactions out. Accounts are proven
+// against the state root of a header so they are complete, but only as of that header.
+pub struct LightClient {
+    spec: ChainSpec,
+    consensus: Box<dyn Consensus>,
+    clock: Box<dyn Clock>,
+    genesis: Block,
+    headers: Vec<Header>,
+    addresses: BTreeSet<String>,
+    // the proven transactions by height and hash, so a proof sent again under another index is
+    // kept once, with the index it claims
+    transactions: BTreeMap<(u64, Hash), (u32, Transaction)>,
+    accounts: BTreeMap<String, (u64, Option<Account>)>, // the proven accounts and their height
+}
+
+impl LightClient {
+    pub fn new(spec: ChainSpec) -> Self {
+        let genesis = spec.genesis();
+        Self {
+            consensus: spec.consensus(),
+            clock: Box::new(SystemClock),
+            headers: vec![genesis.header()],
+            genesis,
+            spec,
+            addresses: BTreeSet::new(),
+            transactions: BTreeMap::new(),
+            accounts: BTreeMap::new(),
+        }
+    }
+
+    // replaces the clock used to check the timestamps of headers
+    pub fn with_clock(mut self, clock: impl Clock + 'static) -> Self {
+        self.clock = Box::new(clock);
+        self
+    }
+
+    // asks for the transactions of the address from the next proof requests on
+    pub fn watch(&mut self, address: impl Into<String>) {
+        self.addresses.insert(address.into());
+    }
+
+    pub fn addresses(&self) -> &BTreeSet<String> {
+        &self.addresses
+    }
+
+    pub fn handshake(&self) -> (u64, Hash) {
+        (self.spec.chain_id, self.genesis.hash())
+    }
+
+    pub fn headers(&self) -> &[Header] {
+        &self.headers
+    }
+
+    pub fn latest_header(&self) -> &Header {
+        self.headers
+            .last()
+            .expect("There should always be a latest header")
+    }
+
+    // the next headers to sync
+    pub fn header_request(&self) -> Request {
+        Request::Headers {
+            start: self.headers.len() as u64,
+            count: MAX_HEADERS,
+        }
+    }
+
+    // asks for every transaction of the watched addresses in the synced headers, ones that
+    // were already proven are ignored when they come back
+    pub fn proof_requests(&self) -> Vec<Request> {
+        self.addresses
+            .iter()
+            .map(|address| Request::Proofs {
+                address: address.clone(),
+                start: 1,
+                end: self.headers.len() as u64,
+            })
+            .collect()
+    }
+
+    // asks for the latest account of every watched address
+    pub fn account_requests(&self) -> Vec<Request> {
+        self.addresses
+            .iter()
+            .map(|address| Request::Account {
+                address: address.clone(),
+            })
+            .collect()
+    }
+
+    pub fn handle(&mut self, response: Response) -> Result<(), LightClientError> {
+        match response {
+            Response::Headers(headers) => self.add_headers(&headers),
+            Response::Proofs(proofs) => self.add_proofs(&proofs),
+            Response::Account {
+                address,
+                height,
+                proof,
+            } => self.add_account(address, height, &proof),
+        }
+    }
+
+    // appends headers that follow on the latest one, the headers before an invalid one
+    // are kept
+    pub fn add_headers(&mut self, headers: &[Header]) -> Result<(), LightClientError> {
+        for header in headers {
+            self.check_header(header).map_err(|error| {
+                LightClientError::InvalidHeader(self.headers.len() as u64, error)
+            })?;
+            self.headers.push(header.clone());
+        }
+
+        Ok(())
+    }
+
+    // the rules of Blockchain::import_block that do not need the transactions
+    fn check_header(&self, header: &Header) -> Result<(), BlockError> {
+        if header.prev_hash != self.latest_header().hash() {
+            return Err(BlockError::UnknownParent);
+        }
+
+        let timestamps = self.headers.iter().map(|header| header.timestamp);
+        if header.timestamp <= blockchain::median_time(timestamps) {
+            return Err(BlockError::TimestampTooOld);
+        }
+
+        if header.timestamp > self.clock.now() + MAX_FUTURE_DRIFT {
+            return Err(BlockError::TimestampInFuture);
+        }
+
+        if !self.consensus.verify_header(&self.headers, header) {
+            return Err(BlockError::InvalidSeal);
+        }
+
+        Ok(())
+    }
+
+    // keeps the transactions once every proof has been checked against the synced headers
+    pub fn add_proofs(&mut self, proofs: &[TransactionProof]) -> Result<(), LightClientError> {
+        for proof in proofs {
+            let hash = proof.transaction.hash();
+            let header = usize::try_from(proof.height)
+                .ok()
+                .filter(|height| *height > 0)
+                .and_then(|height| self.headers.get(height))
+                .ok_or(LightClientError::UnknownHeight(proof.height))?;
+
+            if !self
+                .addresses
+                .iter()
+                .any(|address| proof.transaction.involves(address))
+            {
+                return Err(LightClientError::NotWatched(hash));
+            }
+
+            if !proof.verify(header) {
+                return Err(LightClientError::InvalidProof(hash));
+            }
+        }
+
+        for proof in proofs {
+            self.transactions.insert(
+                (proof.height, proof.transaction.hash()),
+                (proof.index, proof.transaction.clone()),
+            );
+        }
+
+        Ok(())
+    }
+
+    // keeps the account once the proof has been checked against the state root of the header
+    // at the height, an account proven at a lower height than the one kept is ignored
+    pub fn add_account(
+        &mut self,
+        address: String,
+        height: u64,
+        proof: &AccountProof,
+    ) -> Result<(), LightClientError> {
+        let header = usize::try_from(height)
+            .ok()
+            .and_then(|height| self.headers.get(height))
+            .ok_or(LightClientError::UnknownHeight(height))?;
+
+        if !proof.verify(&header.state_root, &address) {
+            return Err(LightClientError::InvalidAccountProof(address));
+        }
+
+        if self
+            .accounts
+            .get(&address)
+            .is_none_or(|(proven, _)| *proven <= height)
+        {
+            self.accounts
+                .insert(address, (height, proof.account.clone()));
+        }
+
+        Ok(())
+    }
+
+    // the proven account of an address and the height it was proven at, the account is none if
+    // the address had none
+    pub fn account(&self, address: &str) -> Option<(u64, Option<&Account>)> {
+        self.accounts
+            .get(address)
+            .map(|(height, account)| (*height, account.as_ref()))
+    }
+
+    // syncs the headers, then the proofs and the accounts from a node in the same process
+    pub fn sync(&mut self, node: &Blockchain) -> Result<(), LightClientError> {
+        let (chain_id, genesis_hash) = self.handshake();
+        node.accept_peer(chain_id, genesis_hash)
+            .map_err(|_| LightClientError::ForeignChain)?;
+        let serve = |request: &Request| {
+            node.serve(request)
+                .map_err(|_| LightClientError::Unavailable)
+        };
+
+        loop {
+            let synced = self.headers.len();
+            self.handle(serve(&self.header_request())?)?;
+            if self.headers.len() == synced {
+                break;
+            }
+        }
+
+        for request in self.proof_requests() {
+            self.handle(serve(&request)?)?;
+        }
+
+        for request in self.account_requests() {
+            self.handle(serve(&request)?)?;
+        }
+
+        Ok(())
+    }
+
+    // the proven transactions in chain order
+    pub fn transactions(&self) -> impl Iterator<Item = &Transaction> {
+        let mut transactions: Vec<_> = self
+            .transactions
+            .iter()
+            .map(|((height, _), (index, transaction))| ((*height, *index), transaction))
+            .collect();
+        transactions.sort_by_key(|(position, _)| *position);
+
+        transactions.into_iter().map(|(_, transaction)| transaction)
+    }
+
+    // the genesis with the proven transactions applied, only what the watched addresses hold
+    // and owe is complete
+    pub fn state(&self) -> ChainState {
+        let mut state = ChainState::genesis(&self.genesis, self.spec.ledger);
+        for transaction in self.transactions() {
+            state.apply_unchecked(transaction);
+        }
+
+        state
+    }
+
+    // what a watched address holds according to the proven transactions
+    pub fn balance_of(&self, address: &str, asset: Option<AssetId>) -> u64 {
+        self.state().balance_of(address, asset)
+    }
+}
@@ -1,23 +1,101 @@
-mod block;
-mod blockchain;
-mod keygen;
-mod transaction;
-
-use crate::transaction::TransactionKind;
-use blockchain::Blockchain;
+use blockchain::{
+    asset::AssetId,
+    backup::{Backup, Format},
+    blockchain::{self as chain, Blockchain},
+    chain_spec::{ChainSpec, ConsensusSpec, Ledger, SLOT_DURATION},
+    encoding::{hex, parse_hex, Encode},
+    escrow::Escrow,
+    explorer::Explorer,
+    htlc::Htlc,
+    keygen,
+    light::LightClient,
+    storage::DiskStorage,
+    transaction::{Lock, Transaction, TransactionError, TransactionKind},
+};
 use k256::ecdsa::{SigningKey, VerifyingKey};
-use std::collections::HashMap;
+use sha2::Digest;
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    thread,
+};
 use text_io::read;
-use transaction::Transaction;
+
+const SNAPSHOT_INTERVAL: u64 = 100; // how many blocks apart snapshots are taken
 
 fn main() {
-    let mut blockchain = Blockchain::default();
     let mut users: HashMap<String, (SigningKey, VerifyingKey)> = HashMap::new();
+    let args: Vec<String> = std::env::args().collect();
+
+    let mut spec = match args.iter().position(|arg| arg == "--spec") {
+        Some(i) => ChainSpec::load(&args[i + 1]).expect("Could not load the chain spec"),
+        None => ChainSpec::default(),
+    };
+
+    if args.iter().any(|arg| arg == "--utxo") {
+        spec.ledger = Ledger::Utxo;
+    }
+
+    // proof of stake needs someone to sign the first blocks
+    if args.iter().any(|arg| arg == "--pos") {
+        println!("Enter a username for the genesis validator:");
+        let input: String = read!("{}\n");
+        let validator = keygen::gen_key_pair();
+        let stakes = [(keygen::address(&validator.1), 1)].into();
+        users.insert(input, validator);
+
+        spec.consensus = ConsensusSpec::ProofOfStake {
+            stakes,
+            slot_duration: SLOT_DURATION,
+        };
+    } else if args.iter().any(|arg| arg == "--poa") {
+        println!("Enter the usernames of the authorities separated by spaces:");
+        let input: String = read!("{}\n");
+        let mut authorities = Vec::new();
+        for name in input.split_whitespace() {
+            let authority = keygen::gen_key_pair();
+            authorities.push(keygen::address(&authority.1));
+            users.insert(name.to_owned(), authority);
+        }
+
+        spec.consensus = ConsensusSpec::ProofOfAuthority {
+            authorities,
+            votes: Vec::new(),
+        };
+    }
+
+    // a chain kept on disk carries on from the blocks stored by the last run
+    let blockchain = match args.iter().position(|arg| arg == "--data") {
+        Some(i) => {
+            let storage = DiskStorage::open(&args[i + 1]).expect("Could not open the data file");
+            Blockchain::open(spec, storage).expect("Could not load the stored chain")
+        }
+        None => Blockchain::new(spec),
+    };
+    let mut blockchain = blockchain.with_snapshots(SNAPSHOT_INTERVAL);
+    if let Some(i) = args.iter().position(|arg| arg == "--prune") {
+        let depth = args[i + 1]
+            .parse()
+            .expect("The pruning depth should be a number");
+        blockchain = blockchain.with_pruning(depth);
+    }
+
+    // the explorer answers between commands as a command holds the chain until it is done
+    let blockchain = Arc::new(Mutex::new(blockchain));
+    if let Some(i) = args.iter().position(|arg| arg == "--explorer") {
+        let explorer = Explorer::bind(&args[i + 1]).expect("Could not start the explorer");
+        println!("Explorer running on http://{}", explorer.address());
+        let blockchain = blockchain.clone();
+        thread::spawn(move || explorer.run(blockchain));
+    }
 
     loop {
-        println!("Enter a command: (list, pay, add, loan, info, mine, sign, print, exit)");
+        println!(
+            "Enter a command: (list, pay, add, loan, info, mine, sign, repay, stake, vote, cancel, swap, escrow, release, approve, refund, issue, light, snapshot, export, import, verify, print, exit)"
+        );
         let input: String = read!("{}\n");
 
+        let mut blockchain = blockchain.lock().unwrap();
         match input.to_lowercase().as_str() {
             "list" => list(&users),
             "pay" => pay(&users, &mut blockchain),
@@ -25,6 +103,21 @@ fn main() {
             "loan" => add(&mut users),
             "info" => info(&users, &blockchain),
             "mine" => mine(&users, &mut blockchain),
+            "repay" => repay(&users, &mut blockchain),
+            "stake" => stake(&users, &mut blockchain),
+            "vote" => vote(&users, &mut blockchain),
+            "cancel" => cancel(&users, &mut blockchain),
+            "swap" => swap(&users, &mut blockchain),
+            "escrow" => escrow(&users, &mut blockchain),
+            "release" => release(&users, &mut blockchain),
+            "approve" => approve(&users, &mut blockchain),
+            "refund" => refund(&users, &mut blockchain),
+            "issue" => issue(&users, &mut blockchain),
+            "light" => light(&users, &blockchain),
+            "snapshot" => snapshot(&blockchain),
+            "export" => export(&blockchain),
+            "import" => import(&mut blockchain),
+            "verify" => verify(&blockchain),
             "print" => print_blockchain(&blockchain),
             "exit" => sign_loan(&users, &mut blockchain),
             "sign" => break,
@@ -36,13 +129,31 @@ fn main() {
 }
 
 fn print_blockchain(blockchain: &Blockchain) {
-    for block in blockchain.blocks() {
+    // only the latest blocks keep their transactions in memory
+    for height in 0..blockchain.blocks().len() as u64 {
+        let block = match blockchain.block(height) {
+            Ok(Some(block)) => block,
+            Ok(None) => continue,
+            Err(e) => {
+                println!("Failed: {:?}", e);
+                return;
+            }
+        };
         println!(
-            "Prev hash: {:X?}\nHash: {:X?}",
-            block.prev_hash(),
-            block.hash()
+            "Height: {} Time: {} Transactions: {}\nPrev hash: {}\nHash: {}",
+            height,
+            block.timestamp(),
+            block.transactions().len(),
+            hex(&block.prev_hash()),
+            hex(&block.hash())
         )
     }
+
+    println!(
+        "Total supply: {} Circulating supply: {}",
+        blockchain.total_supply(),
+        blockchain.circulating_supply()
+    );
 }
 
 fn list(users: &HashMap<String, (SigningKey, VerifyingKey)>) {
@@ -71,19 +182,58 @@ fn pay(users: &HashMap<String, (SigningKey, VerifyingKey)>, blockchain: &mut Blo
         return;
     };
 
+    let Some(asset) = read_asset() else {
+        return;
+    };
+
     println!("Enter an amount to pay:");
     let amount: u64 = read!("{}\n");
     // let amount = amount.parse::<u64>().unwrap();
 
-    let mut transaction = Transaction::new(
-        Some(serde_json::to_string(&payer.1).unwrap()),
-        serde_json::to_string(&payee.1).unwrap(),
-        amount,
-        TransactionKind::Normal,
-    );
+    println!("Enter a fee for the miner:");
+    let fee: u64 = read!("{}\n");
+
+    println!("Enter the height the payment can be mined from (0 to pay now):");
+    let height: u64 = read!("{}\n");
+
+    let from = serde_json::to_string(&payer.1).unwrap();
+    let to = serde_json::to_string(&payee.1).unwrap();
+    let mut transaction = match blockchain.spec().ledger {
+        Ledger::Account => {
+            Transaction::new(Some(from.clone()), to, amount, TransactionKind::Normal)
+                .with_nonce(blockchain.next_nonce(&from))
+                .with_fee(fee)
+        }
+        // the wallet picks which outputs to spend and pays the rest back to the payer
+        Ledger::Utxo => {
+            let Some(cost) = amount.checked_add(fee) else {
+                println!("Failed: {:?}", TransactionError::Overflow);
+                return;
+            };
+            match blockchain.select_coins(&from, cost) {
+                Ok((inputs, total)) => {
+                    Transaction::spend(from.clone(), to, amount, fee, inputs, total)
+                        .expect("The amount and fee were checked to add up")
+                        .with_nonce(blockchain.next_nonce(&from))
+                }
+                Err(e) => {
+                    println!("Failed: {:?}", e);
+                    return;
+                }
+            }
+        }
+    };
+    if height > 0 {
+        transaction = transaction.with_lock(Lock::Height(height));
+    }
+    if let Some(asset) = asset {
+        transaction = transaction.with_asset(asset);
+    }
 
     transaction.sign_transaction(&payer.0).unwrap();
-    blockchain.add_transaction(transaction).unwrap();
+    if let Err(e) = blockchain.add_transaction(transaction) {
+        println!("Failed: {:?}", e);
+    }
 }
 
 fn add(users: &mut HashMap<String, (SigningKey, VerifyingKey)>) {
@@ -105,7 +255,650 @@ fn mine(users: &HashMap<String, (SigningKey, VerifyingKey)>, blockchain: &mut Bl
         return;
     };
 
-    blockchain.mine_pending_transactions(serde_json::to_string(&user.1).unwrap());
+    // engines that choose who seals a block need the block to be signed
+    let result = match blockchain.expected_sealer() {
+        Some(_) => blockchain.seal_pending_transactions(&user.0),
+        None => blockchain.mine_pending_transactions(keygen::address(&user.1)),
+    };
+
+    if let Err(e) = result {
+        println!("Failed: {:?}", e);
+        if let Some(sealer) = blockchain.expected_sealer() {
+            let name = users
+                .iter()
+                .find(|(_, (_, key))| keygen::address(key) == sealer)
+                .map(|(name, _)| name.as_str())
+                .unwrap_or("unknown");
+            println!("The current sealer is {}", name);
+        }
+    }
+}
+
+fn repay(users: &HashMap<String, (SigningKey, VerifyingKey)>, blockchain: &mut Blockchain) {
+    println!("Who is repaying");
+    let input: String = read!("{}\n");
+    let borrower = if let Some(user) = users.get(&input) {
+        user
+    } else {
+        println!("No user found.");
+        return;
+    };
+
+    println!("Who is being repaid");
+    let input: String = read!("{}\n");
+    let lender = if let Some(user) = users.get(&input) {
+        user
+    } else {
+        println!("No user found.");
+        return;
+    };
+
+    let Some(asset) = read_asset() else {
+        return;
+    };
+
+    let debt = blockchain.debt(
+        &keygen::address(&borrower.1),
+        &keygen::address(&lender.1),
+        asset,
+    );
+    println!("Enter an amount to repay (owed {}):", debt);
+    let amount: u64 = read!("{}\n");
+
+    let mut transaction = Transaction::new(
+        Some(keygen::address(&borrower.1)),
+        keygen::address(&lender.1),
+        amount,
+        TransactionKind::Repayment,
+    )
+    .with_nonce(blockchain.next_nonce(&keygen::address(&borrower.1)));
+    if let Some(asset) = asset {
+        transaction = transaction.with_asset(asset);
+    }
+
+    transaction.sign_transaction(&borrower.0).unwrap();
+    if let Err(e) = blockchain.add_transaction(transaction) {
+        println!("Failed: {:?}", e);
+    }
+}
+
+fn vote(users: &HashMap<String, (SigningKey, VerifyingKey)>, blockchain: &mut Blockchain) {
+    println!("Which authority is voting");
+    let input: String = read!("{}\n");
+    let voter = if let Some(user) = users.get(&input) {
+        user
+    } else {
+        println!("No user found.");
+        return;
+    };
+
+    println!("Who is the vote for");
+    let input: String = read!("{}\n");
+    let candidate = if let Some(user) = users.get(&input) {
+        user
+    } else {
+        println!("No user found.");
+        return;
+    };
+
+    println!("Add or remove the authority: (add, remove)");
+    let input: String = read!("{}\n");
+    let kind = match input.to_lowercase().as_str() {
+        "add" => TransactionKind::AddAuthority,
+        "remove" => TransactionKind::RemoveAuthority,
+        _ => {
+            println!("Unknown vote.");
+            return;
+        }
+    };
+
+    let mut transaction = Transaction::new(
+        Some(keygen::address(&voter.1)),
+        keygen::address(&candidate.1),
+        0,
+        kind,
+    )
+    .with_nonce(blockchain.next_nonce(&keygen::address(&voter.1)));
+
+    transaction.sign_transaction(&voter.0).unwrap();
+    if let Err(e) = blockchain.add_transaction(transaction) {
+        println!("Failed: {:?}", e);
+    }
+}
+
+fn cancel(users: &HashMap<String, (SigningKey, VerifyingKey)>, blockchain: &mut Blockchain) {
+    println!("Who is cancelling");
+    let input: String = read!("{}\n");
+    let user = if let Some(user) = users.get(&input) {
+        user
+    } else {
+        println!("No user found.");
+        return;
+    };
+
+    let address = keygen::address(&user.1);
+    println!("Pending transactions:");
+    for transaction in blockchain.mempool().from_sender(&address) {
+        println!(
+            "Nonce: {} Amount: {} Fee: {}",
+            transaction.nonce(),
+            transaction.amount(),
+            transaction.fee()
+        );
+    }
+
+    println!("Enter the nonce of the transaction to cancel:");
+    let nonce: u64 = read!("{}\n");
+    println!("Enter a fee higher than the one it pays:");
+    let fee: u64 = read!("{}\n");
+
+    let mut transaction = Transaction::cancellation(address, nonce, fee);
+    transaction.sign_transaction(&user.0).unwrap();
+    if let Err(e) = blockchain.add_transaction(transaction) {
+        println!("Failed: {:?}", e);
+    }
+}
+
+// Walks through an atomic swap between this chain and a second local chain. The first user
+// locks coins here for the second with a hash only they know the secret of, the second locks
+// coins on the other chain with the same hash and a shorter timeout. Claiming the coins on the
+// other chain reveals the secret, which lets the second user claim the coins here.
+fn swap(users: &HashMap<String, (SigningKey, VerifyingKey)>, blockchain: &mut Blockchain) {
+    if blockchain.expected_sealer().is_some() {
+        println!("Swaps can only be shown on a proof of work chain.");
+        return;
+    }
+
+    println!("Who is swapping coins on this chain");
+    let alice_name: String = read!("{}\n");
+    let alice = if let Some(user) = users.get(&alice_name) {
+        user
+    } else {
+        println!("No user found.");
+        return;
+    };
+
+    println!("Who is swapping coins on the other chain");
+    let bob_name: String = read!("{}\n");
+    let bob = if let Some(user) = users.get(&bob_name) {
+        user
+    } else {
+        println!("No user found.");
+        return;
+    };
+
+    println!("Enter an amount to swap on this chain:");
+    let amount: u64 = read!("{}\n");
+    println!("Enter an amount to swap on the other chain:");
+    let other_amount: u64 = read!("{}\n");
+
+    let alice_address = keygen::address(&alice.1);
+    let bob_address = keygen::address(&bob.1);
+    // the rewards go elsewhere so the balances only show the swap
+    let miner = keygen::address(&keygen::gen_key_pair().1);
+
+    // the other chain starts with the coins of the second user
+    let spec = blockchain.spec();
+    let mut other = Blockchain::new(ChainSpec {
+        chain_id: spec.chain_id + 1,
+        allocations: [(bob_address.clone(), other_amount)].into(),
+        consensus: ConsensusSpec::ProofOfWork,
+        ..spec.clone()
+    });
+
+    let secret: [u8; 32] = rand::random();
+    let hash: [u8; 32] = sha2::Sha256::digest(secret).into();
+
+    // the first user has to wait longer for a refund so the second always has time to claim
+    let timeout = blockchain.blocks().len() as u64 + 10;
+    let mut lock = Transaction::new(
+        Some(alice_address.clone()),
+        bob_address.clone(),
+        amount,
+        TransactionKind::Htlc { hash, timeout },
+    )
+    .with_nonce(blockchain.next_nonce(&alice_address));
+    lock.sign_transaction(&alice.0).unwrap();
+    let contract = Htlc::of(&lock).unwrap();
+    if let Err(e) = blockchain.add_transaction(lock) {
+        println!("Failed: {:?}", e);
+        return;
+    }
+    blockchain.mine_pending_transactions(miner.clone()).unwrap();
+    println!("Locked {} on this chain until height {}", amount, timeout);
+
+    let other_timeout = other.blocks().len() as u64 + 5;
+    let mut other_lock = Transaction::new(
+        Some(bob_address.clone()),
+        alice_address.clone(),
+        other_amount,
+        TransactionKind::Htlc {
+            hash,
+            timeout: other_timeout,
+        },
+    );
+    other_lock.sign_transaction(&bob.0).unwrap();
+    let other_contract = Htlc::of(&other_lock).unwrap();
+    other.add_transaction(other_lock).unwrap();
+    other.mine_pending_transactions(miner.clone()).unwrap();
+    println!(
+        "Locked {} on the other chain until height {}",
+        other_amount, other_timeout
+    );
+
+    // claiming on the other chain puts the secret on that chain for everyone to see
+    let claim = other_contract.claim(
+        &secret,
+        other_amount,
+        other.next_nonce(&other_contract.address()),
+        &alice.0,
+    );
+    other.add_transaction(claim).unwrap();
+    other.mine_pending_transactions(miner.clone()).unwrap();
+    println!("Claimed {} on the other chain", other_amount);
+
+    let revealed = other
+        .latest_block()
+        .transactions()
+        .iter()
+        .find_map(|transaction| other_contract.secret(transaction))
+        .unwrap();
+    let claim = contract.claim(
+        &revealed,
+        amount,
+        blockchain.next_nonce(&contract.address()),
+        &bob.0,
+    );
+    blockchain.add_transaction(claim).unwrap();
+    blockchain.mine_pending_transactions(miner.clone()).unwrap();
+    println!("Claimed {} on this chain with the revealed secret", amount);
+
+    for (name, address) in [(&alice_name, &alice_address), (&bob_name, &bob_address)] {
+        println!(
+            "{} has {} on this chain and {} on the other",
+            name,
+            blockchain.balance_of(address, None).unwrap(),
+            other.balance_of(address, None).unwrap()
+        );
+    }
+}
+
+fn escrow(users: &HashMap<String, (SigningKey, VerifyingKey)>, blockchain: &mut Blockchain) {
+    println!("Who is buying");
+    let input: String = read!("{}\n");
+    let buyer = if let Some(user) = users.get(&input) {
+        user
+    } else {
+        println!("No user found.");
+        return;
+    };
+
+    println!("Who is selling");
+    let input: String = read!("{}\n");
+    let seller = if let Some(user) = users.get(&input) {
+        user
+    } else {
+        println!("No user found.");
+        return;
+    };
+
+    println!("Who is the arbiter");
+    let input: String = read!("{}\n");
+    let arbiter = if let Some(user) = users.get(&input) {
+        user
+    } else {
+        println!("No user found.");
+        return;
+    };
+
+    println!("Enter an amount to hold in escrow:");
+    let amount: u64 = read!("{}\n");
+
+    println!("Enter the height the buyer can be refunded from:");
+    let timeout: u64 = read!("{}\n");
+
+    let address = keygen::address(&buyer.1);
+    let mut transaction = Transaction::new(
+        Some(address.clone()),
+        keygen::address(&seller.1),
+        amount,
+        TransactionKind::Escrow {
+            arbiter: keygen::address(&arbiter.1),
+            timeout,
+        },
+    )
+    .with_nonce(blockchain.next_nonce(&address));
+
+    transaction.sign_transaction(&buyer.0).unwrap();
+    if let Err(e) = blockchain.add_transaction(transaction) {
+        println!("Failed: {:?}", e);
+    }
+}
+
+// lets the user pick one of the open escrows they are part of
+fn choose_escrow(blockchain: &Blockchain, address: &str) -> Option<Escrow> {
+    println!("Open escrows:");
+    let escrows = blockchain.escrows_of(address);
+    for (i, escrow) in escrows.iter().enumerate() {
+        println!(
+            "{i}. Hash: {:X?} Amount: {} Timeout: {}",
+            escrow.hash, escrow.amount, escrow.timeout
+        );
+    }
+
+    println!("\nEnter a number to choose");
+    let pos: usize = read!("{}\n");
+    let escrow = escrows.get(pos).cloned();
+    if escrow.is_none() {
+        println!("No escrow found.");
+    }
+
+    escrow
+}
+
+// The buyer releases an escrow to the seller, who still has to approve it, or the arbiter
+// releases it on their own in a dispute
+fn release(users: &HashMap<String, (SigningKey, VerifyingKey)>, blockchain: &mut Blockchain) {
+    println!("Who is releasing");
+    let input: String = read!("{}\n");
+    let user = if let Some(user) = users.get(&input) {
+        user
+    } else {
+        println!("No user found.");
+        return;
+    };
+
+    let address = keygen::address(&user.1);
+    let Some(escrow) = choose_escrow(blockchain, &address) else {
+        return;
+    };
+
+    let transaction = if address == escrow.buyer {
+        escrow.release()
+    } else if address == escrow.arbiter {
+        escrow.arbitrate()
+    } else {
+        println!("Failed: Only the buyer or the arbiter can release an escrow");
+        return;
+    };
+
+    let mut transaction = transaction.with_nonce(blockchain.next_nonce(&address));
+    transaction.sign_transaction(&user.0).unwrap();
+    if let Err(e) = blockchain.add_transaction(transaction) {
+        println!("Failed: {:?}", e);
+    }
+}
+
+fn approve(users: &HashMap<String, (SigningKey, VerifyingKey)>, blockchain: &mut Blockchain) {
+    println!("Who is approving");
+    let input: String = read!("{}\n");
+    let user = if let Some(user) = users.get(&input) {
+        user
+    } else {
+        println!("No user found.");
+        return;
+    };
+
+    println!("Unsigned Releases:");
+    let releases = blockchain.releases_of(&keygen::address(&user.1), false);
+    let transactions = releases
+        .iter()
+        .map(|(hash, (_, amount))| (*hash, *amount))
+        .collect::<Vec<_>>();
+    for (i, (hash, amount)) in transactions.iter().enumerate() {
+        println!("{i}. Hash: {:X?} Amount: {}", hash, amount);
+    }
+    println!("\nEnter a number to sign");
+    let pos: usize = read!("{}\n");
+    let Some((hash, _)) = transactions.get(pos) else {
+        println!("No release found.");
+        return;
+    };
+
+    if let Err(e) = blockchain.sign_release(&user.0, *hash) {
+        println!("Failed: {:?}", e);
+    }
+}
+
+fn refund(users: &HashMap<String, (SigningKey, VerifyingKey)>, blockchain: &mut Blockchain) {
+    println!("Who is being refunded");
+    let input: String = read!("{}\n");
+    let user = if let Some(user) = users.get(&input) {
+        user
+    } else {
+        println!("No user found.");
+        return;
+    };
+
+    let address = keygen::address(&user.1);
+    let Some(escrow) = choose_escrow(blockchain, &address) else {
+        return;
+    };
+
+    if address != escrow.buyer {
+        println!("Failed: Only the buyer can be refunded");
+        return;
+    }
+
+    let mut transaction = escrow.refund().with_nonce(blockchain.next_nonce(&address));
+    transaction.sign_transaction(&user.0).unwrap();
+    if let Err(e) = blockchain.add_transaction(transaction) {
+        println!("Failed: {:?}", e);
+    }
+}
+
+// asks which asset an amount is in, none is the coin of the chain
+fn read_asset() -> Option<Option<AssetId>> {
+    println!("Enter the asset: (coin or the id of an asset)");
+    let input: String = read!("{}\n");
+    if input.to_lowercase() == "coin" {
+        return Some(None);
+    }
+
+    let asset: Option<AssetId> = parse_hex(&input).and_then(|bytes| bytes.try_into().ok());
+    if asset.is_none() {
+        println!("No asset found.");
+        return None;
+    }
+
+    Some(asset)
+}
+
+fn issue(users: &HashMap<String, (SigningKey, VerifyingKey)>, blockchain: &mut Blockchain) {
+    println!("Who is issuing");
+    let input: String = read!("{}\n");
+    let issuer = if let Some(user) = users.get(&input) {
+        user
+    } else {
+        println!("No user found.");
+        return;
+    };
+
+    println!("Enter the name of the asset:");
+    let name: String = read!("{}\n");
+    println!("Enter the supply in the smallest unit:");
+    let supply: u64 = read!("{}\n");
+    println!("Enter the number of decimals:");
+    let decimals: u8 = read!("{}\n");
+
+    let address = keygen::address(&issuer.1);
+    let mut transaction = Transaction::issuance(address.clone(), name, supply, decimals)
+        .with_nonce(blockchain.next_nonce(&address));
+    transaction.sign_transaction(&issuer.0).unwrap();
+
+    let id = transaction.hash();
+    match blockchain.add_transaction(transaction) {
+        Ok(()) => println!("Asset id: {}", hex(&id)),
+        Err(e) => println!("Failed: {:?}", e),
+    }
+}
+
+fn stake(users: &HashMap<String, (SigningKey, VerifyingKey)>, blockchain: &mut Blockchain) {
+    println!("Who is staking");
+    let input: String = read!("{}\n");
+    let user = if let Some(user) = users.get(&input) {
+        user
+    } else {
+        println!("No user found.");
+        return;
+    };
+
+    println!("Enter an amount to stake:");
+    let amount: u64 = read!("{}\n");
+
+    let address = keygen::address(&user.1);
+    let nonce = blockchain.next_nonce(&address);
+    let mut transaction = Transaction::new(
+        Some(address.clone()),
+        address,
+        amount,
+        TransactionKind::Stake,
+    )
+    .with_nonce(nonce);
+
+    transaction.sign_transaction(&user.0).unwrap();
+    if let Err(e) = blockchain.add_transaction(transaction) {
+        println!("Failed: {:?}", e);
+    }
+}
+
+// shows the latest snapshot and checks that a node started from it reaches the same state
+fn snapshot(blockchain: &Blockchain) {
+    let snapshot = blockchain
+        .latest_snapshot()
+        .cloned()
+        .unwrap_or_else(|| blockchain.snapshot());
+    println!(
+        "Height: {} Root: {:X?} Size: {} bytes",
+        snapshot.height,
+        snapshot.root(),
+        snapshot.to_bytes().len()
+    );
+    println!("Blocks pruned up to: {}", blockchain.pruned_height());
+
+    let synced = blockchain
+        .headers(0, snapshot.height + 1)
+        .and_then(|headers| {
+            Blockchain::from_snapshot(blockchain.spec().clone(), snapshot.clone(), &headers)
+        })
+        .and_then(|mut synced| {
+            for height in snapshot.height + 1..blockchain.blocks().len() as u64 {
+                if let Some(block) = blockchain.block(height)? {
+                    synced.import_block(block)?;
+                }
+            }
+            Ok(synced)
+        });
+    match synced {
+        Ok(synced) if synced.state().root() == blockchain.state().root() => {
+            println!("A node synced from the snapshot has the same state.")
+        }
+        Ok(_) => println!("A node synced from the snapshot has a different state."),
+        Err(error) => println!("Could not sync from the snapshot: {:?}", error),
+    }
+}
+
+// writes a range of blocks to a file another node can import
+fn export(blockchain: &Blockchain) {
+    println!("Enter the first height to export:");
+    let start: u64 = read!("{}\n");
+    println!(
+        "Enter the last height to export (latest {}):",
+        blockchain.blocks().len() - 1
+    );
+    let end: u64 = read!("{}\n");
+
+    println!("Enter a format: (json, binary)");
+    let input: String = read!("{}\n");
+    let format = match input.to_lowercase().as_str() {
+        "json" => Format::Json,
+        "binary" => Format::Binary,
+        _ => {
+            println!("Unknown format.");
+            return;
+        }
+    };
+
+    println!("Enter a file to write:");
+    let path: String = read!("{}\n");
+    let backup = match Backup::export(blockchain, start, end) {
+        Ok(backup) => backup,
+        Err(e) => {
+            println!("Failed: {:?}", e);
+            return;
+        }
+    };
+    match backup.save(&path, format) {
+        Ok(()) => println!("Exported {} blocks.", backup.blocks.len()),
+        Err(e) => println!("Failed: {:?}", e),
+    }
+}
+
+// imports the blocks of a backup, every one is validated as if it came from a peer
+fn import(blockchain: &mut Blockchain) {
+    println!("Enter a file to import:");
+    let path: String = read!("{}\n");
+    match Backup::load(&path).and_then(|backup| backup.import(blockchain)) {
+        Ok(imported) => println!("Imported {} blocks.", imported),
+        Err(e) => println!("Failed: {:?}", e),
+    }
+}
+
+// checks a backup would import without changing the chain
+fn verify(blockchain: &Blockchain) {
+    println!("Enter a file to verify:");
+    let path: String = read!("{}\n");
+    let backup = match Backup::load(&path) {
+        Ok(backup) => backup,
+        Err(e) => {
+            println!("Failed: {:?}", e);
+            return;
+        }
+    };
+    match backup.verify(blockchain) {
+        Ok(imported) => println!(
+            "The backup is valid, {} of its {} blocks are new.",
+            imported,
+            backup.blocks.len()
+        ),
+        Err(e) => println!("Failed: {:?}", e),
+    }
+}
+
+// syncs a light client that watches the user from the chain as if it was a remote node
+fn light(users: &HashMap<String, (SigningKey, VerifyingKey)>, blockchain: &Blockchain) {
+    println!("Enter a username:");
+    let input: String = read!("{}\n");
+    let Some(user) = users.get(&input) else {
+        println!("No user found.");
+        return;
+    };
+
+    let address = keygen::address(&user.1);
+    let mut client = LightClient::new(blockchain.spec().clone());
+    client.watch(address.clone());
+    if let Err(error) = client.sync(blockchain) {
+        println!("Could not sync: {:?}", error);
+        return;
+    }
+
+    println!(
+        "Headers: {} Proven transactions: {}",
+        client.headers().len(),
+        client.transactions().count()
+    );
+    println!("Verified balance: {}", client.balance_of(&address, None));
+    match client.account(&address) {
+        Some((height, Some(account))) => println!(
+            "Proven account at height {}: balance {} nonce {} stake {}",
+            height,
+            account.balance(None),
+            account.nonce,
+            account.stake
+        ),
+        Some((height, None)) => println!("Proven to have no account at height {}", height),
+        None => (),
+    }
 }
 
 fn info(users: &HashMap<String, (SigningKey, VerifyingKey)>, blockchain: &Blockchain) {
@@ -121,16 +914,42 @@ fn info(users: &HashMap<String, (SigningKey, VerifyingKey)>, blockchain: &Blockc
     println!(
         "Balance: {}\n",
         blockchain
-            .balance_of(&serde_json::to_string(&user.1).unwrap())
+            .balance_of(&serde_json::to_string(&user.1).unwrap(), None)
             .unwrap()
     );
+    if let Ok(utxos) = blockchain.utxos_of(&keygen::address(&user.1)) {
+        println!("Unspent outputs:");
+        for (outpoint, output) in utxos {
+            println!(
+                "Transaction: {:X?} Index: {} Amount: {}",
+                outpoint.transaction, outpoint.index, output.amount
+            );
+        }
+        println!();
+    }
+    let balances = blockchain.balances_of(&keygen::address(&user.1));
+    for (id, amount) in balances
+        .iter()
+        .filter_map(|(id, amount)| Some((id.as_ref()?, amount)))
+    {
+        match blockchain.asset(id) {
+            Some(asset) => println!("{} ({})", asset.format(*amount), hex(id)),
+            None => println!("{} of {}", amount, hex(id)),
+        }
+    }
+    println!(
+        "Stake: {}\n",
+        blockchain.stake_of(&serde_json::to_string(&user.1).unwrap())
+    );
 
     println!("Signed Loans:");
     let loans = blockchain.loans_of(&serde_json::to_string(&user.1).unwrap(), true);
     for (hash, (to, amount)) in loans {
         println!("Hash: {:X?} Amount: {} To: {}", hash, amount, to);
     }
-    let loans = blockchain.all_loans_of(&serde_json::to_string(&user.1).unwrap());
+    let loans = blockchain
+        .all_loans_of(&serde_json::to_string(&user.1).unwrap())
+        .unwrap();
     for (hash, (to, amount)) in loans {
         println!("Hash: {:X?} Amount: {} To: {}", hash, amount, to);
     }
@@ -163,6 +982,10 @@ fn loan(users: &HashMap<String, (SigningKey, VerifyingKey)>, blockchain: &mut Bl
         return;
     };
 
+    let Some(asset) = read_asset() else {
+        return;
+    };
+
     println!("Enter an amount to loan:");
     let amount: u64 = read!("{}\n");
 
@@ -171,7 +994,11 @@ fn loan(users: &HashMap<String, (SigningKey, VerifyingKey)>, blockchain: &mut Bl
         serde_json::to_string(&payee.1).unwrap(),
         amount,
         TransactionKind::Loan(None),
-    );
+    )
+    .with_nonce(blockchain.next_nonce(&serde_json::to_string(&payer.1).unwrap()));
+    if let Some(asset) = asset {
+        transaction = transaction.with_asset(asset);
+    }
 
     transaction.sign_transaction(&payer.0).unwrap();
     blockchain.add_transaction(transaction).unwrap();
@@ -198,15 +1025,13 @@ fn sign_loan(users: &HashMap<String, (SigningKey, VerifyingKey)>, blockchain: &m
     match blockchain.sign_loan(&user.0, *transactions[pos].0) {
         Ok(_) => (),
         Err(e) => match e {
-            blockchain::BlockchainError::InvalidTransaction => todo!(),
-            blockchain::BlockchainError::NegativeBalance => todo!(),
-            blockchain::BlockchainError::BalanceTooSmall => todo!(),
-            blockchain::BlockchainError::InvalidSigner => {
+            chain::BlockchainError::InvalidSigner => {
                 println!("Failed: User is not involved in this loan")
             }
-            blockchain::BlockchainError::NoTransactionFound => {
+            chain::BlockchainError::NoTransactionFound => {
                 println!("Failed: Transaction not found")
             }
+            e => println!("Failed: {:?}", e),
         },
     }
 }
@@ -0,0 +1,276 @@
+use crate::{block::Hash, encoding::Encode, transaction::Transaction};
+use indexmap::IndexMap;
+use std::{
+    cmp::Reverse,
+    collections::{BTreeMap, HashMap},
+};
+
+pub const MAX_COUNT: usize = 5000; // how many transactions may wait at once
+pub const MAX_BYTES: usize = 1 << 20; // how large the encoded waiting transactions may be
+pub const TTL: u64 = 3 * 60 * 60; // how long a transaction may wait before it is dropped
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MempoolError {
+    Duplicate,
+    Underpriced, // the pending transaction with the same sender and nonce pays at least as much
+    Full,        // every pending transaction pays at least as much
+}
+
+// What adding a transaction pushed out of the pool
+#[derive(Debug, Clone, Default)]
+pub struct Insertion {
+    pub replaced: Option<Transaction>, // the transaction with the same sender and nonce
+    pub evicted: Vec<Transaction>,     // transactions paying a lower fee
+}
+
+#[derive(Debug, Clone)]
+struct Entry {
+    transaction: Transaction,
+    size: usize,
+    added: u64,
+}
+
+// The transactions waiting to be included in a block. They are kept in the order they
+// arrived, indexed by their hash and by their sender. When the pool is over its budget the
+// transactions paying the lowest fee are evicted, newest first between equal fees.
+#[derive(Debug, Clone)]
+pub struct Mempool {
+    entries: IndexMap<Hash, Entry>,
+    senders: HashMap<String, BTreeMap<u64, Hash>>, // the pending hashes of a sender by nonce
+    bytes: usize,
+    max_count: usize,
+    max_bytes: usize,
+    ttl: u64,
+}
+
+impl Default for Mempool {
+    fn default() -> Self {
+        Self::new(MAX_COUNT, MAX_BYTES, TTL)
+    }
+}
+
+impl Mempool {
+    pub fn new(max_count: usize, max_bytes: usize, ttl: u64) -> Self {
+        Self {
+            entries: IndexMap::new(),
+            senders: HashMap::new(),
+            bytes: 0,
+            max_count,
+            max_bytes,
+            ttl,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    // the size of every pending transaction in the canonical encoding
+    pub fn bytes(&self) -> usize {
+        self.bytes
+    }
+
+    pub fn contains(&self, hash: &Hash) -> bool {
+        self.entries.contains_key(hash)
+    }
+
+    pub fn get(&self, hash: &Hash) -> Option<&Transaction> {
+        self.entries.get(hash).map(|entry| &entry.transaction)
+    }
+
+    // the pending transactions in the order they arrived
+    pub fn iter(&self) -> impl Iterator<Item = &Transaction> {
+        self.entries.values().map(|entry| &entry.transaction)
+    }
+
+    // the pending transactions of a sender ordered by nonce
+    pub fn from_sender<'a>(&'a self, address: &str) -> impl Iterator<Item = &'a Transaction> {
+        self.senders
+            .get(address)
+            .into_iter()
+            .flat_map(|nonces| nonces.values())
+            .map(|hash| &self.entries[hash].transaction)
+    }
+
+    // Adds a transaction that arrived at the given time. A pending transaction with the same
+    // sender and nonce is replaced if the new one pays a strictly higher fee. Nothing changes
+    // if it is rejected.
+    pub fn insert(
+        &mut self,
+        transaction: Transaction,
+        now: u64,
+    ) -> Result<Insertion, MempoolError> {
+        let hash = transaction.hash();
+        if self.contains(&hash) {
+            return Err(MempoolError::Duplicate);
+        }
+
+        let from = transaction.from().clone().unwrap_or_default();
+        let replacing = self
+            .senders
+            .get(&from)
+            .and_then(|nonces| nonces.get(&transaction.nonce()))
+            .copied();
+        if let Some(replacing) = replacing {
+            if self.entries[&replacing].transaction.fee() >= transaction.fee() {
+                return Err(MempoolError::Underpriced);
+            }
+        }
+
+        let size = transaction.to_bytes().len();
+        let evict = self.evictions(transaction.fee(), size, replacing)?;
+
+        let replaced = replacing.and_then(|hash| self.remove(&hash));
+        let evicted = evict.iter().filter_map(|hash| self.remove(hash)).collect();
+
+        self.bytes += size;
+        self.senders
+            .entry(from)
+            .or_default()
+            .insert(transaction.nonce(), hash);
+        self.entries.insert(
+            hash,
+            Entry {
+                transaction,
+                size,
+                added: now,
+            },
+        );
+
+        Ok(Insertion { replaced, evicted })
+    }
+
+    // what has to go to make room for a transaction besides the one it replaces, only
+    // transactions paying a lower fee may be evicted for it
+    fn evictions(
+        &self,
+        fee: u64,
+        size: usize,
+        replacing: Option<Hash>,
+    ) -> Result<Vec<Hash>, MempoolError> {
+        if size > self.max_bytes || self.max_count == 0 {
+            return Err(MempoolError::Full);
+        }
+
+        let mut candidates: Vec<_> = self
+            .entries
+            .iter()
+            .enumerate()
+            .filter(|(_, (hash, _))| Some(**hash) != replacing)
+            .collect();
+        candidates.sort_by_key(|(i, (_, entry))| (entry.transaction.fee(), Reverse(*i)));
+
+        let replaced_size = replacing.map_or(0, |hash| self.entries[&hash].size);
+        let mut count = self.len() + 1 - usize::from(replacing.is_some());
+        let mut bytes = self.bytes + size - replaced_size;
+        let mut evict = Vec::new();
+        for (_, (hash, entry)) in candidates {
+            if count <= self.max_count && bytes <= self.max_bytes {
+                break;
+            }
+
+            if entry.transaction.fee() >= fee {
+                return Err(MempoolError::Full);
+            }
+
+            evict.push(*hash);
+            count -= 1;
+            bytes -= entry.size;
+        }
+
+        Ok(evict)
+    }
+
+    pub fn remove(&mut self, hash: &Hash) -> Option<Transaction> {
+        let entry = self.entries.shift_remove(hash)?;
+        self.bytes -= entry.size;
+
+        let from = entry.transaction.from().clone().unwrap_or_default();
+        if let Some(nonces) = self.senders.get_mut(&from) {
+            nonces.remove(&entry.transaction.nonce());
+            if nonces.is_empty() {
+                self.senders.remove(&from);
+            }
+        }
+
+        Some(entry.transaction)
+    }
+
+    // keeps only the transactions the function returns true for and returns the others
+    pub fn retain(&mut self, mut keep: impl FnMut(&Transaction) -> bool) -> Vec<Transaction> {
+        let removed: Vec<_> = self
+            .entries
+            .iter()
+            .filter(|(_, entry)| !keep(&entry.transaction))
+            .map(|(hash, _)| *hash)
+            .collect();
+
+        removed
+            .iter()
+            .filter_map(|hash| self.remove(hash))
+            .collect()
+    }
+
+    // Drops and returns the transactions that have waited longer than the time to live. A
+    // locked transaction is held until it could be included in the next block, the height of
+    // which is given, and only then starts to age.
+    pub fn expire(&mut self, now: u64, height: u64) -> Vec<Transaction> {
+        for entry in self.entries.values_mut() {
+            if !entry.transaction.is_mature(height, now) {
+                entry.added = now;
+            }
+        }
+
+        let ttl = self.ttl;
+        let expired: Vec<_> = self
+            .entries
+            .iter()
+            .filter(|(_, entry)| now.saturating_sub(entry.added) > ttl)
+            .map(|(hash, _)| *hash)
+            .collect();
+
+        expired
+            .iter()
+            .filter_map(|hash| self.remove(hash))
+            .collect()
+    }
+
+    // Changes a pending transaction in place, the change must not alter its hash. A change
+    // that makes it larger is checked against the budget like an insert, evicting transactions
+    // paying a lower fee. If it still does not fit it is left as it was.
+    pub fn update<T>(
+        &mut self,
+        hash: &Hash,
+        change: impl FnOnce(&mut Transaction) -> T,
+    ) -> Option<Result<(T, Vec<Transaction>), MempoolError>> {
+        let entry = self.entries.get_mut(hash)?;
+        let before = entry.transaction.clone();
+        let result = change(&mut entry.transaction);
+        assert_eq!(
+            entry.transaction.hash(),
+            *hash,
+            "A pending transaction must keep its hash"
+        );
+
+        let size = entry.transaction.to_bytes().len();
+        let fee = entry.transaction.fee();
+        let evict = match self.evictions(fee, size, Some(*hash)) {
+            Ok(evict) => evict,
+            Err(error) => {
+                self.entries[hash].transaction = before;
+                return Some(Err(error));
+            }
+        };
+        let evicted = evict.iter().filter_map(|hash| self.remove(hash)).collect();
+
+        let entry = &mut self.entries[hash];
+        self.bytes = self.bytes - entry.size + size;
+        entry.size = size;
+
+        Some(Ok((result, evicted)))
+    }
+}
@@ -0,0 +1,109 @@
+use crate::{
+    block::Hash,
+    encoding::{Decode, DecodeError, Encode, Reader},
+};
+use sha2::Digest;
+
+// A binary merkle tree over the ids of the transactions in a block. Leaves and inner nodes
+// are hashed with different prefixes so one can never be passed off as the other, and a
+// node without a sibling is moved up a level unchanged.
+
+const LEAF_PREFIX: u8 = 0;
+const NODE_PREFIX: u8 = 1;
+
+// a node next to the path from a leaf to the root and the side of the path it is on
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Sibling {
+    Left(Hash),
+    Right(Hash),
+}
+
+impl Encode for Sibling {
+    fn encode(&self, out: &mut Vec<u8>) {
+        let (tag, hash) = match self {
+            Sibling::Left(hash) => (0u8, hash),
+            Sibling::Right(hash) => (1, hash),
+        };
+        tag.encode(out);
+        hash.encode(out);
+    }
+}
+
+impl Decode for Sibling {
+    fn decode(reader: &mut Reader) -> Result<Self, DecodeError> {
+        match reader.u8()? {
+            0 => Ok(Sibling::Left(reader.hash()?)),
+            1 => Ok(Sibling::Right(reader.hash()?)),
+            tag => Err(DecodeError::InvalidTag(tag)),
+        }
+    }
+}
+
+pub fn leaf_hash(leaf: &Hash) -> Hash {
+    sha2::Sha256::digest([&[LEAF_PREFIX][..], leaf].concat()).into()
+}
+
+pub fn node_hash(left: &Hash, right: &Hash) -> Hash {
+    sha2::Sha256::digest([&[NODE_PREFIX][..], left, right].concat()).into()
+}
+
+// the root of the tree, an empty tree has the hash of no bytes as its root
+pub fn root(leaves: &[Hash]) -> Hash {
+    if leaves.is_empty() {
+        return sha2::Sha256::digest([]).into();
+    }
+
+    let mut level: Vec<_> = leaves.iter().map(leaf_hash).collect();
+    while level.len() > 1 {
+        level = parents(&level);
+    }
+
+    level[0]
+}
+
+// the siblings on the way from a leaf to the root, a level where the node has no sibling is
+// left out as the node is moved up unchanged
+pub fn proof(leaves: &[Hash], index: usize) -> Option<Vec<Sibling>> {
+    if index >= leaves.len() {
+        return None;
+    }
+
+    let mut siblings = Vec::new();
+    let mut index = index;
+    let mut level: Vec<_> = leaves.iter().map(leaf_hash).collect();
+    while level.len() > 1 {
+        if index % 2 == 1 {
+            siblings.push(Sibling::Left(level[index - 1]));
+        } else if let Some(right) = level.get(index + 1) {
+            siblings.push(Sibling::Right(*right));
+        }
+
+        level = parents(&level);
+        index /= 2;
+    }
+
+    Some(siblings)
+}
+
+// checks that the siblings lead from the leaf to the root
+pub fn verify(root: &Hash, leaf: &Hash, siblings: &[Sibling]) -> bool {
+    let node = siblings
+        .iter()
+        .fold(leaf_hash(leaf), |node, sibling| match sibling {
+            Sibling::Left(left) => node_hash(left, &node),
+            Sibling::Right(right) => node_hash(&node, right),
+        });
+
+    node == *root
+}
+
+fn parents(level: &[Hash]) -> Vec<Hash> {
+    level
+        .chunks(2)
+        .map(|pair| match pair {
+            [left, right] => node_hash(left, right),
+            [single] => *single,
+            _ => unreachable!(),
+        })
+        .collect()
+}
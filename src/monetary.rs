@@ -0,0 +1,81 @@
+use serde::{Deserialize, Serialize};
+
+// How the block reward changes with the height of the block
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RewardSchedule {
+    #[default]
+    Constant,
+    // the reward halves every interval blocks
+    Halving {
+        interval: u64,
+    },
+    // the reward shrinks by a percentage every interval blocks
+    Decay {
+        interval: u64,
+        percent: u64,
+    },
+}
+
+// The rules for how many new coins are created
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MonetaryPolicy {
+    initial_reward: u64,
+    schedule: RewardSchedule,
+    max_supply: Option<u64>,
+}
+
+impl MonetaryPolicy {
+    pub fn new(initial_reward: u64, schedule: RewardSchedule, max_supply: Option<u64>) -> Self {
+        Self {
+            initial_reward,
+            schedule,
+            max_supply,
+        }
+    }
+
+    // the reward the schedule gives a block ignoring the supply cap
+    pub fn scheduled_reward(&self, height: u64) -> u64 {
+        match self.schedule {
+            RewardSchedule::Constant => self.initial_reward,
+            RewardSchedule::Halving { interval } => {
+                let halvings = height / interval.max(1);
+                u32::try_from(halvings)
+                    .ok()
+                    .and_then(|halvings| self.initial_reward.checked_shr(halvings))
+                    .unwrap_or(0)
+            }
+            RewardSchedule::Decay { interval, percent } => {
+                let percent = percent.min(100);
+                if percent == 0 {
+                    return self.initial_reward;
+                }
+
+                // every step takes at least 1 off the reward so it reaches 0 within a few
+                // thousand steps whatever the height
+                let mut reward = self.initial_reward;
+                for _ in 0..height / interval.max(1) {
+                    if reward == 0 {
+                        break;
+                    }
+                    // a large reward would overflow before it is divided
+                    reward = (reward as u128 * (100 - percent) as u128 / 100) as u64;
+                }
+                reward
+            }
+        }
+    }
+
+    // the reward a block at the height may create given the coins that already exist
+    pub fn reward(&self, height: u64, supply: u64) -> u64 {
+        let reward = self.scheduled_reward(height);
+        match self.max_supply {
+            Some(max_supply) => reward.min(max_supply.saturating_sub(supply)),
+            None => reward,
+        }
+    }
+
+    pub fn max_supply(&self) -> Option<u64> {
+        self.max_supply
+    }
+}
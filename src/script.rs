@@ -0,0 +1,434 @@
+use crate::{
+    encoding::{encode_list, hex, Decode, DecodeError, Encode, Reader},
+    signature,
+    transaction::{Lock, Transaction},
+};
+use sha2::Digest;
+
+pub const SCRIPT_PREFIX: &str = "script:"; // addresses that are the hash of a script start with this
+pub const MAX_STEPS: u64 = 1000; // the gas a script may use
+pub const SIGNITURE_STEPS: u64 = 100; // the gas every signiture check uses
+pub const MAX_STACK: usize = 100; // how many items the stack may hold
+pub const MAX_ITEM: usize = 520; // how large an item on the stack may be
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ScriptError {
+    StepLimit,
+    StackUnderflow,
+    StackOverflow,
+    ItemTooLarge,
+    InvalidNumber,
+    VerifyFailed,
+    UnbalancedIf,
+    LockNotMet, // the transaction is not locked until at least what the script requires
+    False,      // the script finished without leaving true on the stack
+}
+
+// An instruction of a script. Items on the stack are bytes, numbers are 8 byte big endian and
+// an item is true if any of its bytes is not zero.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Op {
+    Push(Vec<u8>),
+    Dup,
+    Drop,
+    Swap,
+    Equal,       // pushes whether the top two items are the same
+    EqualVerify, // fails unless the top two items are the same
+    Verify,      // fails unless the top item is true
+    Not,         // pushes whether the top item is false
+    Sha256,      // replaces the top item with its hash
+    CheckSig,    // pops an address and a signiture and pushes whether it signed the transaction
+    CheckSigVerify,
+    CheckMultiSig, // pops n, n addresses, m and m signitures in the same order as the addresses
+    CheckLockHeight, // fails unless the transaction is locked until at least the height on top
+    CheckLockTime, // fails unless the transaction is locked until at least the time on top
+    If,            // pops the top item and runs until Else or EndIf only if it is true
+    Else,
+    EndIf,
+}
+
+impl Op {
+    pub fn number(value: u64) -> Self {
+        Op::Push(value.to_be_bytes().to_vec())
+    }
+
+    fn tag(&self) -> u8 {
+        match self {
+            Op::Push(_) => 0,
+            Op::Dup => 1,
+            Op::Drop => 2,
+            Op::Swap => 3,
+            Op::Equal => 4,
+            Op::EqualVerify => 5,
+            Op::Verify => 6,
+            Op::Not => 7,
+            Op::Sha256 => 8,
+            Op::CheckSig => 9,
+            Op::CheckSigVerify => 10,
+            Op::CheckMultiSig => 11,
+            Op::CheckLockHeight => 12,
+            Op::CheckLockTime => 13,
+            Op::If => 14,
+            Op::Else => 15,
+            Op::EndIf => 16,
+        }
+    }
+}
+
+impl Encode for Op {
+    fn encode(&self, out: &mut Vec<u8>) {
+        self.tag().encode(out);
+        if let Op::Push(bytes) = self {
+            bytes.encode(out);
+        }
+    }
+}
+
+impl Decode for Op {
+    fn decode(reader: &mut Reader) -> Result<Self, DecodeError> {
+        Ok(match reader.u8()? {
+            0 => Op::Push(reader.bytes()?),
+            1 => Op::Dup,
+            2 => Op::Drop,
+            3 => Op::Swap,
+            4 => Op::Equal,
+            5 => Op::EqualVerify,
+            6 => Op::Verify,
+            7 => Op::Not,
+            8 => Op::Sha256,
+            9 => Op::CheckSig,
+            10 => Op::CheckSigVerify,
+            11 => Op::CheckMultiSig,
+            12 => Op::CheckLockHeight,
+            13 => Op::CheckLockTime,
+            14 => Op::If,
+            15 => Op::Else,
+            16 => Op::EndIf,
+            tag => return Err(DecodeError::InvalidTag(tag)),
+        })
+    }
+}
+
+// The conditions for spending from an address. Coins sent to the address of a script can be
+// spent by a transaction that reveals the script along with a witness, the items that are
+// pushed before the script runs, that makes the script finish with true on the stack.
+// Plain addresses use the script that checks a signiture of the address.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Script(Vec<Op>);
+
+impl Script {
+    pub fn new(ops: Vec<Op>) -> Self {
+        Self(ops)
+    }
+
+    pub fn ops(&self) -> &[Op] {
+        &self.0
+    }
+
+    // the address coins are sent to so that only this script can spend them
+    pub fn address(&self) -> String {
+        let hash = sha2::Sha256::digest(self.to_bytes());
+
+        format!("{}{}", SCRIPT_PREFIX, hex(&hash))
+    }
+
+    // the default script of a plain address, the witness is its signiture
+    pub fn pay_to_address(address: &str) -> Self {
+        Self(vec![Op::Push(address.as_bytes().to_vec()), Op::CheckSig])
+    }
+
+    // m of the addresses have to sign, the witness is their signitures in the same order
+    pub fn multisig(required: u64, addresses: &[&str]) -> Self {
+        let mut ops = vec![Op::number(required)];
+        ops.extend(
+            addresses
+                .iter()
+                .map(|address| Op::Push(address.as_bytes().to_vec())),
+        );
+        ops.push(Op::number(addresses.len() as u64));
+        ops.push(Op::CheckMultiSig);
+
+        Self(ops)
+    }
+
+    // the address has to sign and reveal what hashes to the hash, the witness is the signiture
+    // and then the secret
+    pub fn hashlock(hash: [u8; 32], address: &str) -> Self {
+        Self(vec![
+            Op::Sha256,
+            Op::Push(hash.to_vec()),
+            Op::EqualVerify,
+            Op::Push(address.as_bytes().to_vec()),
+            Op::CheckSig,
+        ])
+    }
+
+    // the address can only spend once the chain reaches the height
+    pub fn timelock(height: u64, address: &str) -> Self {
+        Self(vec![
+            Op::number(height),
+            Op::CheckLockHeight,
+            Op::Drop,
+            Op::Push(address.as_bytes().to_vec()),
+            Op::CheckSig,
+        ])
+    }
+
+    // any two of the buyer, the seller and the arbiter have to sign so the arbiter decides
+    // when the other two disagree
+    pub fn escrow(buyer: &str, seller: &str, arbiter: &str) -> Self {
+        Self::multisig(2, &[buyer, seller, arbiter])
+    }
+
+    // runs the script for a transaction after pushing the witness
+    pub fn execute(
+        &self,
+        transaction: &Transaction,
+        witness: &[Vec<u8>],
+    ) -> Result<(), ScriptError> {
+        let mut machine = Machine {
+            transaction,
+            stack: Vec::new(),
+            executing: Vec::new(),
+            steps: 0,
+        };
+
+        for item in witness {
+            machine.push(item.clone())?;
+        }
+        for op in &self.0 {
+            machine.step(op)?;
+        }
+
+        if !machine.executing.is_empty() {
+            return Err(ScriptError::UnbalancedIf);
+        }
+
+        match machine.stack.last() {
+            Some(item) if is_true(item) => Ok(()),
+            _ => Err(ScriptError::False),
+        }
+    }
+}
+
+impl Encode for Script {
+    fn encode(&self, out: &mut Vec<u8>) {
+        encode_list(&self.0, out);
+    }
+}
+
+impl Decode for Script {
+    fn decode(reader: &mut Reader) -> Result<Self, DecodeError> {
+        Ok(Self(reader.list()?))
+    }
+}
+
+pub fn is_script_address(address: &str) -> bool {
+    address.starts_with(SCRIPT_PREFIX)
+}
+
+fn is_true(item: &[u8]) -> bool {
+    item.iter().any(|byte| *byte != 0)
+}
+
+fn bool_item(value: bool) -> Vec<u8> {
+    if value {
+        vec![1]
+    } else {
+        Vec::new()
+    }
+}
+
+struct Machine<'a> {
+    transaction: &'a Transaction,
+    stack: Vec<Vec<u8>>,
+    executing: Vec<bool>, // for every If that was entered whether its branch runs
+    steps: u64,
+}
+
+impl Machine<'_> {
+    fn charge(&mut self, steps: u64) -> Result<(), ScriptError> {
+        self.steps += steps;
+        if self.steps > MAX_STEPS {
+            return Err(ScriptError::StepLimit);
+        }
+
+        Ok(())
+    }
+
+    fn push(&mut self, item: Vec<u8>) -> Result<(), ScriptError> {
+        if item.len() > MAX_ITEM {
+            return Err(ScriptError::ItemTooLarge);
+        }
+        if self.stack.len() >= MAX_STACK {
+            return Err(ScriptError::StackOverflow);
+        }
+
+        self.stack.push(item);
+        Ok(())
+    }
+
+    fn pop(&mut self) -> Result<Vec<u8>, ScriptError> {
+        self.stack.pop().ok_or(ScriptError::StackUnderflow)
+    }
+
+    fn pop_number(&mut self) -> Result<u64, ScriptError> {
+        let item = self.pop()?;
+        let bytes: [u8; 8] = item.try_into().map_err(|_| ScriptError::InvalidNumber)?;
+
+        Ok(u64::from_be_bytes(bytes))
+    }
+
+    fn check_signiture(&mut self, address: &[u8], signiture: &[u8]) -> Result<bool, ScriptError> {
+        self.charge(SIGNITURE_STEPS)?;
+        let Ok(address) = std::str::from_utf8(address) else {
+            return Ok(false);
+        };
+
+        Ok(signature::verify(
+            address,
+            &self.transaction.hash(),
+            signiture,
+        ))
+    }
+
+    fn check_multisig(&mut self) -> Result<bool, ScriptError> {
+        let count = self.pop_number()? as usize;
+        if count > self.stack.len() {
+            return Err(ScriptError::StackUnderflow);
+        }
+        let addresses = self.stack.split_off(self.stack.len() - count);
+
+        let required = self.pop_number()? as usize;
+        if required > self.stack.len() || required > count {
+            return Err(ScriptError::StackUnderflow);
+        }
+        let signitures = self.stack.split_off(self.stack.len() - required);
+
+        // the signitures must be in the same order as their addresses so each address is
+        // only tried once
+        let mut addresses = addresses.iter();
+        for signiture in &signitures {
+            loop {
+                let Some(address) = addresses.next() else {
+                    return Ok(false);
+                };
+                if self.check_signiture(address, signiture)? {
+                    break;
+                }
+            }
+        }
+
+        Ok(true)
+    }
+
+    fn check_lock(&mut self, height: bool) -> Result<(), ScriptError> {
+        let required: [u8; 8] = self
+            .stack
+            .last()
+            .ok_or(ScriptError::StackUnderflow)?
+            .as_slice()
+            .try_into()
+            .map_err(|_| ScriptError::InvalidNumber)?;
+        let required = u64::from_be_bytes(required);
+
+        let met = match (self.transaction.lock(), height) {
+            (Some(Lock::Height(lock)), true) | (Some(Lock::Time(lock)), false) => lock >= required,
+            _ => false,
+        };
+        if !met {
+            return Err(ScriptError::LockNotMet);
+        }
+
+        Ok(())
+    }
+
+    fn step(&mut self, op: &Op) -> Result<(), ScriptError> {
+        self.charge(1)?;
+
+        // branches that do not run only keep track of nested ifs
+        let running = self.executing.iter().all(|branch| *branch);
+        match op {
+            Op::If => {
+                let branch = running && is_true(&self.pop()?);
+                self.executing.push(branch);
+                return Ok(());
+            }
+            Op::Else => {
+                let branch = self.executing.pop().ok_or(ScriptError::UnbalancedIf)?;
+                let parent_running = self.executing.iter().all(|branch| *branch);
+                self.executing.push(parent_running && !branch);
+                return Ok(());
+            }
+            Op::EndIf => {
+                self.executing.pop().ok_or(ScriptError::UnbalancedIf)?;
+                return Ok(());
+            }
+            _ if !running => return Ok(()),
+            _ => (),
+        }
+
+        match op {
+            Op::Push(bytes) => self.push(bytes.clone())?,
+            Op::Dup => {
+                let item = self
+                    .stack
+                    .last()
+                    .ok_or(ScriptError::StackUnderflow)?
+                    .clone();
+                self.push(item)?;
+            }
+            Op::Drop => {
+                self.pop()?;
+            }
+            Op::Swap => {
+                let top = self.pop()?;
+                let below = self.pop()?;
+                self.push(top)?;
+                self.push(below)?;
+            }
+            Op::Equal | Op::EqualVerify => {
+                let equal = self.pop()? == self.pop()?;
+                if *op == Op::EqualVerify && !equal {
+                    return Err(ScriptError::VerifyFailed);
+                }
+                if *op == Op::Equal {
+                    self.push(bool_item(equal))?;
+                }
+            }
+            Op::Verify => {
+                if !is_true(&self.pop()?) {
+                    return Err(ScriptError::VerifyFailed);
+                }
+            }
+            Op::Not => {
+                let item = self.pop()?;
+                self.push(bool_item(!is_true(&item)))?;
+            }
+            Op::Sha256 => {
+                let item = self.pop()?;
+                self.push(sha2::Sha256::digest(item).to_vec())?;
+            }
+            Op::CheckSig | Op::CheckSigVerify => {
+                let address = self.pop()?;
+                let signiture = self.pop()?;
+                let valid = self.check_signiture(&address, &signiture)?;
+                if *op == Op::CheckSigVerify && !valid {
+                    return Err(ScriptError::VerifyFailed);
+                }
+                if *op == Op::CheckSig {
+                    self.push(bool_item(valid))?;
+                }
+            }
+            Op::CheckMultiSig => {
+                let valid = self.check_multisig()?;
+                self.push(bool_item(valid))?;
+            }
+            Op::CheckLockHeight => self.check_lock(true)?,
+            Op::CheckLockTime => self.check_lock(false)?,
+            Op::If | Op::Else | Op::EndIf => unreachable!("Branches are handled above"),
+        }
+
+        Ok(())
+    }
+}
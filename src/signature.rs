@@ -0,0 +1,160 @@
+use crate::{block::Hash, encoding::parse_hex};
+use elliptic_curve::{
+    group::Group,
+    ops::{LinearCombinationExt, Reduce},
+    point::DecompactPoint,
+    rand_core::OsRng,
+    Field, PrimeField,
+};
+use k256::{
+    ecdsa::{self, VerifyingKey},
+    schnorr::{self, signature::Verifier},
+    AffinePoint, FieldBytes, ProjectivePoint, Scalar, U256,
+};
+use sha2::Digest;
+
+const CHALLENGE_TAG: &[u8] = b"BIP0340/challenge";
+
+// How a transaction is signed. The address of the sender must be of the same kind, ECDSA
+// addresses are the json encoded public key and Schnorr addresses are the hex encoded
+// x-only public key from BIP340.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SignatureScheme {
+    #[default]
+    Ecdsa,
+    Schnorr,
+}
+
+impl SignatureScheme {
+    pub fn tag(&self) -> u8 {
+        match self {
+            SignatureScheme::Ecdsa => 0,
+            SignatureScheme::Schnorr => 1,
+        }
+    }
+
+    pub fn from_tag(tag: u8) -> Option<Self> {
+        match tag {
+            0 => Some(SignatureScheme::Ecdsa),
+            1 => Some(SignatureScheme::Schnorr),
+            _ => None,
+        }
+    }
+
+    // works out the scheme from the format of an address
+    pub fn of_address(address: &str) -> Self {
+        if address.len() == 64 && address.bytes().all(|byte| byte.is_ascii_hexdigit()) {
+            SignatureScheme::Schnorr
+        } else {
+            SignatureScheme::Ecdsa
+        }
+    }
+}
+
+// A public key parsed from an address, parsing is slow so these are worth keeping around
+#[derive(Debug, Clone)]
+pub enum PublicKey {
+    Ecdsa(VerifyingKey),
+    Schnorr(schnorr::VerifyingKey),
+}
+
+impl PublicKey {
+    pub fn parse(address: &str) -> Option<Self> {
+        match SignatureScheme::of_address(address) {
+            SignatureScheme::Ecdsa => serde_json::from_str(address).ok().map(PublicKey::Ecdsa),
+            SignatureScheme::Schnorr => {
+                let bytes = parse_hex(address)?;
+
+                schnorr::VerifyingKey::from_bytes(&bytes)
+                    .ok()
+                    .map(PublicKey::Schnorr)
+            }
+        }
+    }
+
+    pub fn scheme(&self) -> SignatureScheme {
+        match self {
+            PublicKey::Ecdsa(_) => SignatureScheme::Ecdsa,
+            PublicKey::Schnorr(_) => SignatureScheme::Schnorr,
+        }
+    }
+
+    // checks that the owner of the key signed the hash
+    pub fn verify(&self, hash: &Hash, signiture: &[u8]) -> bool {
+        match self {
+            PublicKey::Ecdsa(public_key) => {
+                let Ok(signiture) = ecdsa::Signature::from_slice(signiture) else {
+                    return false;
+                };
+
+                public_key.verify(hash, &signiture).is_ok()
+            }
+            PublicKey::Schnorr(public_key) => {
+                // parsing a signiture of the wrong length panics
+                if signiture.len() != schnorr::Signature::BYTE_SIZE {
+                    return false;
+                }
+                let Ok(signiture) = schnorr::Signature::try_from(signiture) else {
+                    return false;
+                };
+
+                public_key.verify_raw(hash, &signiture).is_ok()
+            }
+        }
+    }
+}
+
+// checks that the owner of the address signed the hash
+pub fn verify(address: &str, hash: &Hash, signiture: &[u8]) -> bool {
+    PublicKey::parse(address).is_some_and(|public_key| public_key.verify(hash, signiture))
+}
+
+// Checks many Schnorr signatures at once as described in BIP340. Every signature gets a
+// random weight and all of them are checked with one equation
+//     (a1*s1 + ... + an*sn)G = a1*R1 + ... + an*Rn + a1*e1*P1 + ... + an*en*Pn
+// which only holds for all the weights if every signature is valid. Both sides are computed
+// as a single multi scalar multiplication which is what makes it faster than checking each.
+pub fn verify_schnorr_batch(signitures: &[(&schnorr::VerifyingKey, Hash, &[u8])]) -> bool {
+    let mut s_sum = Scalar::ZERO;
+    let mut terms = Vec::with_capacity(2 * signitures.len() + 1);
+
+    for (i, (public_key, hash, signiture)) in signitures.iter().enumerate() {
+        if signiture.len() != schnorr::Signature::BYTE_SIZE {
+            return false;
+        }
+        let (r_bytes, s_bytes) = signiture.split_at(32);
+        let r_bytes = FieldBytes::from(<[u8; 32]>::try_from(r_bytes).unwrap());
+        let s_bytes = FieldBytes::from(<[u8; 32]>::try_from(s_bytes).unwrap());
+
+        let Some(r) = Option::<AffinePoint>::from(AffinePoint::decompact(&r_bytes)) else {
+            return false;
+        };
+        let Some(s) = Option::<Scalar>::from(Scalar::from_repr(s_bytes)) else {
+            return false;
+        };
+
+        let challenge = sha2::Sha256::new()
+            .chain_update(sha2::Sha256::digest(CHALLENGE_TAG))
+            .chain_update(sha2::Sha256::digest(CHALLENGE_TAG))
+            .chain_update(r_bytes)
+            .chain_update(public_key.to_bytes())
+            .chain_update(hash)
+            .finalize();
+        let e = <Scalar as Reduce<U256>>::reduce_bytes(&challenge);
+
+        // the first weight can be one without weakening the check
+        let a = if i == 0 {
+            Scalar::ONE
+        } else {
+            Scalar::random(&mut OsRng)
+        };
+
+        s_sum += a * s;
+        terms.push((ProjectivePoint::from(r), a));
+        terms.push((ProjectivePoint::from(*public_key.as_affine()), a * e));
+    }
+
+    terms.push((ProjectivePoint::GENERATOR, -s_sum));
+    let sum = <ProjectivePoint as LinearCombinationExt<[_]>>::lincomb_ext(&terms);
+    bool::from(sum.is_identity())
+}
@@ -0,0 +1,52 @@
+use crate::{
+    block::Hash,
+    chain_spec::ConsensusSpec,
+    encoding::{Decode, DecodeError, Encode, Reader},
+    state::ChainState,
+};
+use sha2::Digest;
+
+// The state of a chain after the block at a height, which is all a node needs to carry on
+// from there without the blocks before it. The engine is kept as a checkpoint as proof of
+// stake and proof of authority pick sealers from the transactions of the chain.
+#[derive(Debug, Clone)]
+pub struct Snapshot {
+    pub height: u64,
+    pub hash: Hash, // the hash of the block at the height
+    pub consensus: ConsensusSpec,
+    pub state: ChainState,
+}
+
+impl Snapshot {
+    // commits to the height, the block, the engine and the root of the state, a node should
+    // only start from a snapshot whose root it got from a node it trusts
+    pub fn root(&self) -> Hash {
+        let mut bytes = Vec::new();
+        self.height.encode(&mut bytes);
+        self.hash.encode(&mut bytes);
+        self.consensus.encode(&mut bytes);
+        self.state.root().encode(&mut bytes);
+
+        sha2::Sha256::digest(bytes).into()
+    }
+}
+
+impl Encode for Snapshot {
+    fn encode(&self, out: &mut Vec<u8>) {
+        self.height.encode(out);
+        self.hash.encode(out);
+        self.consensus.encode(out);
+        self.state.encode(out);
+    }
+}
+
+impl Decode for Snapshot {
+    fn decode(reader: &mut Reader) -> Result<Self, DecodeError> {
+        Ok(Self {
+            height: reader.u64()?,
+            hash: reader.hash()?,
+            consensus: ConsensusSpec::decode(reader)?,
+            state: ChainState::decode(reader)?,
+        })
+    }
+}
@@ -0,0 +1,151 @@
+use crate::{
+    block::Hash,
+    encoding::{encode_list, Decode, DecodeError, Encode, Reader},
+};
+use sha2::Digest;
+use std::collections::BTreeMap;
+
+// A sparse merkle tree over 256 bit keys where the path to a key follows its bits from the
+// most significant one. A subtree without keys hashes to zero and a subtree with a single key
+// hashes to the leaf of that key, so the tree is only as deep as it needs to be to tell its
+// keys apart.
+
+const LEAF_PREFIX: u8 = 0;
+const NODE_PREFIX: u8 = 1;
+const MAX_DEPTH: usize = 256;
+
+pub const EMPTY: Hash = [0; 32];
+
+pub fn leaf_hash(key: &Hash, value: &Hash) -> Hash {
+    sha2::Sha256::digest([&[LEAF_PREFIX][..], key, value].concat()).into()
+}
+
+pub fn node_hash(left: &Hash, right: &Hash) -> Hash {
+    sha2::Sha256::digest([&[NODE_PREFIX][..], left, right].concat()).into()
+}
+
+// whether the path to the key goes right at the depth
+fn bit(key: &Hash, depth: usize) -> bool {
+    key[depth / 8] >> (7 - depth % 8) & 1 == 1
+}
+
+// the root of the tree holding the values by key
+pub fn root(entries: &BTreeMap<Hash, Hash>) -> Hash {
+    let entries: Vec<_> = entries.iter().collect();
+    subtree(&entries, 0)
+}
+
+// the hash of the subtree at the depth holding the entries, which are sorted by key
+fn subtree(entries: &[(&Hash, &Hash)], depth: usize) -> Hash {
+    match entries {
+        [] => EMPTY,
+        [(key, value)] => leaf_hash(key, value),
+        _ => {
+            let split = entries.partition_point(|(key, _)| !bit(key, depth));
+            node_hash(
+                &subtree(&entries[..split], depth + 1),
+                &subtree(&entries[split..], depth + 1),
+            )
+        }
+    }
+}
+
+// The siblings on the path to a key from the root down and the leaf the path ends in, if it
+// does not end in an empty subtree. The key is in the tree only if the leaf is its own.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Proof {
+    pub siblings: Vec<Hash>,
+    pub leaf: Option<(Hash, Hash)>, // the key and value of the leaf
+}
+
+pub fn prove(entries: &BTreeMap<Hash, Hash>, key: &Hash) -> Proof {
+    let entries: Vec<_> = entries.iter().collect();
+    let mut path = &entries[..];
+    let mut siblings = Vec::new();
+    while path.len() > 1 {
+        let depth = siblings.len();
+        let (left, right) = path.split_at(path.partition_point(|(key, _)| !bit(key, depth)));
+        if bit(key, depth) {
+            siblings.push(subtree(left, depth + 1));
+            path = right;
+        } else {
+            siblings.push(subtree(right, depth + 1));
+            path = left;
+        }
+    }
+
+    Proof {
+        siblings,
+        leaf: path.first().map(|(key, value)| (**key, **value)),
+    }
+}
+
+impl Proof {
+    // the root the proof leads to if the key has the value, or is not in the tree if there is
+    // no value. None if the proof does not show that.
+    pub fn root(&self, key: &Hash, value: Option<&Hash>) -> Option<Hash> {
+        let depth = self.siblings.len();
+        if depth > MAX_DEPTH {
+            return None;
+        }
+
+        let node = match (&self.leaf, value) {
+            (None, None) => EMPTY,
+            (Some((leaf_key, leaf_value)), _) => {
+                // the leaf has to be on the path to the key
+                if (0..depth).any(|depth| bit(leaf_key, depth) != bit(key, depth)) {
+                    return None;
+                }
+
+                match value {
+                    Some(value) if leaf_key == key && leaf_value == value => (),
+                    None if leaf_key != key => (),
+                    _ => return None,
+                }
+                leaf_hash(leaf_key, leaf_value)
+            }
+            (None, Some(_)) => return None,
+        };
+
+        Some(
+            self.siblings
+                .iter()
+                .enumerate()
+                .rev()
+                .fold(node, |node, (depth, sibling)| {
+                    if bit(key, depth) {
+                        node_hash(sibling, &node)
+                    } else {
+                        node_hash(&node, sibling)
+                    }
+                }),
+        )
+    }
+
+    pub fn verify(&self, root: &Hash, key: &Hash, value: Option<&Hash>) -> bool {
+        self.root(key, value).as_ref() == Some(root)
+    }
+}
+
+impl Encode for Proof {
+    fn encode(&self, out: &mut Vec<u8>) {
+        encode_list(&self.siblings, out);
+        match &self.leaf {
+            Some((key, value)) => {
+                1u8.encode(out);
+                key.encode(out);
+                value.encode(out);
+            }
+            None => 0u8.encode(out),
+        }
+    }
+}
+
+impl Decode for Proof {
+    fn decode(reader: &mut Reader) -> Result<Self, DecodeError> {
+        Ok(Self {
+            siblings: reader.list_with(Reader::hash)?,
+            leaf: reader.option(|reader| Ok((reader.hash()?, reader.hash()?)))?,
+        })
+    }
+}
@@ -0,0 +1,660 @@
+use crate::{
+    account::{self, Account, AccountProof},
+    asset::{Asset, AssetId},
+    block::{Block, BlockError, Hash},
+    chain_spec::Ledger,
+    encoding::{Decode, DecodeError, Encode, Reader},
+    escrow::Escrow,
+    monetary::MonetaryPolicy,
+    sparse_merkle,
+    transaction::{Lock, Transaction, TransactionKind},
+    utxo::{OutPoint, UtxoSet},
+};
+use sha2::Digest;
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+// The balances, assets, debts, open escrows and unspent outputs after applying a chain of
+// blocks. Every rule a block must follow that depends on what came before
+// it is checked here.
+#[derive(Debug, Clone, Default)]
+pub struct ChainState {
+    balances: HashMap<(String, Option<AssetId>), u64>, // none is the coin of the chain
+    assets: HashMap<AssetId, Asset>,
+    debts: HashMap<(String, String, Option<AssetId>), u64>, // what a borrower owes a lender
+    nonces: HashMap<String, u64>, // the nonce the next transaction of a sender must use
+    stakes: HashMap<String, u64>, // the coins each address has locked by staking
+    escrows: HashMap<Hash, Escrow>, // the escrows that were neither released nor refunded
+    utxos: Option<UtxoSet>,       // only kept on a chain that uses the UTXO model
+    supply: u64,
+}
+
+impl ChainState {
+    // the genesis block is trusted so its allocations are applied without any checks
+    pub fn genesis(block: &Block, ledger: Ledger) -> Self {
+        let mut state = Self {
+            utxos: (ledger == Ledger::Utxo).then(UtxoSet::new),
+            ..Self::default()
+        };
+        for transaction in block.transactions() {
+            state.credit(transaction.to(), None, transaction.amount());
+            state.create_outputs(transaction);
+            state.supply += transaction.amount();
+        }
+
+        state
+    }
+
+    // replays a whole chain without checking it
+    pub fn replay(blocks: &[Block], ledger: Ledger) -> Self {
+        let mut state = Self::genesis(&blocks[0], ledger);
+        for block in &blocks[1..] {
+            state.apply_unchecked_block(block);
+        }
+
+        state
+    }
+
+    // applies a block that is already part of the chain without checking it
+    pub fn apply_unchecked_block(&mut self, block: &Block) {
+        for transaction in block.transactions() {
+            self.apply_unchecked(transaction);
+        }
+        self.supply += Self::minted(block);
+    }
+
+    // the coins a block created, which is what its coinbase paid minus the fees it collected
+    fn minted(block: &Block) -> u64 {
+        let (coinbase, fees) =
+            block
+                .transactions()
+                .iter()
+                .fold((0u64, 0u64), |(coinbase, fees), transaction| {
+                    if transaction.is_coinbase() {
+                        (coinbase + transaction.amount(), fees)
+                    } else {
+                        (coinbase, fees + transaction.fee())
+                    }
+                });
+
+        coinbase.saturating_sub(fees)
+    }
+
+    // what the address holds of an asset, none is the coin of the chain
+    pub fn balance_of(&self, address: &str, asset: Option<AssetId>) -> u64 {
+        self.balances
+            .get(&(address.to_owned(), asset))
+            .copied()
+            .unwrap_or_default()
+    }
+
+    // everything the address holds by asset
+    pub fn balances_of(&self, address: &str) -> BTreeMap<Option<AssetId>, u64> {
+        self.balances
+            .iter()
+            .filter(|((owner, _), balance)| owner == address && **balance > 0)
+            .map(|((_, asset), balance)| (*asset, *balance))
+            .collect()
+    }
+
+    pub fn asset(&self, id: &AssetId) -> Option<&Asset> {
+        self.assets.get(id)
+    }
+
+    // every asset that has been issued
+    pub fn assets(&self) -> impl Iterator<Item = &Asset> {
+        self.assets.values()
+    }
+
+    // what the borrower still has to repay the lender in an asset
+    pub fn debt(&self, borrower: &str, lender: &str, asset: Option<AssetId>) -> u64 {
+        self.debts
+            .get(&(borrower.to_owned(), lender.to_owned(), asset))
+            .copied()
+            .unwrap_or_default()
+    }
+
+    pub fn nonce_of(&self, address: &str) -> u64 {
+        self.nonces.get(address).copied().unwrap_or_default()
+    }
+
+    pub fn stake_of(&self, address: &str) -> u64 {
+        self.stakes.get(address).copied().unwrap_or_default()
+    }
+
+    // all the coins that are locked by staking
+    pub fn staked(&self) -> u64 {
+        self.stakes.values().sum()
+    }
+
+    // an escrow by the hash of the transaction that opened it if it is still open
+    pub fn escrow(&self, hash: &Hash) -> Option<&Escrow> {
+        self.escrows.get(hash)
+    }
+
+    // the escrows that were neither released nor refunded
+    pub fn escrows(&self) -> impl Iterator<Item = &Escrow> {
+        self.escrows.values()
+    }
+
+    // the unspent outputs if the chain uses the UTXO model
+    pub fn utxos(&self) -> Option<&UtxoSet> {
+        self.utxos.as_ref()
+    }
+
+    pub fn supply(&self) -> u64 {
+        self.supply
+    }
+
+    // the account of an address, none if it holds, owes and staked nothing and never sent
+    // a transaction
+    pub fn account(&self, address: &str) -> Option<Account> {
+        self.accounts().remove(address)
+    }
+
+    // every account that is not empty by address
+    pub fn accounts(&self) -> BTreeMap<String, Account> {
+        let mut accounts: BTreeMap<String, Account> = BTreeMap::new();
+        for ((address, asset), balance) in nonzero(&self.balances) {
+            let account = accounts.entry(address.clone()).or_default();
+            account.balances.insert(*asset, *balance);
+        }
+        for ((borrower, lender, asset), debt) in nonzero(&self.debts) {
+            let account = accounts.entry(borrower.clone()).or_default();
+            account.debts.insert((lender.clone(), *asset), *debt);
+        }
+        for (address, nonce) in nonzero(&self.nonces) {
+            accounts.entry(address.clone()).or_default().nonce = *nonce;
+        }
+        for (address, stake) in nonzero(&self.stakes) {
+            accounts.entry(address.clone()).or_default().stake = *stake;
+        }
+
+        accounts
+    }
+
+    // the account hashes by where they are in the tree
+    fn account_leaves(&self) -> BTreeMap<Hash, Hash> {
+        self.accounts()
+            .iter()
+            .map(|(address, account)| (Account::key(address), account.hash()))
+            .collect()
+    }
+
+    // the hash of the assets, escrows, unspent outputs and supply
+    fn rest_hash(&self) -> Hash {
+        let mut bytes = Vec::new();
+        self.encode_rest(&mut bytes);
+        sha2::Sha256::digest(bytes).into()
+    }
+
+    // commits to the tree of accounts and the rest of the state, two nodes with the same root
+    // agree on the whole state
+    pub fn root(&self) -> Hash {
+        account::state_root(
+            &sparse_merkle::root(&self.account_leaves()),
+            &self.rest_hash(),
+        )
+    }
+
+    // proves what the address has in the state or that it has no account
+    pub fn prove(&self, address: &str) -> AccountProof {
+        AccountProof {
+            account: self.account(address),
+            proof: sparse_merkle::prove(&self.account_leaves(), &Account::key(address)),
+            rest: self.rest_hash(),
+        }
+    }
+
+    // applies every transaction in a block, if any rule is broken the state is left unchanged
+    pub fn apply_block(
+        &mut self,
+        block: &Block,
+        policy: &MonetaryPolicy,
+        height: u64,
+    ) -> Result<(), BlockError> {
+        block.verify_signitures()?;
+        self.apply_verified_block(block, policy, height)
+    }
+
+    // the same as apply_block for a block whose signitures were already checked
+    pub fn apply_verified_block(
+        &mut self,
+        block: &Block,
+        policy: &MonetaryPolicy,
+        height: u64,
+    ) -> Result<(), BlockError> {
+        let Some((coinbase, transactions)) = block.transactions().split_first() else {
+            return Err(BlockError::MissingCoinbase);
+        };
+
+        if !coinbase.is_coinbase() {
+            return Err(BlockError::MissingCoinbase);
+        }
+
+        let mut state = self.clone();
+        for transaction in transactions {
+            if !transaction.is_mature(height, block.timestamp()) {
+                return Err(BlockError::Premature(transaction.hash()));
+            }
+
+            state.apply_verified(transaction)?;
+        }
+
+        // the sealer may claim the reward for the height and the fees of the block
+        let fees: u64 = transactions
+            .iter()
+            .map(|transaction| transaction.fee())
+            .sum();
+        let reward = policy.reward(height, self.supply);
+        if coinbase.amount() > reward.saturating_add(fees)
+            || coinbase.kind() != &TransactionKind::Normal
+            || coinbase.nonce() != height
+            || coinbase.lock().is_some()
+            || coinbase.asset().is_some()
+            || coinbase.is_utxo()
+        {
+            return Err(BlockError::InvalidCoinbase);
+        }
+
+        state.credit(coinbase.to(), None, coinbase.amount());
+        state.create_outputs(coinbase);
+        state.supply += coinbase.amount().saturating_sub(fees);
+
+        *self = state;
+        Ok(())
+    }
+
+    // checks a transaction against the state and applies it
+    pub fn apply_transaction(&mut self, transaction: &Transaction) -> Result<(), BlockError> {
+        if !transaction.valid() {
+            return Err(BlockError::InvalidTransaction(transaction.hash()));
+        }
+
+        self.apply_verified(transaction)
+    }
+
+    // the same as apply_transaction for a transaction whose signiture was already checked
+    pub fn apply_verified(&mut self, transaction: &Transaction) -> Result<(), BlockError> {
+        let hash = transaction.hash();
+        let Some(from) = transaction.from() else {
+            return Err(BlockError::ExtraCoinbase(hash));
+        };
+
+        // transactions from a sender must be applied in order and only once, so no transaction
+        // can be replayed without keeping the hashes of the applied ones
+        if transaction.nonce() != self.nonce_of(from) {
+            return Err(BlockError::InvalidNonce(hash));
+        }
+
+        if self.utxos.is_some() {
+            self.check_utxo(transaction)?;
+            self.apply_unchecked(transaction);
+            return Ok(());
+        }
+
+        if transaction.is_utxo() {
+            return Err(BlockError::Unsupported(hash));
+        }
+
+        // if there is a loan but it has not been signed by both parties then it must not be
+        // mined
+        if transaction.is_loan() && !transaction.loan_signed() {
+            return Err(BlockError::UnsignedLoan(hash));
+        }
+
+        // a stake locks coins for the sender, it can not pay them to anyone else
+        if transaction.is_stake() && transaction.to() != from {
+            return Err(BlockError::ForeignStake(hash));
+        }
+
+        if transaction.kind() == &TransactionKind::Repayment
+            && transaction.amount() > self.debt(from, transaction.to(), transaction.asset())
+        {
+            return Err(BlockError::Overpayment(hash));
+        }
+
+        self.check_asset(transaction)?;
+        self.check_escrow(transaction)?;
+
+        // the fee is paid in the coin of the chain by the sender, the amount comes out of the
+        // escrow for a release or refund and is created by an issuance
+        let payer = transaction.payer();
+        let asset = transaction.asset();
+        let mut cost = Some(transaction.fee());
+        if payer.as_deref() == Some(from.as_str()) && asset.is_none() {
+            cost = cost.and_then(|cost| cost.checked_add(transaction.amount()));
+        }
+        // a cost too large to add up is more than anyone has
+        if cost.is_none_or(|cost| cost > self.balance_of(from, None))
+            || payer.is_some_and(|payer| transaction.amount() > self.balance_of(&payer, asset))
+        {
+            return Err(BlockError::Overdraft(hash));
+        }
+
+        self.apply_unchecked(transaction);
+        Ok(())
+    }
+
+    // On a chain that uses the UTXO model only payments in the coin are allowed. Every input
+    // has to be an unspent output of the sender that is used once, the first output has to pay
+    // the amount to the receiver and the inputs have to pay exactly for the outputs and the fee.
+    pub fn check_utxo(&self, transaction: &Transaction) -> Result<(), BlockError> {
+        let hash = transaction.hash();
+        let Some(utxos) = &self.utxos else {
+            return Err(BlockError::Unsupported(hash));
+        };
+
+        if transaction.kind() != &TransactionKind::Normal || transaction.asset().is_some() {
+            return Err(BlockError::Unsupported(hash));
+        }
+
+        let from = transaction.from().as_deref().unwrap_or_default();
+        let mut spent = HashSet::new();
+        let mut total = 0;
+        for input in transaction.inputs() {
+            let Some(output) = utxos.get(input) else {
+                return Err(BlockError::DoubleSpend(hash));
+            };
+            if !spent.insert(input) {
+                return Err(BlockError::DoubleSpend(hash));
+            }
+            if output.address != from {
+                return Err(BlockError::ForeignInput(hash));
+            }
+
+            total += output.amount;
+        }
+
+        let outputs = transaction.outputs();
+        if outputs[0].address != transaction.to() || outputs[0].amount != transaction.amount() {
+            return Err(BlockError::InvalidOutputs(hash));
+        }
+
+        let paid = outputs.iter().try_fold(transaction.fee(), |paid, output| {
+            paid.checked_add(output.amount)
+        });
+        match paid {
+            Some(paid) if paid > total => Err(BlockError::Overdraft(hash)),
+            Some(paid) if paid == total => Ok(()),
+            _ => Err(BlockError::InvalidOutputs(hash)),
+        }
+    }
+
+    // an issued asset has to have a valid name and decimals, any other asset has to exist and
+    // only the coin of the chain can be staked
+    fn check_asset(&self, transaction: &Transaction) -> Result<(), BlockError> {
+        let hash = transaction.hash();
+        if let Some(asset) = Asset::of(transaction) {
+            if !asset.valid() {
+                return Err(BlockError::InvalidAsset(hash));
+            }
+
+            return Ok(());
+        }
+
+        match transaction.asset() {
+            Some(asset) if !self.assets.contains_key(&asset) || transaction.is_stake() => {
+                Err(BlockError::InvalidAsset(hash))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    // A release has to pay the whole escrow to the seller and be sent by the arbiter or by the
+    // buyer with the signiture of the seller. A refund has to pay the whole escrow back to the
+    // buyer, who sends it, and be locked until the timeout.
+    fn check_escrow(&self, transaction: &Transaction) -> Result<(), BlockError> {
+        let hash = transaction.hash();
+        let from = transaction.from().as_deref().unwrap_or_default();
+        let (escrow, refund) = match transaction.kind() {
+            TransactionKind::Release { escrow, .. } => (escrow, false),
+            TransactionKind::Refund { escrow } => (escrow, true),
+            _ => return Ok(()),
+        };
+
+        let Some(escrow) = self.escrows.get(escrow) else {
+            return Err(BlockError::InvalidEscrow(hash));
+        };
+        if transaction.amount() != escrow.amount || transaction.asset() != escrow.asset {
+            return Err(BlockError::InvalidEscrow(hash));
+        }
+
+        if refund {
+            let locked = matches!(
+                transaction.lock(),
+                Some(Lock::Height(height)) if height >= escrow.timeout
+            );
+            if from != escrow.buyer || transaction.to() != escrow.buyer || !locked {
+                return Err(BlockError::InvalidEscrow(hash));
+            }
+        } else if transaction.to() != escrow.seller {
+            return Err(BlockError::InvalidEscrow(hash));
+        } else if from == escrow.buyer && !transaction.release_signed() {
+            return Err(BlockError::UnsignedRelease(hash));
+        } else if from != escrow.buyer && from != escrow.arbiter {
+            return Err(BlockError::InvalidEscrow(hash));
+        }
+
+        Ok(())
+    }
+
+    // applies a transaction without checking it, a light client does this with the
+    // transactions it has proofs for
+    pub fn apply_unchecked(&mut self, transaction: &Transaction) {
+        let asset = transaction.asset();
+        let Some(from) = transaction.from() else {
+            self.credit(transaction.to(), asset, transaction.amount());
+            self.create_outputs(transaction);
+            return;
+        };
+
+        *self.nonces.entry(from.to_owned()).or_default() += 1;
+
+        if self.utxos.is_some() {
+            self.spend_outputs(transaction);
+            return;
+        }
+
+        let payer = transaction.payer();
+        self.debit(from, None, transaction.fee());
+        if let Some(payer) = &payer {
+            self.debit(payer, asset, transaction.amount());
+        }
+
+        if transaction.is_stake() {
+            *self.stakes.entry(from.to_owned()).or_default() += transaction.amount();
+        }
+
+        // paying yourself or staking only costs the sender
+        let payee = transaction.payee();
+        if payer.as_deref() != Some(&*payee) {
+            self.credit(&payee, asset, transaction.amount());
+        }
+
+        if let Some(asset) = Asset::of(transaction) {
+            self.assets.insert(asset.id, asset);
+        }
+
+        if let Some(escrow) = Escrow::of(transaction) {
+            self.escrows.insert(escrow.hash, escrow);
+        }
+        if let TransactionKind::Release { escrow, .. } | TransactionKind::Refund { escrow } =
+            transaction.kind()
+        {
+            self.escrows.remove(escrow);
+        }
+
+        let (borrower, lender) = match transaction.kind() {
+            TransactionKind::Loan(_) => (transaction.to(), from.as_str()),
+            TransactionKind::Repayment => (from.as_str(), transaction.to()),
+            _ => return,
+        };
+        let debt = self
+            .debts
+            .entry((borrower.to_owned(), lender.to_owned(), asset))
+            .or_default();
+        *debt = match transaction.kind() {
+            TransactionKind::Loan(_) => *debt + transaction.amount(),
+            _ => debt.saturating_sub(transaction.amount()),
+        };
+    }
+
+    // moves the coins of the inputs to the outputs on a chain that uses the UTXO model, the
+    // balances are kept as well so they can be looked up the same way for either model
+    fn spend_outputs(&mut self, transaction: &Transaction) {
+        let Some(utxos) = self.utxos.as_mut() else {
+            return;
+        };
+
+        let spent: Vec<_> = transaction
+            .inputs()
+            .iter()
+            .filter_map(|input| utxos.remove(input))
+            .collect();
+        for output in spent {
+            self.debit(&output.address, None, output.amount);
+        }
+        for output in transaction.outputs().iter() {
+            self.credit(&output.address, None, output.amount);
+        }
+
+        self.create_outputs(transaction);
+    }
+
+    // adds the outputs of a transaction to the unspent outputs, outputs of nothing are left out
+    // as they could never be worth spending
+    fn create_outputs(&mut self, transaction: &Transaction) {
+        let Some(utxos) = self.utxos.as_mut() else {
+            return;
+        };
+
+        let hash = transaction.hash();
+        for (index, output) in transaction.outputs().iter().enumerate() {
+            if output.amount > 0 {
+                let outpoint = OutPoint {
+                    transaction: hash,
+                    index: index as u32,
+                };
+                utxos.insert(outpoint, output.clone());
+            }
+        }
+    }
+
+    fn credit(&mut self, address: &str, asset: Option<AssetId>, amount: u64) {
+        *self
+            .balances
+            .entry((address.to_owned(), asset))
+            .or_default() += amount;
+    }
+
+    fn debit(&mut self, address: &str, asset: Option<AssetId>, amount: u64) {
+        let balance = self
+            .balances
+            .entry((address.to_owned(), asset))
+            .or_default();
+        *balance = balance.saturating_sub(amount);
+    }
+}
+
+// Maps are written in the order of their keys and entries of nothing are left out so the same
+// state always has the same encoding and root
+impl Encode for ChainState {
+    fn encode(&self, out: &mut Vec<u8>) {
+        self.encode_accounts(out);
+        self.encode_rest(out);
+    }
+}
+
+impl ChainState {
+    // the balances, debts, nonces and stakes, which make up the accounts
+    fn encode_accounts(&self, out: &mut Vec<u8>) {
+        encode_sorted(
+            nonzero(&self.balances),
+            out,
+            |(address, asset), balance, out| {
+                address.encode(out);
+                asset.as_ref().encode(out);
+                balance.encode(out);
+            },
+        );
+        encode_sorted(
+            nonzero(&self.debts),
+            out,
+            |(borrower, lender, asset), debt, out| {
+                borrower.encode(out);
+                lender.encode(out);
+                asset.as_ref().encode(out);
+                debt.encode(out);
+            },
+        );
+        encode_sorted(nonzero(&self.nonces), out, |address, nonce, out| {
+            address.encode(out);
+            nonce.encode(out);
+        });
+        encode_sorted(nonzero(&self.stakes), out, |address, stake, out| {
+            address.encode(out);
+            stake.encode(out);
+        });
+    }
+
+    fn encode_rest(&self, out: &mut Vec<u8>) {
+        encode_sorted(self.assets.iter(), out, |_, asset, out| asset.encode(out));
+        encode_sorted(self.escrows.iter(), out, |_, escrow, out| {
+            escrow.encode(out)
+        });
+        self.utxos.as_ref().encode(out);
+        self.supply.encode(out);
+    }
+}
+
+impl Decode for ChainState {
+    fn decode(reader: &mut Reader) -> Result<Self, DecodeError> {
+        let balances = reader.list_with(|reader| {
+            let key = (reader.string()?, reader.option(Reader::hash)?);
+            Ok((key, reader.u64()?))
+        })?;
+        let debts = reader.list_with(|reader| {
+            let key = (
+                reader.string()?,
+                reader.string()?,
+                reader.option(Reader::hash)?,
+            );
+            Ok((key, reader.u64()?))
+        })?;
+        let nonces = reader.list_with(|reader| Ok((reader.string()?, reader.u64()?)))?;
+        let stakes = reader.list_with(|reader| Ok((reader.string()?, reader.u64()?)))?;
+        let assets = reader.list::<Asset>()?;
+        let escrows = reader.list::<Escrow>()?;
+
+        Ok(Self {
+            balances: balances.into_iter().collect(),
+            assets: assets.into_iter().map(|asset| (asset.id, asset)).collect(),
+            debts: debts.into_iter().collect(),
+            nonces: nonces.into_iter().collect(),
+            stakes: stakes.into_iter().collect(),
+            escrows: escrows
+                .into_iter()
+                .map(|escrow| (escrow.hash, escrow))
+                .collect(),
+            utxos: reader.option(UtxoSet::decode)?,
+            supply: reader.u64()?,
+        })
+    }
+}
+
+// writes the entries of a map ordered by key
+fn encode_sorted<'a, K: Ord + 'a, V: 'a>(
+    entries: impl Iterator<Item = (&'a K, &'a V)>,
+    out: &mut Vec<u8>,
+    mut write: impl FnMut(&K, &V, &mut Vec<u8>),
+) {
+    let entries: BTreeMap<_, _> = entries.collect();
+    (entries.len() as u32).encode(out);
+    for (key, value) in entries {
+        write(key, value, out);
+    }
+}
+
+fn nonzero<K>(map: &HashMap<K, u64>) -> impl Iterator<Item = (&K, &u64)> {
+    map.iter().filter(|(_, amount)| **amount > 0)
+}
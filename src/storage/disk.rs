@@ -0,0 +1,261 @@
+use super::{indexed, Location, Storage, StorageError};
+use crate::{
+    block::{Block, Hash, Header},
+    encoding::{encode_list, Decode, Encode, Reader},
+    snapshot::Snapshot,
+    transaction::Transaction,
+};
+use redb::{
+    Database, MultimapTableDefinition, ReadableTable, ReadableTableMetadata, TableDefinition,
+};
+use std::path::Path;
+
+// a header is split from the transactions of its block so pruning only drops the body
+const HEADERS: TableDefinition<u64, &[u8]> = TableDefinition::new("headers");
+const BODIES: TableDefinition<u64, &[u8]> = TableDefinition::new("bodies");
+const HEIGHTS: TableDefinition<&[u8], u64> = TableDefinition::new("heights");
+const LOCATIONS: TableDefinition<&[u8], (u64, u32)> = TableDefinition::new("locations");
+const HISTORY: MultimapTableDefinition<&str, (u64, u32)> = MultimapTableDefinition::new("history");
+const SNAPSHOT: TableDefinition<(), &[u8]> = TableDefinition::new("snapshot");
+const MEMPOOL: TableDefinition<(), &[u8]> = TableDefinition::new("mempool");
+const LOG: TableDefinition<(), &[u8]> = TableDefinition::new("log");
+
+// Keeps the chain in a single file using redb, an embedded key value store written in Rust.
+// Every write is its own transaction so a block and its indexes are stored together or
+// not at all.
+pub struct DiskStorage {
+    database: Database,
+}
+
+impl DiskStorage {
+    // opens the file or creates it if it does not exist
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, StorageError> {
+        let database = Database::create(path).map_err(backend)?;
+
+        // the tables are created up front so reads never find one missing
+        let transaction = database.begin_write().map_err(backend)?;
+        transaction.open_table(HEADERS).map_err(backend)?;
+        transaction.open_table(BODIES).map_err(backend)?;
+        transaction.open_table(HEIGHTS).map_err(backend)?;
+        transaction.open_table(LOCATIONS).map_err(backend)?;
+        transaction.open_multimap_table(HISTORY).map_err(backend)?;
+        transaction.open_table(SNAPSHOT).map_err(backend)?;
+        transaction.open_table(MEMPOOL).map_err(backend)?;
+        transaction.open_table(LOG).map_err(backend)?;
+        transaction.commit().map_err(backend)?;
+
+        Ok(Self { database })
+    }
+
+    // writes or removes the value of a table that holds a single one
+    fn put(
+        &mut self,
+        table: TableDefinition<(), &[u8]>,
+        value: Option<&[u8]>,
+    ) -> Result<(), StorageError> {
+        let transaction = self.database.begin_write().map_err(backend)?;
+        {
+            let mut table = transaction.open_table(table).map_err(backend)?;
+            match value {
+                Some(value) => table.insert((), value).map(drop),
+                None => table.remove(()).map(drop),
+            }
+            .map_err(backend)?;
+        }
+
+        transaction.commit().map_err(backend)
+    }
+
+    fn get(&self, table: TableDefinition<(), &[u8]>) -> Result<Option<Vec<u8>>, StorageError> {
+        let transaction = self.database.begin_read().map_err(backend)?;
+        let table = transaction.open_table(table).map_err(backend)?;
+
+        Ok(table
+            .get(())
+            .map_err(backend)?
+            .map(|value| value.value().to_vec()))
+    }
+}
+
+impl Storage for DiskStorage {
+    fn put_block(&mut self, height: u64, block: &Block) -> Result<(), StorageError> {
+        let transaction = self.database.begin_write().map_err(backend)?;
+        {
+            let mut headers = transaction.open_table(HEADERS).map_err(backend)?;
+            let mut bodies = transaction.open_table(BODIES).map_err(backend)?;
+            let mut heights = transaction.open_table(HEIGHTS).map_err(backend)?;
+            let mut locations = transaction.open_table(LOCATIONS).map_err(backend)?;
+            let mut history = transaction.open_multimap_table(HISTORY).map_err(backend)?;
+
+            let stored = headers.len().map_err(backend)?;
+            if height > stored {
+                return Err(StorageError::MissingBlock(stored));
+            }
+
+            // the block being replaced is dropped from the indexes first
+            if let Some(old) = load(&headers, &bodies, height)? {
+                heights.remove(&old.hash()[..]).map_err(backend)?;
+                for (location, transaction) in indexed(height, &old) {
+                    // a transaction the new branch has at a lower height was indexed there
+                    // already and keeps that location
+                    let location = (location.height, location.index);
+                    let hash = transaction.hash();
+                    let stored = locations
+                        .get(&hash[..])
+                        .map_err(backend)?
+                        .map(|stored| stored.value());
+                    if stored == Some(location) {
+                        locations.remove(&hash[..]).map_err(backend)?;
+                    }
+                    for address in transaction.addresses() {
+                        history
+                            .remove(address.as_str(), location)
+                            .map_err(backend)?;
+                    }
+                }
+            }
+
+            headers
+                .insert(height, &block.header().to_bytes()[..])
+                .map_err(backend)?;
+            if block.is_pruned() {
+                bodies.remove(height).map_err(backend)?;
+            } else {
+                let mut body = Vec::new();
+                encode_list(block.transactions(), &mut body);
+                bodies.insert(height, &body[..]).map_err(backend)?;
+            }
+
+            heights.insert(&block.hash()[..], height).map_err(backend)?;
+            for (location, transaction) in indexed(height, block) {
+                let location = (location.height, location.index);
+                locations
+                    .insert(&transaction.hash()[..], location)
+                    .map_err(backend)?;
+                for address in transaction.addresses() {
+                    history
+                        .insert(address.as_str(), location)
+                        .map_err(backend)?;
+                }
+            }
+        }
+
+        transaction.commit().map_err(backend)
+    }
+
+    fn put_snapshot(&mut self, snapshot: &Snapshot) -> Result<(), StorageError> {
+        self.put(SNAPSHOT, Some(&snapshot.to_bytes()))
+    }
+
+    fn put_mempool(&mut self, transactions: &[Transaction]) -> Result<(), StorageError> {
+        let mut bytes = Vec::new();
+        encode_list(transactions, &mut bytes);
+        self.put(MEMPOOL, Some(&bytes))
+    }
+
+    fn put_log(&mut self, log: Option<&[u8]>) -> Result<(), StorageError> {
+        self.put(LOG, log)
+    }
+
+    fn count(&self) -> Result<u64, StorageError> {
+        let transaction = self.database.begin_read().map_err(backend)?;
+        let headers = transaction.open_table(HEADERS).map_err(backend)?;
+
+        headers.len().map_err(backend)
+    }
+
+    fn header(&self, height: u64) -> Result<Option<Header>, StorageError> {
+        let transaction = self.database.begin_read().map_err(backend)?;
+        let headers = transaction.open_table(HEADERS).map_err(backend)?;
+
+        headers
+            .get(height)
+            .map_err(backend)?
+            .map(|header| Header::from_bytes(header.value()).map_err(StorageError::Decode))
+            .transpose()
+    }
+
+    fn block(&self, height: u64) -> Result<Option<Block>, StorageError> {
+        let transaction = self.database.begin_read().map_err(backend)?;
+        let headers = transaction.open_table(HEADERS).map_err(backend)?;
+        let bodies = transaction.open_table(BODIES).map_err(backend)?;
+
+        load(&headers, &bodies, height)
+    }
+
+    fn height_of(&self, hash: &Hash) -> Result<Option<u64>, StorageError> {
+        let transaction = self.database.begin_read().map_err(backend)?;
+        let heights = transaction.open_table(HEIGHTS).map_err(backend)?;
+
+        Ok(heights
+            .get(&hash[..])
+            .map_err(backend)?
+            .map(|height| height.value()))
+    }
+
+    fn location_of(&self, transaction: &Hash) -> Result<Option<Location>, StorageError> {
+        let read = self.database.begin_read().map_err(backend)?;
+        let locations = read.open_table(LOCATIONS).map_err(backend)?;
+
+        Ok(locations
+            .get(&transaction[..])
+            .map_err(backend)?
+            .map(|location| {
+                let (height, index) = location.value();
+                Location { height, index }
+            }))
+    }
+
+    fn history(&self, address: &str) -> Result<Vec<Location>, StorageError> {
+        let transaction = self.database.begin_read().map_err(backend)?;
+        let history = transaction.open_multimap_table(HISTORY).map_err(backend)?;
+
+        history
+            .get(address)
+            .map_err(backend)?
+            .map(|location| {
+                let (height, index) = location.map_err(backend)?.value();
+                Ok(Location { height, index })
+            })
+            .collect()
+    }
+
+    fn snapshot(&self) -> Result<Option<Snapshot>, StorageError> {
+        self.get(SNAPSHOT)?
+            .map(|bytes| Snapshot::from_bytes(&bytes).map_err(StorageError::Decode))
+            .transpose()
+    }
+
+    fn mempool(&self) -> Result<Vec<Transaction>, StorageError> {
+        match self.get(MEMPOOL)? {
+            Some(bytes) => Reader::new(&bytes).list().map_err(StorageError::Decode),
+            None => Ok(Vec::new()),
+        }
+    }
+
+    fn log(&self) -> Result<Option<Vec<u8>>, StorageError> {
+        self.get(LOG)
+    }
+}
+
+// a full block is its header followed by its transactions, see the encoding of a block
+fn load(
+    headers: &impl ReadableTable<u64, &'static [u8]>,
+    bodies: &impl ReadableTable<u64, &'static [u8]>,
+    height: u64,
+) -> Result<Option<Block>, StorageError> {
+    let Some(header) = headers.get(height).map_err(backend)? else {
+        return Ok(None);
+    };
+
+    let block = match bodies.get(height).map_err(backend)? {
+        Some(body) => Block::from_bytes(&[header.value(), body.value()].concat()),
+        None => Header::from_bytes(header.value()).map(Block::from_header),
+    };
+
+    block.map(Some).map_err(StorageError::Decode)
+}
+
+fn backend(error: impl Into<redb::Error>) -> StorageError {
+    StorageError::Backend(error.into().to_string())
+}
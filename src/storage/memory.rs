@@ -0,0 +1,127 @@
+use super::{indexed, Location, Storage, StorageError};
+use crate::{
+    block::{Block, Hash, Header},
+    snapshot::Snapshot,
+    transaction::Transaction,
+};
+use std::collections::{BTreeSet, HashMap};
+
+// Keeps everything in memory, which is what a chain uses unless it is given a storage
+#[derive(Default)]
+pub struct MemoryStorage {
+    blocks: Vec<Block>,
+    heights: HashMap<Hash, u64>,
+    locations: HashMap<Hash, Location>,
+    history: HashMap<String, BTreeSet<Location>>,
+    snapshot: Option<Snapshot>,
+    mempool: Vec<Transaction>,
+    log: Option<Vec<u8>>,
+}
+
+impl MemoryStorage {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn index(&mut self, height: u64, block: &Block) {
+        self.heights.insert(block.hash(), height);
+        for (location, transaction) in indexed(height, block) {
+            self.locations.insert(transaction.hash(), location);
+            for address in transaction.addresses() {
+                self.history.entry(address).or_default().insert(location);
+            }
+        }
+    }
+
+    fn unindex(&mut self, height: u64) {
+        let block = self.blocks[height as usize].clone();
+        self.heights.remove(&block.hash());
+        for (location, transaction) in indexed(height, &block) {
+            // a transaction the new branch has at a lower height was indexed there already
+            // and keeps that location
+            let hash = transaction.hash();
+            if self.locations.get(&hash) == Some(&location) {
+                self.locations.remove(&hash);
+            }
+            for address in transaction.addresses() {
+                if let Some(history) = self.history.get_mut(&address) {
+                    history.remove(&location);
+                }
+            }
+        }
+    }
+}
+
+impl Storage for MemoryStorage {
+    fn put_block(&mut self, height: u64, block: &Block) -> Result<(), StorageError> {
+        let stored = self.blocks.len() as u64;
+        if height > stored {
+            return Err(StorageError::MissingBlock(stored));
+        }
+
+        if height < stored {
+            self.unindex(height);
+            self.blocks[height as usize] = block.clone();
+        } else {
+            self.blocks.push(block.clone());
+        }
+        self.index(height, block);
+
+        Ok(())
+    }
+
+    fn put_snapshot(&mut self, snapshot: &Snapshot) -> Result<(), StorageError> {
+        self.snapshot = Some(snapshot.clone());
+        Ok(())
+    }
+
+    fn put_mempool(&mut self, transactions: &[Transaction]) -> Result<(), StorageError> {
+        self.mempool = transactions.to_vec();
+        Ok(())
+    }
+
+    fn put_log(&mut self, log: Option<&[u8]>) -> Result<(), StorageError> {
+        self.log = log.map(<[u8]>::to_vec);
+        Ok(())
+    }
+
+    fn count(&self) -> Result<u64, StorageError> {
+        Ok(self.blocks.len() as u64)
+    }
+
+    fn header(&self, height: u64) -> Result<Option<Header>, StorageError> {
+        Ok(self.blocks.get(height as usize).map(Block::header))
+    }
+
+    fn block(&self, height: u64) -> Result<Option<Block>, StorageError> {
+        Ok(self.blocks.get(height as usize).cloned())
+    }
+
+    fn height_of(&self, hash: &Hash) -> Result<Option<u64>, StorageError> {
+        Ok(self.heights.get(hash).copied())
+    }
+
+    fn location_of(&self, transaction: &Hash) -> Result<Option<Location>, StorageError> {
+        Ok(self.locations.get(transaction).copied())
+    }
+
+    fn history(&self, address: &str) -> Result<Vec<Location>, StorageError> {
+        Ok(self
+            .history
+            .get(address)
+            .map(|history| history.iter().copied().collect())
+            .unwrap_or_default())
+    }
+
+    fn snapshot(&self) -> Result<Option<Snapshot>, StorageError> {
+        Ok(self.snapshot.clone())
+    }
+
+    fn mempool(&self) -> Result<Vec<Transaction>, StorageError> {
+        Ok(self.mempool.clone())
+    }
+
+    fn log(&self) -> Result<Option<Vec<u8>>, StorageError> {
+        Ok(self.log.clone())
+    }
+}
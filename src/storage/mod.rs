@@ -0,0 +1,128 @@
+mod disk;
+mod memory;
+mod wal;
+
+pub use disk::DiskStorage;
+pub use memory::MemoryStorage;
+pub use wal::{recover, Batch, Write};
+
+use crate::{
+    block::{Block, Hash, Header},
+    encoding::DecodeError,
+    snapshot::Snapshot,
+    transaction::Transaction,
+};
+use std::collections::BTreeMap;
+
+#[derive(Debug)]
+pub enum StorageError {
+    Backend(String), // what the backend reported
+    Decode(DecodeError),
+    MissingBlock(u64), // a block below the height being written or read is not stored
+}
+
+// Where a transaction is on the chain
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Location {
+    pub height: u64,
+    pub index: u32, // the position in the block
+}
+
+// Keeps the blocks of a chain with indexes to look them up by hash and to find the
+// transactions by hash and by the addresses they involve. Transactions are keyed by
+// Transaction::hash and indexed by Transaction::addresses. A pruned block is stored with
+// only its header and its transactions are dropped from the indexes. The chain writes
+// through a Batch so that what belongs together survives a crash together.
+pub trait Storage: Send {
+    // stores the block at the height, replacing the block that was there. Every height
+    // below it has to be stored already.
+    fn put_block(&mut self, height: u64, block: &Block) -> Result<(), StorageError>;
+
+    // stores the snapshot the blocks after it are applied to, see Blockchain::pruned_height
+    fn put_snapshot(&mut self, snapshot: &Snapshot) -> Result<(), StorageError>;
+
+    // replaces the transactions waiting to be included in a block
+    fn put_mempool(&mut self, transactions: &[Transaction]) -> Result<(), StorageError>;
+
+    // writes or clears the write-ahead log, see Batch
+    fn put_log(&mut self, log: Option<&[u8]>) -> Result<(), StorageError>;
+
+    // how many blocks are stored, the latest is at one below it
+    fn count(&self) -> Result<u64, StorageError>;
+
+    fn header(&self, height: u64) -> Result<Option<Header>, StorageError>;
+
+    // a pruned block comes back without its transactions
+    fn block(&self, height: u64) -> Result<Option<Block>, StorageError>;
+
+    fn height_of(&self, hash: &Hash) -> Result<Option<u64>, StorageError>;
+
+    fn location_of(&self, transaction: &Hash) -> Result<Option<Location>, StorageError>;
+
+    // where the transactions involving the address are, in chain order
+    fn history(&self, address: &str) -> Result<Vec<Location>, StorageError>;
+
+    fn snapshot(&self) -> Result<Option<Snapshot>, StorageError>;
+
+    fn mempool(&self) -> Result<Vec<Transaction>, StorageError>;
+
+    fn log(&self) -> Result<Option<Vec<u8>>, StorageError>;
+
+    fn block_by_hash(&self, hash: &Hash) -> Result<Option<Block>, StorageError> {
+        match self.height_of(hash)? {
+            Some(height) => self.block(height),
+            None => Ok(None),
+        }
+    }
+
+    fn transaction(&self, hash: &Hash) -> Result<Option<(Location, Transaction)>, StorageError> {
+        let Some(location) = self.location_of(hash)? else {
+            return Ok(None);
+        };
+        let block = self
+            .block(location.height)?
+            .ok_or(StorageError::MissingBlock(location.height))?;
+
+        Ok(block
+            .transactions()
+            .get(location.index as usize)
+            .map(|transaction| (location, transaction.clone())))
+    }
+
+    // the transactions involving the address in chain order, each block is only read once
+    fn transactions_of(&self, address: &str) -> Result<Vec<(Location, Transaction)>, StorageError> {
+        let mut locations: BTreeMap<u64, Vec<u32>> = BTreeMap::new();
+        for location in self.history(address)? {
+            locations
+                .entry(location.height)
+                .or_default()
+                .push(location.index);
+        }
+
+        let mut transactions = Vec::new();
+        for (height, indexes) in locations {
+            let block = self
+                .block(height)?
+                .ok_or(StorageError::MissingBlock(height))?;
+            for index in indexes {
+                if let Some(transaction) = block.transactions().get(index as usize) {
+                    transactions.push((Location { height, index }, transaction.clone()));
+                }
+            }
+        }
+
+        Ok(transactions)
+    }
+}
+
+// the transactions of a block the indexes hold, none for a pruned block
+fn indexed(height: u64, block: &Block) -> impl Iterator<Item = (Location, &Transaction)> {
+    block
+        .transactions()
+        .iter()
+        .enumerate()
+        .map(move |(index, transaction)| {
+            let index = index as u32;
+            (Location { height, index }, transaction)
+        })
+}
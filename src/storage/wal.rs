@@ -0,0 +1,160 @@
+use super::{Storage, StorageError};
+use crate::{
+    block::{Block, Hash, Header},
+    encoding::{encode_list, Decode, DecodeError, Encode, Reader},
+    snapshot::Snapshot,
+    transaction::Transaction,
+};
+use sha2::Digest;
+
+// One write to a storage. Each can be carried out again with the same result, which is what
+// lets recover replay a batch that was cut off part way.
+#[derive(Clone)]
+pub enum Write {
+    Block(u64, Box<Block>), // a pruned block is written with only its header
+    Snapshot(Box<Snapshot>),
+    Mempool(Vec<Transaction>),
+}
+
+impl Write {
+    fn apply(&self, storage: &mut (impl Storage + ?Sized)) -> Result<(), StorageError> {
+        match self {
+            Write::Block(height, block) => storage.put_block(*height, block),
+            Write::Snapshot(snapshot) => storage.put_snapshot(snapshot),
+            Write::Mempool(transactions) => storage.put_mempool(transactions),
+        }
+    }
+}
+
+impl Encode for Write {
+    fn encode(&self, out: &mut Vec<u8>) {
+        match self {
+            Write::Block(height, block) if block.is_pruned() => {
+                1u8.encode(out);
+                height.encode(out);
+                block.header().encode(out);
+            }
+            Write::Block(height, block) => {
+                0u8.encode(out);
+                height.encode(out);
+                block.encode(out);
+            }
+            Write::Snapshot(snapshot) => {
+                2u8.encode(out);
+                snapshot.encode(out);
+            }
+            Write::Mempool(transactions) => {
+                3u8.encode(out);
+                encode_list(transactions, out);
+            }
+        }
+    }
+}
+
+impl Decode for Write {
+    fn decode(reader: &mut Reader) -> Result<Self, DecodeError> {
+        match reader.u8()? {
+            0 => Ok(Write::Block(
+                reader.u64()?,
+                Box::new(Block::decode(reader)?),
+            )),
+            1 => Ok(Write::Block(
+                reader.u64()?,
+                Box::new(Block::from_header(Header::decode(reader)?)),
+            )),
+            2 => Ok(Write::Snapshot(Box::new(Snapshot::decode(reader)?))),
+            3 => Ok(Write::Mempool(reader.list()?)),
+            tag => Err(DecodeError::InvalidTag(tag)),
+        }
+    }
+}
+
+// Writes that have to happen together, such as a block, the blocks it lets the chain prune and
+// the mempool without the transactions it included. The batch is written to the log of the
+// storage with a checksum before any of its writes are carried out, so a crash either happens
+// before it was logged and nothing changed or after and recover finishes it.
+#[derive(Clone, Default)]
+pub struct Batch {
+    writes: Vec<Write>,
+}
+
+impl Batch {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn push(&mut self, write: Write) {
+        self.writes.push(write);
+    }
+
+    pub fn writes(&self) -> &[Write] {
+        &self.writes
+    }
+
+    // Logs the batch and carries it out. An error means it was not logged and nothing was
+    // written. Once it is logged the batch counts as written even if carrying it out fails,
+    // the rest is done by recover the next time the storage is written to or opened.
+    pub fn commit(&self, storage: &mut (impl Storage + ?Sized)) -> Result<(), StorageError> {
+        // a batch left by a write that failed has to be finished before the next one
+        recover(storage)?;
+
+        let bytes = self.to_bytes();
+        let checksum: Hash = sha2::Sha256::digest(&bytes).into();
+        storage.put_log(Some(&[&checksum[..], &bytes].concat()))?;
+
+        if self.apply(storage).is_ok() {
+            // a log that could not be cleared is only replayed again
+            let _ = storage.put_log(None);
+        }
+
+        Ok(())
+    }
+
+    fn apply(&self, storage: &mut (impl Storage + ?Sized)) -> Result<(), StorageError> {
+        self.writes
+            .iter()
+            .try_for_each(|write| write.apply(storage))
+    }
+}
+
+impl FromIterator<Write> for Batch {
+    fn from_iter<T: IntoIterator<Item = Write>>(writes: T) -> Self {
+        Self {
+            writes: writes.into_iter().collect(),
+        }
+    }
+}
+
+impl Encode for Batch {
+    fn encode(&self, out: &mut Vec<u8>) {
+        encode_list(&self.writes, out);
+    }
+}
+
+impl Decode for Batch {
+    fn decode(reader: &mut Reader) -> Result<Self, DecodeError> {
+        Ok(Self {
+            writes: reader.list()?,
+        })
+    }
+}
+
+// Finishes the batch in the log of the storage, if there is one, and clears the log. A log
+// whose checksum does not match was cut off while it was written, none of its writes were
+// carried out so it is dropped. Returns the batch that was replayed.
+pub fn recover(storage: &mut (impl Storage + ?Sized)) -> Result<Option<Batch>, StorageError> {
+    let Some(log) = storage.log()? else {
+        return Ok(None);
+    };
+
+    let batch = (log.len() >= 32)
+        .then(|| log.split_at(32))
+        .filter(|(checksum, bytes)| sha2::Sha256::digest(bytes)[..] == checksum[..])
+        .and_then(|(_, bytes)| Batch::from_bytes(bytes).ok());
+    if let Some(batch) = &batch {
+        batch.apply(storage)?;
+    }
+    storage.put_log(None)?;
+
+    Ok(batch)
+}
@@ -1,16 +1,29 @@
-use crate::block::Hash;
-use elliptic_curve::generic_array::GenericArray;
+use crate::{
+    asset::AssetId,
+    block::Hash,
+    encoding::{encode_list, Decode, DecodeError, Encode, Reader},
+    escrow::Escrow,
+    htlc::Htlc,
+    keygen,
+    script::{self, Script},
+    signature::{self, SignatureScheme},
+    utxo::{OutPoint, Output},
+};
 use k256::{
     ecdsa::{Signature, SigningKey, VerifyingKey},
-    schnorr::signature::{Signer, Verifier},
+    schnorr::{self, signature::Signer},
 };
 use sha2::Digest;
+use std::{borrow::Cow, collections::BTreeSet};
 
 #[derive(Debug)]
 pub enum TransactionError {
     NoFromSignError,
     ForeignPubkey,
     NotLoan,
+    NotRelease,
+    WrongScheme,
+    Overflow, // the amount and fee add up to more than a u64 holds
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -18,64 +31,345 @@ pub enum TransactionKind {
     Normal,
     Loan(Option<Vec<u8>>), // the other parties signiture
     Repayment,
+    Stake,           // locks the amount so the sender can validate blocks
+    AddAuthority,    // a vote by an authority to make the receiver an authority
+    RemoveAuthority, // a vote by an authority to remove the receiver as an authority
+    // locks the amount in a hash time locked contract
+    Htlc {
+        hash: Hash,
+        timeout: u64,
+    },
+    // locks the amount for the receiver until released
+    Escrow {
+        arbiter: String,
+        timeout: u64,
+    },
+    // pays an escrow to the seller
+    Release {
+        escrow: Hash,
+        signiture: Option<Vec<u8>>,
+    },
+    // pays an escrow back to the buyer after its timeout
+    Refund {
+        escrow: Hash,
+    },
+    // creates an asset with the amount as its supply, paid to the receiver
+    Issue {
+        name: String,
+        decimals: u8,
+    },
 }
 
 impl TransactionKind {
-    fn bytes(&self) -> &[u8] {
+    fn tag(&self) -> u8 {
         match self {
-            TransactionKind::Normal => &[0],
-            TransactionKind::Loan(_) => &[1],
-            TransactionKind::Repayment => &[2],
+            TransactionKind::Normal => 0,
+            TransactionKind::Loan(_) => 1,
+            TransactionKind::Repayment => 2,
+            TransactionKind::Stake => 3,
+            TransactionKind::AddAuthority => 4,
+            TransactionKind::RemoveAuthority => 5,
+            TransactionKind::Htlc { .. } => 6,
+            TransactionKind::Escrow { .. } => 7,
+            TransactionKind::Release { .. } => 8,
+            TransactionKind::Refund { .. } => 9,
+            TransactionKind::Issue { .. } => 10,
+        }
+    }
+
+    // how the kind is shown, for example by the explorer
+    pub fn name(&self) -> &'static str {
+        match self {
+            TransactionKind::Normal => "normal",
+            TransactionKind::Loan(_) => "loan",
+            TransactionKind::Repayment => "repayment",
+            TransactionKind::Stake => "stake",
+            TransactionKind::AddAuthority => "add_authority",
+            TransactionKind::RemoveAuthority => "remove_authority",
+            TransactionKind::Htlc { .. } => "htlc",
+            TransactionKind::Escrow { .. } => "escrow",
+            TransactionKind::Release { .. } => "release",
+            TransactionKind::Refund { .. } => "refund",
+            TransactionKind::Issue { .. } => "issue",
+        }
+    }
+
+    // the signiture of the other party which is not covered by the hash as it signs the hash
+    fn counter_signiture(&self) -> Option<&Vec<u8>> {
+        match self {
+            TransactionKind::Loan(signiture) => signiture.as_ref(),
+            TransactionKind::Release { signiture, .. } => signiture.as_ref(),
+            _ => None,
         }
     }
 
     fn is_loan(&self) -> bool {
+        matches!(self, TransactionKind::Loan(_))
+    }
+
+    // the escrow a release or refund pays out of
+    fn escrow(&self) -> Option<&Hash> {
         match self {
-            TransactionKind::Loan(_) => true,
-            _ => false,
+            TransactionKind::Release { escrow, .. } | TransactionKind::Refund { escrow } => {
+                Some(escrow)
+            }
+            _ => None,
         }
     }
+}
 
-    fn loan_signed(&self) -> bool {
+// When a transaction may first be included in a block
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Lock {
+    Height(u64), // the height of the block
+    Time(u64),   // the timestamp of the block
+}
+
+impl Lock {
+    pub fn is_mature(&self, height: u64, timestamp: u64) -> bool {
         match self {
-            TransactionKind::Loan(sig) => sig.is_some(),
-            _ => false,
+            Lock::Height(lock) => height >= *lock,
+            Lock::Time(lock) => timestamp >= *lock,
         }
     }
 }
 
-#[derive(Debug)]
+impl Encode for Lock {
+    fn encode(&self, out: &mut Vec<u8>) {
+        let (tag, value) = match self {
+            Lock::Height(height) => (0u8, height),
+            Lock::Time(time) => (1u8, time),
+        };
+        tag.encode(out);
+        value.encode(out);
+    }
+}
+
+impl Decode for Lock {
+    fn decode(reader: &mut Reader) -> Result<Self, DecodeError> {
+        match reader.u8()? {
+            0 => Ok(Lock::Height(reader.u64()?)),
+            1 => Ok(Lock::Time(reader.u64()?)),
+            tag => Err(DecodeError::InvalidTag(tag)),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
 pub struct Transaction {
     from: Option<String>,
     to: String,
     amount: u64,
+    fee: u64,   // paid to whoever seals the block
+    nonce: u64, // how many transactions the sender made before this one
     hash: Hash,
     signiture: Vec<u8>,
+    scheme: SignatureScheme, // how the sender signs
+    lock: Option<Lock>,      // the transaction can not be mined before this
+    asset: Option<AssetId>,  // what the amount is in, none for the coin of the chain
+    inputs: Vec<OutPoint>,   // the outputs spent on a chain that uses the UTXO model
+    outputs: Vec<Output>,    // the outputs created, if empty one pays the amount to the receiver
+    script: Option<Script>,  // the script that the address of the sender is the hash of
+    witness: Vec<Vec<u8>>,   // what the script of the sender needs, like signitures
     kind: TransactionKind,
 }
 
 impl Transaction {
     pub fn new(from: Option<String>, to: String, amount: u64, kind: TransactionKind) -> Self {
-        let hash = Self::hash_transaction(&from, &to, amount, &kind);
-
-        Self {
+        let mut transaction = Self {
             from,
             to,
             amount,
-            hash,
+            fee: 0,
+            nonce: 0,
+            hash: [0; 32],
             signiture: Vec::new(),
+            scheme: SignatureScheme::Ecdsa,
+            lock: None,
+            asset: None,
+            inputs: Vec::new(),
+            outputs: Vec::new(),
+            script: None,
+            witness: Vec::new(),
             kind,
+        };
+        transaction.hash = transaction.hash_transaction();
+
+        transaction
+    }
+
+    // sets the fee, this must be done before the transaction is signed
+    pub fn with_fee(mut self, fee: u64) -> Self {
+        self.fee = fee;
+        self.hash = self.hash_transaction();
+        self
+    }
+
+    // sets the nonce, for a coinbase this is the height of the block so that every coinbase
+    // has a different hash, this must be done before the transaction is signed
+    pub fn with_nonce(mut self, nonce: u64) -> Self {
+        self.nonce = nonce;
+        self.hash = self.hash_transaction();
+        self
+    }
+
+    // stops the transaction from being mined before a height or time, this must be done before
+    // the transaction is signed
+    pub fn with_lock(mut self, lock: Lock) -> Self {
+        self.lock = Some(lock);
+        self.hash = self.hash_transaction();
+        self
+    }
+
+    // denominates the amount in an issued asset instead of the coin of the chain, the fee is
+    // always paid in the coin, this must be done before the transaction is signed
+    pub fn with_asset(mut self, asset: AssetId) -> Self {
+        self.asset = Some(asset);
+        self.hash = self.hash_transaction();
+        self
+    }
+
+    // spends outputs on a chain that uses the UTXO model, this must be done before the
+    // transaction is signed
+    pub fn with_inputs(mut self, inputs: Vec<OutPoint>) -> Self {
+        self.inputs = inputs;
+        self.hash = self.hash_transaction();
+        self
+    }
+
+    // creates the outputs on a chain that uses the UTXO model, the first has to pay the amount
+    // to the receiver, this must be done before the transaction is signed
+    pub fn with_outputs(mut self, outputs: Vec<Output>) -> Self {
+        self.outputs = outputs;
+        self.hash = self.hash_transaction();
+        self
+    }
+
+    // whether the transaction may be included in a block with the height and timestamp
+    pub fn is_mature(&self, height: u64, timestamp: u64) -> bool {
+        self.lock
+            .is_none_or(|lock| lock.is_mature(height, timestamp))
+    }
+
+    // reveals the script for a sender whose address is the hash of a script, the script is
+    // bound by the address so it is not covered by the signitures
+    pub fn with_script(mut self, script: Script) -> Self {
+        self.script = Some(script);
+        self
+    }
+
+    // sets what is pushed before the script of the sender runs
+    pub fn set_witness(&mut self, witness: Vec<Vec<u8>>) {
+        self.witness = witness;
+    }
+
+    // a signiture of the transaction for a witness, for example by one of the parties of a
+    // multisig
+    pub fn witness_signiture(&self, private_key: &SigningKey) -> Vec<u8> {
+        let signiture: Signature = private_key.sign(&self.hash);
+        signiture.to_bytes().to_vec()
+    }
+
+    pub fn witness_signiture_schnorr(&self, private_key: &schnorr::SigningKey) -> Vec<u8> {
+        Self::sign_schnorr(private_key, &self.hash)
+    }
+
+    // a transfer of nothing to the sender, with the nonce of a pending transaction and a higher
+    // fee it replaces that transaction so it is never mined
+    pub fn cancellation(from: String, nonce: u64, fee: u64) -> Self {
+        Self::new(Some(from.clone()), from, 0, TransactionKind::Normal)
+            .with_fee(fee)
+            .with_nonce(nonce)
+    }
+
+    // pays the receiver out of the selected outputs of the sender, whatever is left after the
+    // amount and fee is paid back to the sender
+    pub fn spend(
+        from: String,
+        to: String,
+        amount: u64,
+        fee: u64,
+        inputs: Vec<OutPoint>,
+        total: u64,
+    ) -> Result<Self, TransactionError> {
+        let cost = amount.checked_add(fee).ok_or(TransactionError::Overflow)?;
+        let mut outputs = vec![Output {
+            address: to.clone(),
+            amount,
+        }];
+        let change = total.saturating_sub(cost);
+        if change > 0 {
+            outputs.push(Output {
+                address: from.clone(),
+                amount: change,
+            });
         }
+
+        Ok(Self::new(Some(from), to, amount, TransactionKind::Normal)
+            .with_fee(fee)
+            .with_inputs(inputs)
+            .with_outputs(outputs))
+    }
+
+    // issues a new asset with the whole supply paid to the issuer
+    pub fn issuance(from: String, name: String, supply: u64, decimals: u8) -> Self {
+        Self::new(
+            Some(from.clone()),
+            from,
+            supply,
+            TransactionKind::Issue { name, decimals },
+        )
+    }
+
+    pub fn is_cancellation(&self) -> bool {
+        self.kind == TransactionKind::Normal
+            && self.amount == 0
+            && self.from.as_deref() == Some(self.to.as_str())
+    }
+
+    // sets how the sender will sign, this must be done before the transaction is signed
+    pub fn with_scheme(mut self, scheme: SignatureScheme) -> Self {
+        self.scheme = scheme;
+        self.hash = self.hash_transaction();
+        self
     }
 
+    // a loan is only signed once the receiver has signed it as well, the signiture of the
+    // sender is checked by valid
     pub fn loan_signed(&self) -> bool {
-        self.valid() && self.kind.loan_signed()
+        let TransactionKind::Loan(Some(signiture)) = &self.kind else {
+            return false;
+        };
+
+        signature::verify(&self.to, &self.hash, signiture)
     }
 
     pub fn is_loan(&self) -> bool {
         self.kind.is_loan()
     }
 
+    // a release by the buyer is only signed once the seller has signed it as well
+    pub fn release_signed(&self) -> bool {
+        let TransactionKind::Release {
+            signiture: Some(signiture),
+            ..
+        } = &self.kind
+        else {
+            return false;
+        };
+
+        signature::verify(&self.to, &self.hash, signiture)
+    }
+
+    // a transaction that creates coins as a reward for sealing a block
+    pub fn is_coinbase(&self) -> bool {
+        self.from.is_none()
+    }
+
+    pub fn is_stake(&self) -> bool {
+        self.kind == TransactionKind::Stake
+    }
+
     pub fn from(&self) -> &Option<String> {
         &self.from
     }
@@ -84,10 +378,69 @@ impl Transaction {
         &self.to
     }
 
+    // the address that is credited with the amount, which is the contract for an HTLC and
+    // the escrow for an escrow
+    pub fn payee(&self) -> Cow<'_, str> {
+        if let Some(htlc) = Htlc::of(self) {
+            return Cow::Owned(htlc.address());
+        }
+
+        match self.kind {
+            TransactionKind::Escrow { .. } => Cow::Owned(Escrow::address(&self.hash)),
+            _ => Cow::Borrowed(&self.to),
+        }
+    }
+
+    // the address the amount is taken from, which is the escrow for a release or refund and
+    // nobody for an issuance, the sender always pays the fee
+    pub fn payer(&self) -> Option<Cow<'_, str>> {
+        if let TransactionKind::Issue { .. } = self.kind {
+            return None;
+        }
+
+        match self.kind.escrow() {
+            Some(escrow) => Some(Cow::Owned(Escrow::address(escrow))),
+            None => self.from.as_deref().map(Cow::Borrowed),
+        }
+    }
+
+    // whether the transaction changes what the address holds, which is what a light client
+    // asks for proofs of
+    pub fn involves(&self, address: &str) -> bool {
+        self.from.as_deref() == Some(address)
+            || self.to == address
+            || self.payee() == address
+            || self.payer().is_some_and(|payer| payer == address)
+            || self.outputs.iter().any(|output| output.address == address)
+    }
+
+    // every address the transaction involves, which is what nodes index it by
+    pub fn addresses(&self) -> BTreeSet<String> {
+        let mut addresses: BTreeSet<_> = self
+            .outputs
+            .iter()
+            .map(|output| output.address.clone())
+            .collect();
+        addresses.extend(self.from.clone());
+        addresses.insert(self.to.clone());
+        addresses.insert(self.payee().into_owned());
+        addresses.extend(self.payer().map(Cow::into_owned));
+
+        addresses
+    }
+
     pub fn amount(&self) -> u64 {
         self.amount
     }
 
+    pub fn fee(&self) -> u64 {
+        self.fee
+    }
+
+    pub fn nonce(&self) -> u64 {
+        self.nonce
+    }
+
     pub fn hash(&self) -> Hash {
         self.hash
     }
@@ -96,36 +449,112 @@ impl Transaction {
         &self.kind
     }
 
-    fn hash_transaction(
-        from: &Option<String>,
-        to: &str,
-        amount: u64,
-        kind: &TransactionKind,
-    ) -> Hash {
-        let bytes = [
-            match from {
-                Some(f) => f.as_bytes(),
-                None => &[0],
-            },
-            to.as_bytes(),
-            &amount.to_be_bytes(),
-            kind.bytes(),
-        ]
-        .into_iter()
-        .flatten()
-        .copied()
-        .collect::<Vec<u8>>();
+    pub fn scheme(&self) -> SignatureScheme {
+        self.scheme
+    }
+
+    pub fn signiture(&self) -> &[u8] {
+        &self.signiture
+    }
+
+    pub fn lock(&self) -> Option<Lock> {
+        self.lock
+    }
+
+    // the asset the amount is in, none for the coin of the chain, an issuance is in the asset
+    // it creates
+    pub fn asset(&self) -> Option<AssetId> {
+        match self.kind {
+            TransactionKind::Issue { .. } => Some(self.hash),
+            _ => self.asset,
+        }
+    }
+
+    pub fn inputs(&self) -> &[OutPoint] {
+        &self.inputs
+    }
+
+    // the outputs the transaction creates on a chain that uses the UTXO model
+    pub fn outputs(&self) -> Cow<'_, [Output]> {
+        if !self.outputs.is_empty() {
+            return Cow::Borrowed(&self.outputs);
+        }
+
+        Cow::Owned(vec![Output {
+            address: self.to.clone(),
+            amount: self.amount,
+        }])
+    }
+
+    // whether the inputs or outputs were set, which only a chain in the UTXO model allows
+    pub fn is_utxo(&self) -> bool {
+        !self.inputs.is_empty() || !self.outputs.is_empty()
+    }
+
+    pub fn script(&self) -> Option<&Script> {
+        self.script.as_ref()
+    }
+
+    pub fn witness(&self) -> &[Vec<u8>] {
+        &self.witness
+    }
+
+    // the fields that are covered by the signitures
+    fn encode_body(&self, out: &mut Vec<u8>) {
+        self.from.as_deref().encode(out);
+        self.to.encode(out);
+        self.amount.encode(out);
+        self.fee.encode(out);
+        self.nonce.encode(out);
+        self.kind.tag().encode(out);
+        match &self.kind {
+            TransactionKind::Htlc { hash, timeout } => {
+                hash.encode(out);
+                timeout.encode(out);
+            }
+            TransactionKind::Escrow { arbiter, timeout } => {
+                arbiter.encode(out);
+                timeout.encode(out);
+            }
+            TransactionKind::Release { escrow, .. } | TransactionKind::Refund { escrow } => {
+                escrow.encode(out)
+            }
+            TransactionKind::Issue { name, decimals } => {
+                name.encode(out);
+                decimals.encode(out);
+            }
+            _ => (),
+        }
+        self.scheme.tag().encode(out);
+        self.lock.as_ref().encode(out);
+        self.asset.as_ref().encode(out);
+        encode_list(&self.inputs, out);
+        encode_list(&self.outputs, out);
+    }
+
+    fn hash_transaction(&self) -> Hash {
+        let mut bytes = Vec::new();
+        self.encode_body(&mut bytes);
 
         sha2::Sha256::digest(bytes).into()
     }
 
+    // the hash of the full encoding including the signitures, blocks commit to this so that
+    // a signiture can not be swapped out without changing the block
+    pub fn id(&self) -> Hash {
+        sha2::Sha256::digest(self.to_bytes()).into()
+    }
+
     pub fn sign_transaction(&mut self, private_key: &SigningKey) -> Result<(), TransactionError> {
         let Some(from) = self.from.as_ref() else {
-            return Err(TransactionError::NoFromSignError)
+            return Err(TransactionError::NoFromSignError);
         };
 
-        let public_key = VerifyingKey::from(private_key);
-        let public_key = serde_json::to_string(&public_key).unwrap();
+        if self.scheme != SignatureScheme::Ecdsa {
+            return Err(TransactionError::WrongScheme);
+        }
+
+        let public_key = keygen::address(&VerifyingKey::from(private_key));
 
         if &public_key != from {
             return Err(TransactionError::ForeignPubkey);
@@ -145,8 +574,7 @@ impl Transaction {
             return Err(TransactionError::NotLoan);
         }
 
-        let public_key = VerifyingKey::from(private_key);
-        let public_key = serde_json::to_string(&public_key).unwrap();
+        let public_key = keygen::address(&VerifyingKey::from(private_key));
 
         if self.to != public_key {
             return Err(TransactionError::ForeignPubkey);
@@ -158,17 +586,200 @@ impl Transaction {
         Ok(())
     }
 
+    // the seller countersigns a release by the buyer the same way a loan is countersigned
+    pub fn sign_release_transaction(
+        &mut self,
+        private_key: &SigningKey,
+    ) -> Result<(), TransactionError> {
+        let TransactionKind::Release { escrow, .. } = self.kind else {
+            return Err(TransactionError::NotRelease);
+        };
+
+        let public_key = keygen::address(&VerifyingKey::from(private_key));
+
+        if self.to != public_key {
+            return Err(TransactionError::ForeignPubkey);
+        }
+
+        let signiture: Signature = private_key.sign(&self.hash);
+        self.kind = TransactionKind::Release {
+            escrow,
+            signiture: Some(signiture.to_bytes().to_vec()),
+        };
+
+        Ok(())
+    }
+
+    pub fn sign_transaction_schnorr(
+        &mut self,
+        private_key: &schnorr::SigningKey,
+    ) -> Result<(), TransactionError> {
+        let Some(from) = self.from.as_ref() else {
+            return Err(TransactionError::NoFromSignError);
+        };
+
+        if self.scheme != SignatureScheme::Schnorr {
+            return Err(TransactionError::WrongScheme);
+        }
+
+        if &keygen::schnorr_address(private_key.verifying_key()) != from {
+            return Err(TransactionError::ForeignPubkey);
+        }
+
+        self.signiture = Self::sign_schnorr(private_key, &self.hash);
+
+        Ok(())
+    }
+
+    pub fn sign_loan_transaction_schnorr(
+        &mut self,
+        private_key: &schnorr::SigningKey,
+    ) -> Result<(), TransactionError> {
+        if !self.is_loan() {
+            return Err(TransactionError::NotLoan);
+        }
+
+        if self.to != keygen::schnorr_address(private_key.verifying_key()) {
+            return Err(TransactionError::ForeignPubkey);
+        }
+
+        self.kind = TransactionKind::Loan(Some(Self::sign_schnorr(private_key, &self.hash)));
+
+        Ok(())
+    }
+
+    pub fn sign_release_transaction_schnorr(
+        &mut self,
+        private_key: &schnorr::SigningKey,
+    ) -> Result<(), TransactionError> {
+        let TransactionKind::Release { escrow, .. } = self.kind else {
+            return Err(TransactionError::NotRelease);
+        };
+
+        if self.to != keygen::schnorr_address(private_key.verifying_key()) {
+            return Err(TransactionError::ForeignPubkey);
+        }
+
+        self.kind = TransactionKind::Release {
+            escrow,
+            signiture: Some(Self::sign_schnorr(private_key, &self.hash)),
+        };
+
+        Ok(())
+    }
+
+    // BIP340 signs the hash directly with fresh auxiliary randomness
+    fn sign_schnorr(private_key: &schnorr::SigningKey, hash: &Hash) -> Vec<u8> {
+        let aux_rand: [u8; 32] = rand::random();
+        private_key
+            .sign_raw(hash, &aux_rand)
+            .expect("Signing a 32 byte hash can not fail")
+            .to_bytes()
+            .to_vec()
+    }
+
     pub fn valid(&self) -> bool {
         let Some(from) = self.from.as_ref() else {
             return true;
         };
 
-        if self.signiture.is_empty() {
+        // coins sent to a script can be spent by whoever satisfies it
+        if script::is_script_address(from) {
+            return self.script.as_ref().is_some_and(|script| {
+                &script.address() == from && script.execute(self, &self.witness).is_ok()
+            });
+        }
+
+        // the declared scheme has to match the address so it can not be swapped
+        if SignatureScheme::of_address(from) != self.scheme {
             return false;
         }
 
-        let public_key: VerifyingKey = serde_json::from_str(from).unwrap();
-        let signiture = Signature::from_bytes(GenericArray::from_slice(&self.signiture)).unwrap();
-        public_key.verify(&self.hash, &signiture).is_ok()
+        Script::pay_to_address(from)
+            .execute(self, std::slice::from_ref(&self.signiture))
+            .is_ok()
+    }
+}
+
+impl Encode for Transaction {
+    fn encode(&self, out: &mut Vec<u8>) {
+        self.encode_body(out);
+        self.signiture.encode(out);
+        self.kind.counter_signiture().encode(out);
+        self.script.as_ref().encode(out);
+        encode_list(&self.witness, out);
+    }
+}
+
+impl Decode for Transaction {
+    fn decode(reader: &mut Reader) -> Result<Self, DecodeError> {
+        let from = reader.option(Reader::string)?;
+        let to = reader.string()?;
+        let amount = reader.u64()?;
+        let fee = reader.u64()?;
+        let nonce = reader.u64()?;
+        let kind = match reader.u8()? {
+            0 => TransactionKind::Normal,
+            1 => TransactionKind::Loan(None),
+            2 => TransactionKind::Repayment,
+            3 => TransactionKind::Stake,
+            4 => TransactionKind::AddAuthority,
+            5 => TransactionKind::RemoveAuthority,
+            6 => TransactionKind::Htlc {
+                hash: reader.hash()?,
+                timeout: reader.u64()?,
+            },
+            7 => TransactionKind::Escrow {
+                arbiter: reader.string()?,
+                timeout: reader.u64()?,
+            },
+            8 => TransactionKind::Release {
+                escrow: reader.hash()?,
+                signiture: None,
+            },
+            9 => TransactionKind::Refund {
+                escrow: reader.hash()?,
+            },
+            10 => TransactionKind::Issue {
+                name: reader.string()?,
+                decimals: reader.u8()?,
+            },
+            tag => return Err(DecodeError::InvalidTag(tag)),
+        };
+        let scheme = reader.u8()?;
+        let scheme = SignatureScheme::from_tag(scheme).ok_or(DecodeError::InvalidTag(scheme))?;
+        let lock = reader.option(Lock::decode)?;
+        let asset = reader.option(Reader::hash)?;
+        let inputs = reader.list()?;
+        let outputs = reader.list()?;
+        let signiture = reader.bytes()?;
+        let counter_signiture = reader.option(Reader::bytes)?;
+        let script = reader.option(Script::decode)?;
+        let witness = reader.list()?;
+
+        // only loans and releases are countersigned
+        let kind = match (kind, counter_signiture) {
+            (kind, None) => kind,
+            (TransactionKind::Loan(_), signiture) => TransactionKind::Loan(signiture),
+            (TransactionKind::Release { escrow, .. }, signiture) => {
+                TransactionKind::Release { escrow, signiture }
+            }
+            (kind, Some(_)) => return Err(DecodeError::InvalidTag(kind.tag())),
+        };
+
+        let mut transaction = Self::new(from, to, amount, kind)
+            .with_fee(fee)
+            .with_nonce(nonce)
+            .with_scheme(scheme);
+        transaction.lock = lock;
+        transaction.asset = asset;
+        transaction.inputs = inputs;
+        transaction.outputs = outputs;
+        transaction.hash = transaction.hash_transaction();
+        transaction.signiture = signiture;
+        transaction.script = script;
+        transaction.witness = witness;
+
+        Ok(transaction)
     }
 }
@@ -0,0 +1,168 @@
+use crate::{
+    block::Hash,
+    encoding::{Decode, DecodeError, Encode, Reader},
+};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+
+// An output of a transaction, identified by the hash of the transaction and its position
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct OutPoint {
+    pub transaction: Hash,
+    pub index: u32,
+}
+
+impl Encode for OutPoint {
+    fn encode(&self, out: &mut Vec<u8>) {
+        self.transaction.encode(out);
+        self.index.encode(out);
+    }
+}
+
+impl Decode for OutPoint {
+    fn decode(reader: &mut Reader) -> Result<Self, DecodeError> {
+        Ok(Self {
+            transaction: reader.hash()?,
+            index: reader.u32()?,
+        })
+    }
+}
+
+// Coins paid to an address that can be spent once as the input of a later transaction
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Output {
+    pub address: String,
+    pub amount: u64,
+}
+
+impl Encode for Output {
+    fn encode(&self, out: &mut Vec<u8>) {
+        self.address.encode(out);
+        self.amount.encode(out);
+    }
+}
+
+impl Decode for Output {
+    fn decode(reader: &mut Reader) -> Result<Self, DecodeError> {
+        Ok(Self {
+            address: reader.string()?,
+            amount: reader.u64()?,
+        })
+    }
+}
+
+// The outputs that have not been spent yet, indexed by the address they pay so the outputs of
+// a wallet can be found without going through the whole set
+#[derive(Debug, Clone, Default)]
+pub struct UtxoSet {
+    outputs: HashMap<OutPoint, Output>,
+    addresses: HashMap<String, BTreeSet<OutPoint>>,
+}
+
+impl UtxoSet {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.outputs.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.outputs.is_empty()
+    }
+
+    pub fn get(&self, outpoint: &OutPoint) -> Option<&Output> {
+        self.outputs.get(outpoint)
+    }
+
+    pub fn contains(&self, outpoint: &OutPoint) -> bool {
+        self.outputs.contains_key(outpoint)
+    }
+
+    // the unspent outputs paying an address
+    pub fn of<'a>(&'a self, address: &str) -> impl Iterator<Item = (OutPoint, &'a Output)> {
+        self.addresses
+            .get(address)
+            .into_iter()
+            .flatten()
+            .map(|outpoint| (*outpoint, &self.outputs[outpoint]))
+    }
+
+    pub fn balance_of(&self, address: &str) -> u64 {
+        self.of(address).map(|(_, output)| output.amount).sum()
+    }
+
+    pub fn insert(&mut self, outpoint: OutPoint, output: Output) {
+        self.addresses
+            .entry(output.address.clone())
+            .or_default()
+            .insert(outpoint);
+        self.outputs.insert(outpoint, output);
+    }
+
+    pub fn remove(&mut self, outpoint: &OutPoint) -> Option<Output> {
+        let output = self.outputs.remove(outpoint)?;
+        if let Some(outpoints) = self.addresses.get_mut(&output.address) {
+            outpoints.remove(outpoint);
+            if outpoints.is_empty() {
+                self.addresses.remove(&output.address);
+            }
+        }
+
+        Some(output)
+    }
+
+    // Picks unspent outputs of the address worth at least the target, skipping the ones in
+    // use by pending transactions. The largest are picked first so the transaction needs as
+    // few inputs as possible. Returns the outputs and what they are worth together.
+    pub fn select(
+        &self,
+        address: &str,
+        target: u64,
+        in_use: &HashSet<OutPoint>,
+    ) -> Option<(Vec<OutPoint>, u64)> {
+        let mut available: Vec<_> = self
+            .of(address)
+            .filter(|(outpoint, _)| !in_use.contains(outpoint))
+            .collect();
+        available.sort_by_key(|(outpoint, output)| (std::cmp::Reverse(output.amount), *outpoint));
+
+        let mut selected = Vec::new();
+        let mut total = 0;
+        for (outpoint, output) in available {
+            if total >= target && !selected.is_empty() {
+                break;
+            }
+
+            selected.push(outpoint);
+            total += output.amount;
+        }
+
+        (total >= target && !selected.is_empty()).then_some((selected, total))
+    }
+}
+
+// the outputs in the order of their outpoints so the same set always has the same encoding
+impl Encode for UtxoSet {
+    fn encode(&self, out: &mut Vec<u8>) {
+        let outputs: BTreeMap<_, _> = self.outputs.iter().collect();
+        (outputs.len() as u32).encode(out);
+        for (outpoint, output) in outputs {
+            outpoint.encode(out);
+            output.encode(out);
+        }
+    }
+}
+
+impl Decode for UtxoSet {
+    fn decode(reader: &mut Reader) -> Result<Self, DecodeError> {
+        let mut utxos = Self::new();
+        for (outpoint, output) in
+            reader.list_with(|reader| Ok((OutPoint::decode(reader)?, Output::decode(reader)?)))?
+        {
+            utxos.insert(outpoint, output);
+        }
+
+        Ok(utxos)
+    }
+}
@@ -0,0 +1,186 @@
+use crate::{
+    block::{Block, BlockError, Hash},
+    script,
+    signature::{self, PublicKey, SignatureScheme},
+    transaction::Transaction,
+};
+use rayon::prelude::*;
+use std::{
+    collections::{HashMap, HashSet},
+    sync::{Mutex, RwLock},
+};
+
+const SCHNORR_BATCH_SIZE: usize = 64; // how many Schnorr signitures each thread checks at once
+
+// Verifies the signitures of transactions across threads. The public key of every address
+// is only parsed once and transactions that were verified when they entered the mempool are
+// not verified again when they show up in a block.
+#[derive(Default)]
+pub struct SignatureVerifier {
+    keys: RwLock<HashMap<String, Option<PublicKey>>>, // none if the address is not a key
+    verified: Mutex<HashSet<Hash>>,                   // the ids of pending transactions
+}
+
+impl SignatureVerifier {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // the parsed key of an address, parsing it the first time it is seen
+    fn key(&self, address: &str) -> Option<PublicKey> {
+        if let Some(public_key) = self.keys.read().unwrap().get(address) {
+            return public_key.clone();
+        }
+
+        let public_key = PublicKey::parse(address);
+        self.keys
+            .write()
+            .unwrap()
+            .insert(address.to_owned(), public_key.clone());
+
+        public_key
+    }
+
+    fn is_verified(&self, transaction: &Transaction) -> bool {
+        self.verified.lock().unwrap().contains(&transaction.id())
+    }
+
+    // the same as Transaction::valid but with the cached key
+    fn check(&self, transaction: &Transaction) -> bool {
+        let Some(from) = transaction.from() else {
+            return true;
+        };
+
+        if script::is_script_address(from) {
+            return transaction.valid();
+        }
+
+        self.key(from).is_some_and(|public_key| {
+            public_key.scheme() == transaction.scheme()
+                && public_key.verify(&transaction.hash(), transaction.signiture())
+        })
+    }
+
+    // checks a transaction that is about to enter the mempool and remembers it if it is valid,
+    // the id covers the signitures so a changed transaction is checked again
+    pub fn verify_transaction(&self, transaction: &Transaction) -> bool {
+        if self.is_verified(transaction) {
+            return true;
+        }
+
+        if !self.check(transaction) {
+            return false;
+        }
+
+        self.verified.lock().unwrap().insert(transaction.id());
+        true
+    }
+
+    // forgets every verified transaction that is no longer pending
+    pub fn retain<'a>(&self, mempool: impl IntoIterator<Item = &'a Transaction>) {
+        let pending: HashSet<_> = mempool
+            .into_iter()
+            .map(|transaction| transaction.id())
+            .collect();
+        self.verified
+            .lock()
+            .unwrap()
+            .retain(|id| pending.contains(id));
+    }
+
+    // Finds the first transaction with an invalid signiture that was not already verified.
+    // ECDSA signitures and scripts are checked one per thread and Schnorr signitures in
+    // batches, a batch that fails is checked one by one to find the invalid transaction.
+    fn find_invalid<'a>(&self, transactions: &[&'a Transaction]) -> Option<&'a Transaction> {
+        let (scripts, signed): (Vec<_>, Vec<_>) = transactions
+            .iter()
+            .enumerate()
+            .filter(|(_, transaction)| !self.is_verified(transaction))
+            .partition(|(_, transaction)| {
+                transaction
+                    .from()
+                    .as_ref()
+                    .is_some_and(|from| script::is_script_address(from))
+            });
+
+        let invalid_script = scripts
+            .par_iter()
+            .find_first(|(_, transaction)| !transaction.valid())
+            .map(|(i, _)| *i);
+
+        let (schnorr, ecdsa): (Vec<_>, Vec<_>) = signed
+            .into_iter()
+            .filter_map(|(i, transaction)| {
+                Some((i, *transaction, self.key(transaction.from().as_ref()?)?))
+            })
+            .partition(|(_, transaction, public_key)| {
+                public_key.scheme() == SignatureScheme::Schnorr
+                    && transaction.scheme() == SignatureScheme::Schnorr
+            });
+
+        let invalid_ecdsa = ecdsa
+            .par_iter()
+            .find_first(|(_, transaction, _)| !self.check(transaction))
+            .map(|(i, _, _)| *i);
+
+        let invalid_schnorr = schnorr
+            .par_chunks(SCHNORR_BATCH_SIZE)
+            .filter(|chunk| {
+                let batch: Vec<_> = chunk
+                    .iter()
+                    .map(|(_, transaction, public_key)| {
+                        let PublicKey::Schnorr(public_key) = public_key else {
+                            unreachable!("Only schnorr keys are batched")
+                        };
+                        (public_key, transaction.hash(), transaction.signiture())
+                    })
+                    .collect();
+
+                !signature::verify_schnorr_batch(&batch)
+            })
+            .flat_map_iter(|chunk| chunk.iter())
+            .find_first(|(_, transaction, _)| !self.check(transaction))
+            .map(|(i, _, _)| *i);
+
+        // senders whose address is neither a key nor a script can never have signed
+        let invalid_address = transactions.iter().position(|transaction| {
+            transaction
+                .from()
+                .as_ref()
+                .is_some_and(|from| !script::is_script_address(from) && self.key(from).is_none())
+        });
+
+        [
+            invalid_script,
+            invalid_ecdsa,
+            invalid_schnorr,
+            invalid_address,
+        ]
+        .into_iter()
+        .flatten()
+        .min()
+        .map(|i| transactions[i])
+    }
+
+    // checks the signiture of every transaction in a block
+    pub fn verify_block(&self, block: &Block) -> Result<(), BlockError> {
+        let transactions: Vec<_> = block.transactions().iter().collect();
+        match self.find_invalid(&transactions) {
+            Some(transaction) => Err(BlockError::InvalidTransaction(transaction.hash())),
+            None => Ok(()),
+        }
+    }
+
+    // checks the signitures of many blocks at once, the Schnorr signitures of all the blocks
+    // are batched together
+    pub fn verify_blocks(&self, blocks: &[Block]) -> Result<(), BlockError> {
+        let transactions: Vec<_> = blocks
+            .iter()
+            .flat_map(|block| block.transactions())
+            .collect();
+        match self.find_invalid(&transactions) {
+            Some(transaction) => Err(BlockError::InvalidTransaction(transaction.hash())),
+            None => Ok(()),
+        }
+    }
+}
@@ -0,0 +1,57 @@
+// Moves blocks between two nodes of the same chain through a backup and checks a bad one
+// changes nothing
+
+mod common;
+
+use blockchain::{
+    backup::{Backup, BackupError},
+    block::Block,
+};
+use common::Node;
+
+#[test]
+fn backup_moves_blocks_to_a_peer() {
+    let mut node = Node::new();
+    for amount in [10, 20, 30] {
+        node.pay("bob", amount);
+    }
+
+    let backup = Backup::export(&node.chain, 1, 10).unwrap();
+    let backup = Backup::from_json(&backup.to_json().unwrap()).unwrap();
+    let mut peer = node.peer();
+    assert_eq!(backup.verify(&peer).unwrap(), 3);
+    assert_eq!(peer.blocks().len(), 1);
+
+    assert_eq!(backup.import(&mut peer).unwrap(), 3);
+    assert_eq!(peer.state().root(), node.chain.state().root());
+    assert_eq!(peer.balance_of("bob", None).unwrap(), 60);
+
+    // importing it again adds nothing
+    assert_eq!(backup.import(&mut peer).unwrap(), 0);
+}
+
+#[test]
+fn bad_block_leaves_the_chain_as_it_was() {
+    let mut node = Node::new();
+    for amount in [10, 20, 30] {
+        node.pay("bob", amount);
+    }
+
+    // the last block loses its payment so it no longer has its seal or state root
+    let mut backup = Backup::export(&node.chain, 1, 3).unwrap();
+    let last = backup.blocks.pop().unwrap();
+    let forged = Block::new(
+        last.timestamp(),
+        last.transactions()[..1].to_vec(),
+        last.prev_hash(),
+    );
+    backup.blocks.push(forged);
+
+    let mut peer = node.peer();
+    assert!(matches!(
+        backup.import(&mut peer),
+        Err(BackupError::InvalidBlock(3, _))
+    ));
+    assert_eq!(peer.blocks().len(), 1);
+    assert_eq!(peer.balance_of("bob", None).unwrap(), 0);
+}
@@ -0,0 +1,87 @@
+// The node the integration tests run against, each test file uses only some of it
+#![allow(dead_code)]
+
+use blockchain::{
+    block::Block,
+    blockchain::Blockchain,
+    chain_spec::ChainSpec,
+    clock::ManualClock,
+    keygen,
+    transaction::{Transaction, TransactionKind},
+};
+use k256::ecdsa::SigningKey;
+
+pub struct Node {
+    pub chain: Blockchain,
+    pub clock: ManualClock,
+    pub key: SigningKey,
+    pub address: String,
+}
+
+impl Node {
+    // a chain where the node starts with 5000 coins
+    pub fn new() -> Self {
+        let (key, public_key) = keygen::gen_key_pair();
+        let address = keygen::address(&public_key);
+        let clock = ManualClock::new(10_000);
+
+        Self {
+            chain: Blockchain::new(Self::spec(&address)).with_clock(clock.clone()),
+            clock,
+            key,
+            address,
+        }
+    }
+
+    pub fn spec(address: &str) -> ChainSpec {
+        let mut spec = ChainSpec {
+            genesis_timestamp: 1000,
+            difficulty: 1,
+            ..ChainSpec::default()
+        };
+        spec.allocations.insert(address.into(), 5000);
+
+        spec
+    }
+
+    // another node of the same chain that has only the genesis and shares the clock
+    pub fn peer(&self) -> Blockchain {
+        Blockchain::new(Self::spec(&self.address)).with_clock(self.clock.clone())
+    }
+
+    // a payment with a fee of 1 that is only added to the mempool
+    pub fn send(&mut self, to: &str, amount: u64) -> Transaction {
+        let mut transaction = Transaction::new(
+            Some(self.address.clone()),
+            to.into(),
+            amount,
+            TransactionKind::Normal,
+        )
+        .with_fee(1)
+        .with_nonce(self.chain.next_nonce(&self.address));
+        transaction.sign_transaction(&self.key).unwrap();
+        self.chain.add_transaction(transaction.clone()).unwrap();
+
+        transaction
+    }
+
+    // pays the amount with a fee of 1 and mines it
+    pub fn pay(&mut self, to: &str, amount: u64) -> Transaction {
+        let transaction = self.send(to, amount);
+        self.mine();
+
+        transaction
+    }
+
+    // mines the pending transactions to the miner
+    pub fn mine(&mut self) -> Block {
+        mine(&mut self.chain, &self.clock, "miner")
+    }
+}
+
+// mines a block on a chain of the node or of a peer after the time of a block has passed
+pub fn mine(chain: &mut Blockchain, clock: &ManualClock, miner: &str) -> Block {
+    clock.advance(10);
+    chain.mine_pending_transactions(miner).unwrap();
+    chain.latest_block().clone()
+}
@@ -0,0 +1,259 @@
+// Builds blocks by hand that break a consensus rule and checks that a node rejects them
+
+use blockchain::{
+    block::{Block, BlockError},
+    blockchain::{Blockchain, BlockchainError},
+    chain_spec::{ChainSpec, ChainSpecError, ConsensusSpec, SLOT_DURATION},
+    clock::ManualClock,
+    consensus::ConsensusError,
+    keygen,
+    monetary::{MonetaryPolicy, RewardSchedule},
+    transaction::{Transaction, TransactionKind},
+};
+use k256::ecdsa::SigningKey;
+use std::collections::BTreeMap;
+
+const DIFFICULTY: usize = 1;
+
+struct Node {
+    chain: Blockchain,
+    key: SigningKey,
+    address: String,
+}
+
+impl Node {
+    fn new() -> Self {
+        let (key, public_key) = keygen::gen_key_pair();
+        let address = keygen::address(&public_key);
+        let mut spec = ChainSpec {
+            genesis_timestamp: 1000,
+            difficulty: DIFFICULTY,
+            ..ChainSpec::default()
+        };
+        spec.allocations.insert(address.clone(), 5000);
+
+        Self {
+            chain: Blockchain::new(spec).with_clock(ManualClock::new(10_000)),
+            key,
+            address,
+        }
+    }
+
+    fn transaction(&self, to: &str, amount: u64, kind: TransactionKind) -> Transaction {
+        let mut transaction = Transaction::new(Some(self.address.clone()), to.into(), amount, kind)
+            .with_fee(1)
+            .with_nonce(self.chain.next_nonce(&self.address));
+        transaction.sign_transaction(&self.key).unwrap();
+
+        transaction
+    }
+
+    // a block after the latest one with the transactions after a coinbase that claims
+    // nothing, committing to the state they lead to but not sealed yet
+    fn unsealed(&self, timestamp: u64, transactions: Vec<Transaction>) -> Block {
+        let height = self.chain.blocks().len() as u64;
+        let coinbase =
+            Transaction::new(None, "miner".into(), 0, TransactionKind::Normal).with_nonce(height);
+        let transactions: Vec<_> = [coinbase].into_iter().chain(transactions).collect();
+        let block = Block::new(timestamp, transactions, self.chain.latest_block().hash());
+
+        let mut state = self.chain.state().clone();
+        state.apply_unchecked_block(&block);
+        block.with_state_root(state.root())
+    }
+
+    // the same block sealed by proof of work
+    fn block(&self, transactions: Vec<Transaction>) -> Block {
+        let height = self.chain.blocks().len() as u64;
+        let mut block = self.unsealed(10_000 + height, transactions);
+        block.mine(DIFFICULTY);
+
+        block
+    }
+}
+
+fn rejected(result: Result<(), BlockchainError>) -> BlockError {
+    match result {
+        Err(BlockchainError::InvalidBlock(error)) => error,
+        other => panic!("expected an invalid block, got {:?}", other),
+    }
+}
+
+#[test]
+fn stake_to_someone_else_is_rejected() {
+    let mut node = Node::new();
+    let stake = node.transaction("bob", 1000, TransactionKind::Stake);
+    let hash = stake.hash();
+    assert!(matches!(
+        node.chain.add_transaction(stake.clone()),
+        Err(BlockchainError::InvalidTransaction)
+    ));
+
+    let block = node.block(vec![stake]);
+    assert_eq!(
+        rejected(node.chain.import_block(block)),
+        BlockError::ForeignStake(hash)
+    );
+    assert_eq!(node.chain.state().stake_of(&node.address), 0);
+    assert_eq!(node.chain.balance_of("bob", None).unwrap(), 0);
+
+    // staking for yourself locks the coins without paying anyone
+    let stake = node.transaction(&node.address, 1000, TransactionKind::Stake);
+    node.chain.import_block(node.block(vec![stake])).unwrap();
+    assert_eq!(node.chain.state().stake_of(&node.address), 1000);
+    assert_eq!(node.chain.balance_of(&node.address, None).unwrap(), 3999);
+    assert_eq!(
+        node.chain.circulating_supply(),
+        node.chain.total_supply() - 1000
+    );
+}
+
+#[test]
+fn difficulty_above_hash_length_is_refused() {
+    let spec = ChainSpec {
+        difficulty: 33,
+        ..ChainSpec::default()
+    };
+    let path = std::env::temp_dir().join(format!("spec-{}.json", std::process::id()));
+    spec.save(&path).unwrap();
+    let loaded = ChainSpec::load(&path);
+    std::fs::remove_file(&path).unwrap();
+    assert!(matches!(loaded, Err(ChainSpecError::InvalidDifficulty(33))));
+
+    // a chain built from such a spec in code refuses to mine instead of panicking
+    let mut chain = Blockchain::new(spec);
+    assert!(matches!(
+        chain.mine_pending_transactions("miner"),
+        Err(BlockchainError::Consensus(
+            ConsensusError::InvalidDifficulty
+        ))
+    ));
+}
+
+#[test]
+fn large_rewards_follow_the_schedule() {
+    let decay = RewardSchedule::Decay {
+        interval: 1,
+        percent: 10,
+    };
+    let policy = MonetaryPolicy::new(u64::MAX, decay, None);
+    assert_eq!(
+        policy.scheduled_reward(1),
+        (u64::MAX as u128 * 9 / 10) as u64
+    );
+
+    // far out the reward is gone, or never shrinks, without stepping through every interval
+    let slowest = RewardSchedule::Decay {
+        interval: 1,
+        percent: 1,
+    };
+    let policy = MonetaryPolicy::new(u64::MAX, slowest, None);
+    assert_eq!(policy.scheduled_reward(u64::MAX), 0);
+    let none = RewardSchedule::Decay {
+        interval: 1,
+        percent: 0,
+    };
+    let policy = MonetaryPolicy::new(u64::MAX, none, None);
+    assert_eq!(policy.scheduled_reward(u64::MAX), u64::MAX);
+
+    let halving = RewardSchedule::Halving { interval: 1 };
+    let policy = MonetaryPolicy::new(u64::MAX, halving, None);
+    assert_eq!(policy.scheduled_reward(1 << 32), 0);
+}
+
+#[test]
+fn block_without_work_is_rejected() {
+    let mut node = Node::new();
+
+    // a block whose hash happens to start with a zero byte would be sealed already
+    let block = (10_000..)
+        .map(|timestamp| node.unsealed(timestamp, Vec::new()))
+        .find(|block| block.hash()[0] != 0)
+        .unwrap();
+    assert_eq!(
+        rejected(node.chain.import_block(block)),
+        BlockError::InvalidSeal
+    );
+    assert_eq!(node.chain.blocks().len(), 1);
+}
+
+#[test]
+fn second_coinbase_is_rejected() {
+    let mut node = Node::new();
+    let coinbase = Transaction::new(None, "thief".into(), 1000, TransactionKind::Normal);
+    let hash = coinbase.hash();
+
+    let block = node.block(vec![coinbase]);
+    assert_eq!(
+        rejected(node.chain.import_block(block)),
+        BlockError::ExtraCoinbase(hash)
+    );
+    assert_eq!(node.chain.balance_of("thief", None).unwrap(), 0);
+}
+
+#[test]
+fn overdraft_is_rejected() {
+    let mut node = Node::new();
+    let payment = node.transaction("bob", 5000, TransactionKind::Normal);
+    let hash = payment.hash();
+
+    // the amount and the fee are one more than the sender has
+    let block = node.block(vec![payment]);
+    assert_eq!(
+        rejected(node.chain.import_block(block)),
+        BlockError::Overdraft(hash)
+    );
+    assert_eq!(node.chain.balance_of(&node.address, None).unwrap(), 5000);
+}
+
+#[test]
+fn wrong_state_root_is_rejected() {
+    let mut node = Node::new();
+    let payment = node.transaction("bob", 10, TransactionKind::Normal);
+    let block = node.block(vec![payment.clone()]);
+    let mut forged = Block::new(
+        block.timestamp(),
+        block.transactions().to_vec(),
+        block.prev_hash(),
+    )
+    .with_state_root([7; 32]);
+    forged.mine(DIFFICULTY);
+
+    assert_eq!(
+        rejected(node.chain.import_block(forged)),
+        BlockError::InvalidStateRoot
+    );
+    node.chain.import_block(block).unwrap();
+    assert!(node.chain.transaction(&payment.hash()).unwrap().is_some());
+}
+
+#[test]
+fn slot_duration_is_kept_by_checkpoints() {
+    let spec = |slot_duration| ChainSpec {
+        consensus: ConsensusSpec::ProofOfStake {
+            stakes: BTreeMap::from([("alice".to_owned(), 10)]),
+            slot_duration,
+        },
+        ..ChainSpec::default()
+    };
+    assert_ne!(
+        spec(60).genesis().hash(),
+        spec(SLOT_DURATION).genesis().hash()
+    );
+
+    // the engine rebuilt from a checkpoint has the slots of the spec
+    let genesis = spec(60).genesis();
+    let checkpoint = spec(60).consensus().checkpoint(&[genesis]);
+    assert!(matches!(
+        checkpoint,
+        ConsensusSpec::ProofOfStake {
+            slot_duration: 60,
+            ..
+        }
+    ));
+
+    // a spec written before the duration could be set has the default one
+    let old: ConsensusSpec =
+        serde_json::from_str(r#"{"proof_of_stake": {"stakes": {"alice": 10}}}"#).unwrap();
+    assert_eq!(old, spec(SLOT_DURATION).consensus);
+}
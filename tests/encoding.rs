@@ -0,0 +1,179 @@
+// Checks the test vectors in docs/encoding.md
+
+use blockchain::{
+    account::{Account, AccountProof},
+    block::{Block, Header},
+    chain_spec::Ledger,
+    encoding::{Decode, Encode},
+    keygen,
+    light::TransactionProof,
+    state::ChainState,
+    transaction::{Transaction, TransactionKind},
+};
+use k256::ecdsa::{SigningKey, VerifyingKey};
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+fn coinbase() -> Transaction {
+    Transaction::new(None, "miner".into(), 1000, TransactionKind::Normal).with_nonce(1)
+}
+
+fn repayment() -> Transaction {
+    Transaction::new(
+        Some("alice".into()),
+        "bob".into(),
+        250,
+        TransactionKind::Repayment,
+    )
+    .with_fee(5)
+    .with_nonce(3)
+}
+
+#[test]
+fn coinbase_vector() {
+    let transaction = coinbase();
+    assert_eq!(
+        hex(&transaction.to_bytes()),
+        "00000000056d696e657200000000000003e80000000000000000000000000000000100000000000000000000000000000000000000000000"
+    );
+    assert_eq!(
+        hex(&transaction.hash()),
+        "686cae8481e70923d10be0d178687dd13c4e947b447b55c99f68f388ae1be444"
+    );
+    assert_eq!(
+        hex(&transaction.id()),
+        "83e10a55816af9a4670818b50e5aea1d0d56c89f47a6f7608b42e056d5fe4aa6"
+    );
+}
+
+#[test]
+fn repayment_vector() {
+    let transaction = repayment();
+    assert_eq!(
+        hex(&transaction.to_bytes()),
+        "0100000005616c69636500000003626f6200000000000000fa0000000000000005000000000000000302000000000000000000000000000000000000000000"
+    );
+    assert_eq!(
+        hex(&transaction.hash()),
+        "e3b7b296a383d0fc02ed1d8a569cee1646c5faa83021e6d0a8c5e68a949f549c"
+    );
+    assert_eq!(
+        hex(&transaction.id()),
+        "6baf1e19f90f93f5361b789ca7ea054a36340f7ae499a7f1abe0cb831d4ae2a4"
+    );
+}
+
+#[test]
+fn loan_vector_covers_counter_signiture() {
+    let lender = SigningKey::from_bytes(&[1u8; 32].into()).unwrap();
+    let borrower = SigningKey::from_bytes(&[2u8; 32].into()).unwrap();
+
+    let mut loan = Transaction::new(
+        Some(keygen::address(&VerifyingKey::from(&lender))),
+        keygen::address(&VerifyingKey::from(&borrower)),
+        300,
+        TransactionKind::Loan(None),
+    )
+    .with_fee(1);
+    loan.sign_transaction(&lender).unwrap();
+    assert_eq!(
+        hex(&loan.id()),
+        "7ad3e42c346d361bddba0355c5c7e20d841139907e19217ddc7311410d97dec4"
+    );
+
+    loan.sign_loan_transaction(&borrower).unwrap();
+    assert_eq!(
+        hex(&loan.hash()),
+        "44e165c265dd2ef4035274716ab821e06d58680fc1f625ff78e3c4ed9606f3b2"
+    );
+    assert_eq!(
+        hex(&loan.id()),
+        "4f540265ed4eaf4df974f826c848f9f7c8ce01acf6a4a85d5a790e2f1f65d55a"
+    );
+
+    let decoded = Transaction::from_bytes(&loan.to_bytes()).unwrap();
+    assert_eq!(decoded.id(), loan.id());
+    assert!(decoded.loan_signed());
+}
+
+#[test]
+fn block_vector() {
+    let block = Block::new(1680307200, vec![coinbase(), repayment()], [0; 32]);
+    assert_eq!(
+        hex(&block.transactions_root()),
+        "38b1c1f8b4c3a0b045d30c005b075f74f1c109782ff72c4b2801d257a5e08ca5"
+    );
+    assert_eq!(
+        hex(&block.hash()),
+        "20b39809cd14b992ebf0f435a4147f0e93e5af0b31894682b4c2bdae6466f344"
+    );
+    assert_eq!(
+        hex(&block.to_bytes()),
+        "00000000000000000000000000000000000000000000000000000000000000000000000064277400000000000000000038b1c1f8b4c3a0b045d30c005b075f74f1c109782ff72c4b2801d257a5e08ca5000000000000000000000000000000000000000000000000000000000000000000000000000000000200000000056d696e657200000000000003e800000000000000000000000000000001000000000000000000000000000000000000000000000100000005616c69636500000003626f6200000000000000fa0000000000000005000000000000000302000000000000000000000000000000000000000000"
+    );
+
+    let decoded = Block::from_bytes(&block.to_bytes()).unwrap();
+    assert_eq!(decoded.hash(), block.hash());
+}
+
+#[test]
+fn proof_vector() {
+    let block = Block::new(1680307200, vec![coinbase(), repayment()], [0; 32]);
+    let header = block.header();
+    assert_eq!(
+        hex(&header.to_bytes()),
+        "00000000000000000000000000000000000000000000000000000000000000000000000064277400000000000000000038b1c1f8b4c3a0b045d30c005b075f74f1c109782ff72c4b2801d257a5e08ca500000000000000000000000000000000000000000000000000000000000000000000000000"
+    );
+    assert_eq!(header.hash(), block.hash());
+    assert_eq!(Header::from_bytes(&header.to_bytes()).unwrap(), header);
+
+    let proof = TransactionProof {
+        height: 1,
+        index: 1,
+        transaction: repayment(),
+        siblings: block.proof(1).unwrap(),
+    };
+    assert_eq!(
+        hex(&proof.to_bytes()),
+        "0000000000000001000000010100000005616c69636500000003626f6200000000000000fa000000000000000500000000000000030200000000000000000000000000000000000000000000000001004d44cfa26d4688ef082e1acde1e6838e4edccd7f9088330b6b31f7518f2e4ea2"
+    );
+    assert!(proof.verify(&header));
+
+    let decoded = TransactionProof::from_bytes(&proof.to_bytes()).unwrap();
+    assert!(decoded.verify(&header));
+    assert!(!TransactionProof {
+        transaction: coinbase(),
+        ..decoded
+    }
+    .verify(&header));
+}
+
+#[test]
+fn account_proof_vector() {
+    let block = Block::new(1680307200, vec![coinbase(), repayment()], [0; 32]);
+    let state = ChainState::genesis(&block, Ledger::Account);
+    assert_eq!(
+        hex(&state.root()),
+        "342aeacf9bd76674a30c1415e509cc1ce72951e08e23a1a7900fa03452d57f54"
+    );
+
+    let proof = state.prove("bob");
+    assert_eq!(hex(&proof.to_bytes()), "01000000010000000000000000fa000000000000000000000000000000000000000000000005000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000035ab7175f50223fa10e42a8a3d655857cd51652c82a5dcdc29a7f23efc7cebad0181b637d8fcd2c6da6359e6963113a1170de795e4b725b84d1e0b4cfd9ec58ce9684fa88746aaf230e8068442a7244a31a42d7485ea05f6fcccdb12e22cfdbf0d3f80b3b45dc9629046f01e6d58618504f87fe0bbdf0d49486fa4f77cf111ce37");
+    assert!(proof.verify(&state.root(), "bob"));
+    assert!(!proof.verify(&state.root(), "alice"));
+
+    let decoded = AccountProof::from_bytes(&proof.to_bytes()).unwrap();
+    assert_eq!(decoded, proof);
+
+    // alice has no account, the proof ends in the leaf of another address or an empty subtree
+    let proof = state.prove("alice");
+    assert_eq!(proof.account, None);
+    assert!(proof.verify(&state.root(), "alice"));
+    assert!(!AccountProof {
+        account: Some(Account::default()),
+        ..proof
+    }
+    .verify(&state.root(), "alice"));
+}
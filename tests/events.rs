@@ -0,0 +1,76 @@
+// Subscribes to the events of a node, switches it to a longer branch and checks what the
+// subscribers are told
+
+mod common;
+
+use blockchain::{
+    block::Block,
+    events::{Event, Filter},
+    explorer,
+};
+use common::{mine, Node};
+use std::sync::{Arc, Mutex};
+
+#[test]
+fn filters_pick_events_by_kind_and_address() {
+    let mut node = Node::new();
+    let events = node.chain.subscribe();
+    node.send("bob", 10);
+    node.send("carol", 10);
+    node.mine();
+    let events: Vec<_> = events.try_iter().collect();
+
+    let kinds: Vec<_> = events.iter().map(Event::kind).collect();
+    assert_eq!(kinds, ["new_transaction", "new_transaction", "new_block"]);
+
+    // the block involves both receivers, each payment only one
+    let bob = Filter::default().with_address("bob");
+    assert_eq!(events.iter().filter(|event| bob.matches(event)).count(), 2);
+    let blocks = Filter::default()
+        .with_kind("new_block")
+        .with_address("carol");
+    assert_eq!(
+        events.iter().filter(|event| blocks.matches(event)).count(),
+        1
+    );
+
+    let filter = explorer::event_filter("kind=new_block,reorg&address=bob");
+    assert_eq!(filter.kinds.len(), 2);
+    assert_eq!(filter.addresses.len(), 1);
+    assert!(!filter.matches(&events[0]));
+    assert!(filter.matches(&events[2]));
+}
+
+#[test]
+fn reorg_is_told_to_callbacks_and_subscribers() {
+    let mut node = Node::new();
+    let payment = node.send("bob", 10);
+    let replaced = node.mine();
+
+    // a peer that never saw the payment mines a longer branch
+    let mut peer = node.peer();
+    let branch: Vec<_> = (0..2)
+        .map(|_| mine(&mut peer, &node.clock, "other"))
+        .collect();
+
+    let kinds = Arc::new(Mutex::new(Vec::new()));
+    let seen = kinds.clone();
+    node.chain
+        .on_event(move |event| seen.lock().unwrap().push(event.kind()));
+    let events = node.chain.subscribe();
+    node.chain.reorg(0, branch.clone()).unwrap();
+
+    assert_eq!(*kinds.lock().unwrap(), ["reorg", "new_block", "new_block"]);
+    let reorg = events.try_iter().next().unwrap();
+    assert!(matches!(
+        &reorg,
+        Event::Reorg { height, removed, added }
+            if *height == 0 && *removed == [replaced.hash()]
+                && *added == branch.iter().map(Block::hash).collect::<Vec<_>>()
+    ));
+
+    // a reorg may change any address so every address filter lets it through
+    assert!(Filter::default().with_address("bob").matches(&reorg));
+    assert!(node.chain.mempool().contains(&payment.hash()));
+    assert_eq!(node.chain.balance_of("bob", None).unwrap(), 0);
+}
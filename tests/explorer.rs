@@ -0,0 +1,56 @@
+// Asks the explorer of a node for its pages and checks what the JSON API answers
+
+mod common;
+
+use blockchain::{encoding::hex, explorer};
+use common::Node;
+use serde_json::Value;
+
+// the JSON the API answers a url with, which has to have the status
+fn get(node: &Node, url: &str, status: u16) -> Value {
+    let response = explorer::handle(&node.chain, url);
+    assert_eq!(response.status, status, "{}", response.body);
+    assert_eq!(response.content_type, "application/json");
+
+    serde_json::from_str(&response.body).unwrap()
+}
+
+#[test]
+fn api_shows_blocks_and_transactions() {
+    let mut node = Node::new();
+    let mined = node.send("bob smith", 100);
+    node.mine();
+    let pending = node.send("carol", 10);
+
+    let status = get(&node, "/api", 200);
+    assert_eq!(status["height"], 1);
+    assert_eq!(status["latest"], hex(&node.chain.latest_block().hash()));
+    assert_eq!(status["pending"], 1);
+
+    // a block by its height and by its hash
+    let block = get(&node, "/api/blocks/1", 200);
+    assert_eq!(block["transactions"][1]["hash"], hex(&mined.hash()));
+    let url = format!("/api/blocks/{}", block["hash"].as_str().unwrap());
+    assert_eq!(get(&node, &url, 200), block);
+
+    let url = format!("/api/transactions/{}", hex(&mined.hash()));
+    let transaction = get(&node, &url, 200);
+    assert_eq!(transaction["location"]["height"], 1);
+    assert_eq!(transaction["location"]["index"], 1);
+    let url = format!("/api/transactions/{}", hex(&pending.hash()));
+    assert_eq!(get(&node, &url, 200)["location"], Value::Null);
+
+    // the address is percent encoded in the path
+    let address = get(&node, "/api/addresses/bob%20smith", 200);
+    assert_eq!(address["address"], "bob smith");
+    assert_eq!(address["balances"][0]["amount"], 100);
+}
+
+#[test]
+fn api_refuses_what_it_can_not_find() {
+    let node = Node::new();
+    get(&node, "/api/blocks/7", 404);
+    get(&node, &format!("/api/transactions/{}", hex(&[1; 32])), 404);
+    get(&node, "/api/transactions/not-a-hash", 400);
+    get(&node, "/api/nowhere", 404);
+}
@@ -0,0 +1,128 @@
+// Syncs a light client from a full node and checks it only accepts what the headers prove
+
+mod common;
+
+use blockchain::light::{LightClient, LightClientError, Request, Response};
+use common::Node;
+
+// a light client of the node's chain that watches the address
+fn light_client(node: &Node, address: &str) -> LightClient {
+    let mut client = LightClient::new(Node::spec(&node.address)).with_clock(node.clock.clone());
+    client.watch(address);
+
+    client
+}
+
+#[test]
+fn light_client_follows_watched_address() {
+    let mut node = Node::new();
+    node.pay("bob", 100);
+    node.pay("carol", 50);
+    node.pay("bob", 20);
+
+    let mut client = light_client(&node, "bob");
+    client.sync(&node.chain).unwrap();
+    assert_eq!(client.headers().len(), 4);
+    assert_eq!(client.latest_header(), &node.chain.latest_block().header());
+
+    // only the payments to bob were downloaded
+    assert_eq!(client.transactions().count(), 2);
+    assert_eq!(client.balance_of("bob", None), 120);
+    let (height, account) = client.account("bob").unwrap();
+    assert_eq!(height, 3);
+    assert_eq!(account.unwrap().balances[&None], 120);
+}
+
+#[test]
+fn forged_proofs_are_refused() {
+    let mut node = Node::new();
+    node.pay("bob", 100);
+    let mut client = light_client(&node, "bob");
+    client.sync(&node.chain).unwrap();
+
+    let request = Request::Proofs {
+        address: "bob".into(),
+        start: 1,
+        end: 2,
+    };
+    let Ok(Response::Proofs(mut proofs)) = node.chain.serve(&request) else {
+        panic!("expected proofs");
+    };
+    // the payment with its fee left out is not the transaction the root commits to
+    let forged = proofs[0].transaction.clone().with_fee(0);
+    proofs[0].transaction = forged.clone();
+    assert_eq!(
+        client.add_proofs(&proofs),
+        Err(LightClientError::InvalidProof(forged.hash()))
+    );
+
+    // an account proof only holds against the state root it was made for
+    let Ok(Response::Account { proof, .. }) = node.chain.serve(&Request::Account {
+        address: "bob".into(),
+    }) else {
+        panic!("expected an account");
+    };
+    assert_eq!(
+        client.add_account("bob".into(), 0, &proof),
+        Err(LightClientError::InvalidAccountProof("bob".into()))
+    );
+
+    // a header that does not follow on the latest one is not synced
+    let mut header = node.chain.latest_block().header();
+    header.timestamp += 1;
+    assert!(matches!(
+        client.add_headers(&[header]),
+        Err(LightClientError::InvalidHeader(2, _))
+    ));
+    assert_eq!(client.balance_of("bob", None), 100);
+}
+
+#[test]
+fn proof_sent_again_under_another_index_counts_once() {
+    let mut node = Node::new();
+    node.pay("bob", 100);
+    let mut client = light_client(&node, "bob");
+    client.sync(&node.chain).unwrap();
+
+    let request = Request::Proofs {
+        address: "bob".into(),
+        start: 1,
+        end: 2,
+    };
+    let Ok(Response::Proofs(proofs)) = node.chain.serve(&request) else {
+        panic!("expected proofs");
+    };
+    // the index is not part of what the proof checks against the root
+    let mut replayed = proofs[0].clone();
+    replayed.index += 5;
+    client.add_proofs(&[replayed]).unwrap();
+
+    assert_eq!(client.transactions().count(), 1);
+    assert_eq!(client.balance_of("bob", None), 100);
+}
+
+#[test]
+fn account_proofs_hold_only_against_their_own_root() {
+    let mut node = Node::new();
+    node.pay("bob", 100);
+    let before = node.chain.prove_account("bob");
+    let root = node.chain.latest_block().state_root();
+    node.pay("bob", 20);
+
+    // a proof shows what an address has or that it has nothing
+    let latest = node.chain.latest_block().state_root();
+    let proof = node.chain.prove_account("bob");
+    assert!(proof.verify(&latest, "bob"));
+    assert_eq!(proof.account.as_ref().unwrap().balances[&None], 120);
+    let missing = node.chain.prove_account("dave");
+    assert_eq!(missing.account, None);
+    assert!(missing.verify(&latest, "dave"));
+
+    // an old proof does not hold after the account changed, nor one with a changed balance
+    assert!(before.verify(&root, "bob"));
+    assert!(!before.verify(&latest, "bob"));
+    let mut forged = proof.clone();
+    forged.account.as_mut().unwrap().balances.insert(None, 1000);
+    assert!(!forged.verify(&latest, "bob"));
+    assert!(!proof.verify(&latest, "carol"));
+}
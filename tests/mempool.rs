@@ -0,0 +1,211 @@
+// Checks which pending transactions the mempool of a node keeps, evicts and refuses
+
+use blockchain::{
+    blockchain::{Blockchain, BlockchainError},
+    chain_spec::ChainSpec,
+    clock::ManualClock,
+    encoding::Encode,
+    events::Event,
+    keygen,
+    mempool::{Mempool, MempoolError, MAX_BYTES},
+    transaction::{Transaction, TransactionKind},
+};
+use k256::ecdsa::SigningKey;
+
+struct User {
+    key: SigningKey,
+    address: String,
+}
+
+impl User {
+    fn new() -> Self {
+        let (key, public_key) = keygen::gen_key_pair();
+        let address = keygen::address(&public_key);
+        Self { key, address }
+    }
+
+    fn pay(&self, chain: &Blockchain, amount: u64, fee: u64) -> Transaction {
+        let nonce = chain.next_nonce(&self.address);
+        self.sign(
+            Transaction::new(
+                Some(self.address.clone()),
+                "bob".into(),
+                amount,
+                TransactionKind::Normal,
+            )
+            .with_fee(fee)
+            .with_nonce(nonce),
+        )
+    }
+
+    fn sign(&self, mut transaction: Transaction) -> Transaction {
+        transaction.sign_transaction(&self.key).unwrap();
+        transaction
+    }
+}
+
+// a chain where every user starts with 5000 coins and the mempool has the given limits
+fn chain(users: &[User], mempool: Mempool) -> (Blockchain, ManualClock) {
+    let mut spec = ChainSpec {
+        genesis_timestamp: 1000,
+        difficulty: 1,
+        ..ChainSpec::default()
+    };
+    for user in users {
+        spec.allocations.insert(user.address.clone(), 5000);
+    }
+    let clock = ManualClock::new(10_000);
+    let chain = Blockchain::new(spec)
+        .with_clock(clock.clone())
+        .with_mempool(mempool);
+
+    (chain, clock)
+}
+
+#[test]
+fn full_pool_evicts_lowest_fee() {
+    let users = [User::new(), User::new(), User::new(), User::new()];
+    let (mut chain, _) = chain(&users, Mempool::new(2, MAX_BYTES, 3600));
+
+    let cheap = users[0].pay(&chain, 10, 1);
+    chain.add_transaction(cheap.clone()).unwrap();
+    chain.add_transaction(users[1].pay(&chain, 10, 3)).unwrap();
+    chain.add_transaction(users[2].pay(&chain, 10, 2)).unwrap();
+    assert_eq!(chain.mempool().len(), 2);
+    assert!(!chain.mempool().contains(&cheap.hash()));
+
+    // nothing pays less than the cheapest pending one
+    assert!(matches!(
+        chain.add_transaction(users[3].pay(&chain, 10, 2)),
+        Err(BlockchainError::Mempool(MempoolError::Full))
+    ));
+}
+
+#[test]
+fn pending_transactions_expire() {
+    let users = [User::new()];
+    let (mut chain, clock) = chain(&users, Mempool::new(10, MAX_BYTES, 100));
+
+    let first = users[0].pay(&chain, 10, 1);
+    chain.add_transaction(first.clone()).unwrap();
+    clock.advance(101);
+    chain.add_transaction(users[0].pay(&chain, 10, 1)).unwrap();
+    assert!(!chain.mempool().contains(&first.hash()));
+    assert_eq!(chain.mempool().len(), 1);
+}
+
+#[test]
+fn overflowing_amount_is_refused() {
+    let users = [User::new()];
+    let (mut chain, _) = chain(&users, Mempool::default());
+
+    let transaction = users[0].pay(&chain, u64::MAX, 1);
+    assert!(matches!(
+        chain.add_transaction(transaction),
+        Err(BlockchainError::BalanceTooSmall)
+    ));
+}
+
+#[test]
+fn used_nonce_is_refused() {
+    let users = [User::new()];
+    let (mut chain, clock) = chain(&users, Mempool::default());
+
+    let mined = users[0].pay(&chain, 10, 1);
+    chain.add_transaction(mined.clone()).unwrap();
+    clock.advance(10);
+    chain.mine_pending_transactions("miner").unwrap();
+
+    let again = users[0].sign(mined.with_fee(2));
+    assert!(matches!(
+        chain.add_transaction(again),
+        Err(BlockchainError::StaleNonce)
+    ));
+    assert!(chain.mempool().is_empty());
+}
+
+#[test]
+fn higher_fee_replaces_pending_transaction() {
+    let users = [User::new()];
+    let (mut chain, clock) = chain(&users, Mempool::default());
+    let events = chain.subscribe();
+
+    let original = users[0].pay(&chain, 10, 1);
+    chain.add_transaction(original.clone()).unwrap();
+
+    // the same nonce has to pay strictly more
+    let same_fee = users[0].sign(
+        Transaction::new(
+            Some(users[0].address.clone()),
+            "carol".into(),
+            10,
+            TransactionKind::Normal,
+        )
+        .with_fee(1)
+        .with_nonce(0),
+    );
+    assert!(matches!(
+        chain.add_transaction(same_fee),
+        Err(BlockchainError::Mempool(MempoolError::Underpriced))
+    ));
+
+    // a cancellation pays nothing to the sender with a higher fee
+    let cancellation = users[0].sign(Transaction::cancellation(users[0].address.clone(), 0, 2));
+    chain.add_transaction(cancellation.clone()).unwrap();
+    assert!(!chain.mempool().contains(&original.hash()));
+    assert!(events.try_iter().any(|event| matches!(
+        event,
+        Event::TransactionReplaced { replaced, by }
+            if replaced.hash() == original.hash() && by == cancellation.hash()
+    )));
+
+    clock.advance(10);
+    chain.mine_pending_transactions("miner").unwrap();
+    assert!(chain.transaction(&original.hash()).unwrap().is_none());
+    assert!(chain.transaction(&cancellation.hash()).unwrap().is_some());
+    assert_eq!(chain.balance_of(&users[0].address, None).unwrap(), 4998);
+}
+
+#[test]
+fn transaction_that_grows_keeps_the_pool_in_budget() {
+    let [alice, bob] = [User::new(), User::new()];
+    let unsigned = |user: &User, fee| {
+        Transaction::new(
+            Some(user.address.clone()),
+            "carol".into(),
+            10,
+            TransactionKind::Normal,
+        )
+        .with_fee(fee)
+    };
+    let (cheap, dear) = (unsigned(&alice, 1), unsigned(&bob, 5));
+    let budget = cheap.to_bytes().len() + dear.to_bytes().len();
+    let mut mempool = Mempool::new(10, budget, 3600);
+    mempool.insert(cheap.clone(), 0).unwrap();
+    mempool.insert(dear.clone(), 0).unwrap();
+
+    // the signature makes the transaction larger so the cheaper one has to go
+    let (signed, evicted) = mempool
+        .update(&dear.hash(), |transaction| {
+            transaction.sign_transaction(&bob.key)
+        })
+        .unwrap()
+        .unwrap();
+    signed.unwrap();
+    assert_eq!(evicted.len(), 1);
+    assert_eq!(evicted[0].hash(), cheap.hash());
+    assert!(mempool.bytes() <= budget);
+
+    // nothing pays less than the cheap one so signing it does not fit and changes nothing
+    let mut mempool = Mempool::new(10, budget, 3600);
+    mempool.insert(cheap.clone(), 0).unwrap();
+    mempool.insert(dear.clone(), 0).unwrap();
+    assert!(matches!(
+        mempool.update(&cheap.hash(), |transaction| {
+            transaction.sign_transaction(&alice.key)
+        }),
+        Some(Err(MempoolError::Full))
+    ));
+    assert!(mempool.get(&cheap.hash()).unwrap().signiture().is_empty());
+    assert_eq!(mempool.bytes(), budget);
+}
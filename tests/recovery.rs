@@ -0,0 +1,334 @@
+// Crashes a node at every write of a block and checks it comes back with the chain and the
+// mempool agreeing
+
+use blockchain::{
+    block::{Block, BlockError, Hash, Header},
+    blockchain::{Blockchain, BlockchainError},
+    chain_spec::ChainSpec,
+    clock::{Clock, ManualClock, SystemClock},
+    keygen,
+    snapshot::Snapshot,
+    storage::{recover, Location, MemoryStorage, Storage, StorageError},
+    transaction::{Transaction, TransactionKind},
+};
+use k256::ecdsa::SigningKey;
+use std::sync::{Arc, Mutex, MutexGuard};
+
+// A storage kept in memory that stops writing from a given write on, like a node that was
+// killed. The write it stops at is only half done if it is the log.
+#[derive(Default)]
+struct Disk {
+    storage: MemoryStorage,
+    writes: usize,
+    crash_at: Option<usize>,
+}
+
+#[derive(Clone, Default)]
+struct Crashing(Arc<Mutex<Disk>>);
+
+impl Crashing {
+    fn disk(&self) -> MutexGuard<'_, Disk> {
+        self.0.lock().unwrap()
+    }
+
+    // counts the writes from now on and fails the one at the step and every one after it
+    fn crash_at(&self, step: usize) {
+        let mut disk = self.disk();
+        disk.writes = 0;
+        disk.crash_at = Some(step);
+    }
+
+    // the same storage after the node was started again
+    fn restart(&self) -> Self {
+        self.disk().crash_at = None;
+        self.clone()
+    }
+
+    fn writes(&self) -> usize {
+        self.disk().writes
+    }
+
+    fn write(
+        &mut self,
+        torn: impl FnOnce(&mut MemoryStorage),
+        write: impl FnOnce(&mut MemoryStorage) -> Result<(), StorageError>,
+    ) -> Result<(), StorageError> {
+        let mut disk = self.disk();
+        let step = disk.writes;
+        disk.writes += 1;
+        match disk.crash_at {
+            Some(crash_at) if step == crash_at => {
+                torn(&mut disk.storage);
+                Err(StorageError::Backend("crashed".into()))
+            }
+            Some(crash_at) if step > crash_at => Err(StorageError::Backend("crashed".into())),
+            _ => write(&mut disk.storage),
+        }
+    }
+}
+
+impl Storage for Crashing {
+    fn put_block(&mut self, height: u64, block: &Block) -> Result<(), StorageError> {
+        self.write(|_| (), |storage| storage.put_block(height, block))
+    }
+
+    fn put_snapshot(&mut self, snapshot: &Snapshot) -> Result<(), StorageError> {
+        self.write(|_| (), |storage| storage.put_snapshot(snapshot))
+    }
+
+    fn put_mempool(&mut self, transactions: &[Transaction]) -> Result<(), StorageError> {
+        self.write(|_| (), |storage| storage.put_mempool(transactions))
+    }
+
+    fn put_log(&mut self, log: Option<&[u8]>) -> Result<(), StorageError> {
+        let torn = log.map(|log| &log[..log.len() / 2]);
+        self.write(
+            |storage| {
+                if let Some(torn) = torn {
+                    storage.put_log(Some(torn)).unwrap();
+                }
+            },
+            |storage| storage.put_log(log),
+        )
+    }
+
+    fn count(&self) -> Result<u64, StorageError> {
+        self.disk().storage.count()
+    }
+
+    fn header(&self, height: u64) -> Result<Option<Header>, StorageError> {
+        self.disk().storage.header(height)
+    }
+
+    fn block(&self, height: u64) -> Result<Option<Block>, StorageError> {
+        self.disk().storage.block(height)
+    }
+
+    fn height_of(&self, hash: &Hash) -> Result<Option<u64>, StorageError> {
+        self.disk().storage.height_of(hash)
+    }
+
+    fn location_of(&self, transaction: &Hash) -> Result<Option<Location>, StorageError> {
+        self.disk().storage.location_of(transaction)
+    }
+
+    fn history(&self, address: &str) -> Result<Vec<Location>, StorageError> {
+        self.disk().storage.history(address)
+    }
+
+    fn snapshot(&self) -> Result<Option<Snapshot>, StorageError> {
+        self.disk().storage.snapshot()
+    }
+
+    fn mempool(&self) -> Result<Vec<Transaction>, StorageError> {
+        self.disk().storage.mempool()
+    }
+
+    fn log(&self) -> Result<Option<Vec<u8>>, StorageError> {
+        self.disk().storage.log()
+    }
+}
+
+struct Node {
+    spec: ChainSpec,
+    clock: ManualClock,
+    pruning: Option<u64>,
+    key: SigningKey,
+    address: String,
+}
+
+impl Node {
+    fn new(pruning: Option<u64>) -> Self {
+        let (key, public_key) = keygen::gen_key_pair();
+        let address = keygen::address(&public_key);
+        let mut spec = ChainSpec {
+            genesis_timestamp: 1000,
+            difficulty: 1,
+            ..ChainSpec::default()
+        };
+        spec.allocations.insert(address.clone(), 5000);
+
+        Self {
+            spec,
+            clock: ManualClock::new(10_000),
+            pruning,
+            key,
+            address,
+        }
+    }
+
+    fn open(&self, disk: &Crashing) -> Blockchain {
+        let chain =
+            Blockchain::open_with_clock(self.spec.clone(), disk.restart(), self.clock.clone())
+                .unwrap();
+        match self.pruning {
+            Some(depth) => chain.with_pruning(depth),
+            None => chain,
+        }
+    }
+
+    fn transaction(&self, chain: &Blockchain) -> Transaction {
+        let nonce = chain.next_nonce(&self.address);
+        let mut transaction = Transaction::new(
+            Some(self.address.clone()),
+            "bob".into(),
+            10,
+            TransactionKind::Normal,
+        )
+        .with_fee(1)
+        .with_nonce(nonce);
+        transaction.sign_transaction(&self.key).unwrap();
+
+        transaction
+    }
+
+    fn mine(&self, chain: &mut Blockchain) -> Result<Hash, String> {
+        let transaction = self.transaction(chain);
+        let hash = transaction.hash();
+        chain.add_transaction(transaction).unwrap();
+        self.clock.advance(10);
+        chain
+            .mine_pending_transactions("miner")
+            .map_err(|e| format!("{:?}", e))?;
+
+        Ok(hash)
+    }
+}
+
+// a node that mined some blocks, with a pending transaction and a crash set for the next block
+fn crashed(node: &Node, disk: &Crashing, step: usize) -> (Blockchain, Hash) {
+    let mut chain = node.open(disk);
+    for _ in 0..4 {
+        node.mine(&mut chain).unwrap();
+    }
+
+    let transaction = node.transaction(&chain);
+    let hash = transaction.hash();
+    chain.add_transaction(transaction).unwrap();
+    disk.crash_at(step);
+    node.clock.advance(10);
+    let mined = chain.mine_pending_transactions("miner");
+    assert_eq!(
+        mined.is_ok(),
+        step > 0,
+        "only a batch that was logged counts"
+    );
+
+    (chain, hash)
+}
+
+// checks that a node that was started again has the block that was mined at the crash and
+// the mempool without its transactions, or neither if the crash came before it was logged
+fn check_recovered(node: &Node, disk: &Crashing, crashed: &Blockchain, hash: &Hash, mined: bool) {
+    let chain = node.open(disk);
+    assert!(chain.valid());
+    assert_eq!(disk.log().unwrap(), None);
+    assert_eq!(disk.count().unwrap(), chain.blocks().len() as u64);
+    assert_eq!(chain.mempool().contains(hash), !mined);
+    assert_eq!(chain.transaction(hash).unwrap().is_some(), mined);
+    if mined {
+        assert_eq!(chain.blocks().len(), 6);
+        assert_eq!(chain.state().root(), crashed.state().root());
+        assert_eq!(chain.pruned_height(), crashed.pruned_height());
+        let hashes = |mempool: Vec<&Transaction>| -> Vec<Hash> {
+            mempool.into_iter().map(Transaction::hash).collect()
+        };
+        assert_eq!(
+            hashes(chain.mempool().iter().collect()),
+            hashes(crashed.mempool().iter().collect())
+        );
+    } else {
+        assert_eq!(chain.blocks().len(), 5);
+    }
+}
+
+// crashes at every write of a block until one goes through without a crash
+fn crash_at_every_step(pruning: Option<u64>) -> usize {
+    for step in 0.. {
+        let node = Node::new(pruning);
+        let disk = Crashing::default();
+        let (chain, hash) = crashed(&node, &disk, step);
+        if disk.writes() <= step {
+            return step;
+        }
+
+        check_recovered(&node, &disk, &chain, &hash, step > 0);
+    }
+
+    unreachable!()
+}
+
+#[test]
+fn crash_while_writing_block() {
+    // the log, the block, the mempool and clearing the log
+    assert_eq!(crash_at_every_step(None), 4);
+}
+
+#[test]
+fn crash_while_writing_pruned_block() {
+    // the snapshot and the header of the block that was pruned come with the block
+    assert_eq!(crash_at_every_step(Some(2)), 6);
+}
+
+#[test]
+fn crash_while_recovering() {
+    for step in 0..3 {
+        let node = Node::new(None);
+        let disk = Crashing::default();
+        let (chain, hash) = crashed(&node, &disk, 1);
+
+        // the block, the mempool and clearing the log are written again
+        disk.crash_at(step);
+        assert!(Blockchain::open(node.spec.clone(), disk.clone()).is_err());
+        assert!(disk.log().unwrap().is_some());
+
+        check_recovered(&node, &disk, &chain, &hash, true);
+    }
+}
+
+#[test]
+fn crash_while_adding_transaction() {
+    let node = Node::new(None);
+    let disk = Crashing::default();
+    let mut chain = node.open(&disk);
+
+    let transaction = node.transaction(&chain);
+    let hash = transaction.hash();
+    disk.crash_at(0);
+    assert!(chain.add_transaction(transaction.clone()).is_err());
+    assert!(!chain.mempool().contains(&hash));
+
+    // once it is logged the transaction is kept even if the mempool was not written
+    let mut chain = node.open(&disk);
+    assert!(!chain.mempool().contains(&hash));
+    disk.crash_at(1);
+    chain.add_transaction(transaction).unwrap();
+    assert!(node.open(&disk).mempool().contains(&hash));
+}
+
+#[test]
+fn torn_log_is_dropped() {
+    let mut storage = MemoryStorage::new();
+    storage.put_log(Some(&[7; 40])).unwrap();
+    assert!(recover(&mut storage).unwrap().is_none());
+    assert_eq!(storage.log().unwrap(), None);
+    assert_eq!(storage.count().unwrap(), 0);
+}
+
+#[test]
+fn reopens_with_its_own_clock() {
+    // a simulation running a month ahead of the machine
+    let node = Node::new(None);
+    node.clock.set(SystemClock.now() + 30 * 24 * 60 * 60);
+    let disk = Crashing::default();
+    let mut chain = node.open(&disk);
+    for _ in 0..3 {
+        node.mine(&mut chain).unwrap();
+    }
+
+    assert_eq!(node.open(&disk).blocks().len(), 4);
+    assert!(matches!(
+        Blockchain::open(node.spec.clone(), disk.restart()),
+        Err(BlockchainError::InvalidBlock(BlockError::TimestampInFuture))
+    ));
+}
@@ -0,0 +1,147 @@
+// Fast syncs a node from the snapshot of another one and checks it carries on with the same
+// state and rules
+
+mod common;
+
+use blockchain::{
+    backup::Backup,
+    block::{Block, BlockError, Header},
+    blockchain::{Blockchain, BlockchainError},
+    encoding::{Decode, Encode},
+    snapshot::Snapshot,
+    storage::{DiskStorage, MemoryStorage, Storage},
+    transaction::{Transaction, TransactionKind},
+};
+use common::Node;
+
+// a node whose chain takes a snapshot every other block
+fn node() -> Node {
+    let mut node = Node::new();
+    node.chain = node.peer().with_snapshots(2);
+
+    node
+}
+
+// a node of the same chain started from the latest snapshot as it arrives over the wire
+fn fast_sync(node: &Node) -> Blockchain {
+    let snapshot = node.chain.latest_snapshot().unwrap();
+    let snapshot = Snapshot::from_bytes(&snapshot.to_bytes()).unwrap();
+    let headers: Vec<Header> = node.chain.blocks()[..=snapshot.height as usize]
+        .iter()
+        .map(Block::header)
+        .collect();
+
+    Blockchain::from_snapshot(Node::spec(&node.address), snapshot, &headers)
+        .unwrap()
+        .with_clock(node.clock.clone())
+}
+
+#[test]
+fn snapshot_does_not_grow_with_history() {
+    let mut node = node();
+    node.pay("bob", 10);
+    node.pay("bob", 10);
+    let size = node.chain.latest_snapshot().unwrap().to_bytes().len();
+
+    // the same addresses with more transactions between them take the same space
+    node.pay("bob", 10);
+    node.pay("bob", 10);
+    let snapshot = node.chain.latest_snapshot().unwrap();
+    assert_eq!(snapshot.height, 4);
+    assert_eq!(snapshot.to_bytes().len(), size);
+}
+
+#[test]
+fn fast_synced_node_refuses_replayed_transactions() {
+    let mut node = node();
+    let payment = node.pay("bob", 100);
+    node.pay("carol", 50);
+
+    let mut synced = fast_sync(&node);
+    assert_eq!(synced.state().root(), node.chain.state().root());
+    assert_eq!(synced.balance_of("bob", None).unwrap(), 100);
+
+    // the payment is no longer in any block the synced node has, its nonce is still used
+    assert!(matches!(
+        synced.add_transaction(payment.clone()),
+        Err(BlockchainError::StaleNonce)
+    ));
+    let coinbase = Transaction::new(None, "miner".into(), 0, TransactionKind::Normal).with_nonce(3);
+    let mut block = Block::new(
+        10_100,
+        vec![coinbase, payment.clone()],
+        synced.latest_block().hash(),
+    );
+    block.mine(1);
+    assert!(matches!(
+        synced.import_block(block),
+        Err(BlockchainError::InvalidBlock(BlockError::InvalidNonce(hash))) if hash == payment.hash()
+    ));
+
+    // blocks mined after the snapshot carry over
+    node.pay("bob", 20);
+    synced
+        .import_block(node.chain.latest_block().clone())
+        .unwrap();
+    assert_eq!(synced.state().root(), node.chain.state().root());
+    assert_eq!(synced.balance_of("bob", None).unwrap(), 120);
+}
+
+#[test]
+fn old_blocks_are_read_from_the_storage() {
+    let mut node = Node::new();
+    node.chain = node.peer().with_memory(2);
+    let payments: Vec<_> = (0..5).map(|_| node.pay("bob", 10)).collect();
+
+    // only the latest two blocks keep their transactions in memory
+    let kept: Vec<_> = node.chain.blocks().iter().map(|b| !b.is_pruned()).collect();
+    assert_eq!(kept, [false, false, false, false, true, true]);
+    let block = node.chain.block(1).unwrap().unwrap();
+    assert_eq!(block.transactions()[1].hash(), payments[0].hash());
+    assert!(node.chain.valid());
+
+    let backup = Backup::export(&node.chain, 1, 5).unwrap();
+    assert!(backup
+        .blocks()
+        .all(|(_, block)| block.transactions().len() == 2));
+    assert_eq!(node.chain.balance_of("bob", None).unwrap(), 50);
+
+    // blocks out of the window can not be replaced, even by a longer branch
+    let branch = backup.blocks.clone();
+    assert!(matches!(
+        node.chain.reorg(1, branch),
+        Err(BlockchainError::InvalidReorg)
+    ));
+}
+
+// the location of a payment the node mined at height 2 once a peer's branch has it at height 1
+fn moved_location(storage: impl Storage + 'static) -> (u64, u32) {
+    let mut node = Node::new();
+    node.chain =
+        Blockchain::open_with_clock(Node::spec(&node.address), storage, node.clock.clone())
+            .unwrap();
+    node.mine();
+    let payment = node.pay("bob", 10);
+
+    // a peer mines the payment in its first block and gets ahead
+    let mut peer = node.peer();
+    peer.add_transaction(payment.clone()).unwrap();
+    for _ in 0..3 {
+        common::mine(&mut peer, &node.clock, "other");
+    }
+    node.chain.reorg(0, peer.blocks()[1..].to_vec()).unwrap();
+
+    let (location, _) = node.chain.transaction(&payment.hash()).unwrap().unwrap();
+    (location.height, location.index)
+}
+
+#[test]
+fn transactions_moved_to_a_lower_height_by_a_reorg_are_found() {
+    // the block the payment was in before is replaced after the payment was indexed at height 1
+    assert_eq!(moved_location(MemoryStorage::new()), (1, 1));
+
+    let path = std::env::temp_dir().join(format!("moved-{}.redb", std::process::id()));
+    let location = moved_location(DiskStorage::open(&path).unwrap());
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(location, (1, 1));
+}
@@ -0,0 +1,383 @@
+// Mines the transaction kinds that lock, move or create coins and checks where the coins end up
+
+use blockchain::{
+    blockchain::{Blockchain, BlockchainError},
+    chain_spec::{ChainSpec, Ledger},
+    clock::ManualClock,
+    escrow::Escrow,
+    htlc::Htlc,
+    keygen,
+    script::Script,
+    transaction::{Lock, Transaction, TransactionError, TransactionKind},
+};
+use k256::ecdsa::SigningKey;
+use sha2::{Digest, Sha256};
+
+struct User {
+    key: SigningKey,
+    address: String,
+}
+
+impl User {
+    fn new() -> Self {
+        let (key, public_key) = keygen::gen_key_pair();
+        let address = keygen::address(&public_key);
+        Self { key, address }
+    }
+
+    fn sign(&self, mut transaction: Transaction) -> Transaction {
+        transaction.sign_transaction(&self.key).unwrap();
+        transaction
+    }
+}
+
+struct Node {
+    chain: Blockchain,
+    clock: ManualClock,
+}
+
+impl Node {
+    // a chain where every user starts with 5000 coins
+    fn new(users: &[&User]) -> Self {
+        Self::with_ledger(users, Ledger::Account)
+    }
+
+    fn with_ledger(users: &[&User], ledger: Ledger) -> Self {
+        let mut spec = ChainSpec {
+            genesis_timestamp: 1000,
+            difficulty: 1,
+            ledger,
+            ..ChainSpec::default()
+        };
+        for user in users {
+            spec.allocations.insert(user.address.clone(), 5000);
+        }
+        let clock = ManualClock::new(10_000);
+
+        Self {
+            chain: Blockchain::new(spec).with_clock(clock.clone()),
+            clock,
+        }
+    }
+
+    // a transaction from the user with the next nonce, signed
+    fn send(&self, user: &User, to: &str, amount: u64, kind: TransactionKind) -> Transaction {
+        self.sign(
+            user,
+            Transaction::new(Some(user.address.clone()), to.into(), amount, kind).with_fee(1),
+        )
+    }
+
+    // signs a transaction of the user with the next nonce
+    fn sign(&self, user: &User, transaction: Transaction) -> Transaction {
+        user.sign(transaction.with_nonce(self.chain.next_nonce(&user.address)))
+    }
+
+    fn mine(&mut self) {
+        self.clock.advance(10);
+        self.chain.mine_pending_transactions("miner").unwrap();
+    }
+
+    fn height(&self) -> u64 {
+        self.chain.blocks().len() as u64 - 1
+    }
+
+    fn mined(&self, transaction: &Transaction) -> bool {
+        self.chain
+            .transaction(&transaction.hash())
+            .unwrap()
+            .is_some()
+    }
+
+    fn balance(&self, address: &str) -> u64 {
+        self.chain.balance_of(address, None).unwrap()
+    }
+}
+
+#[test]
+fn locked_transactions_wait_until_mature() {
+    let alice = User::new();
+    let mut node = Node::new(&[&alice]);
+
+    let payment = Transaction::new(
+        Some(alice.address.clone()),
+        "bob".into(),
+        10,
+        TransactionKind::Normal,
+    )
+    .with_fee(1)
+    .with_lock(Lock::Height(3));
+    let by_height = node.sign(&alice, payment);
+    node.chain.add_transaction(by_height.clone()).unwrap();
+
+    // the next payment is locked until a time instead
+    let payment = Transaction::new(
+        Some(alice.address.clone()),
+        "bob".into(),
+        10,
+        TransactionKind::Normal,
+    )
+    .with_fee(1)
+    .with_lock(Lock::Time(10_045));
+    let by_time = node.sign(&alice, payment);
+    node.chain.add_transaction(by_time.clone()).unwrap();
+
+    for _ in 0..2 {
+        node.mine();
+        assert!(!node.mined(&by_height));
+        assert!(node.chain.mempool().contains(&by_height.hash()));
+    }
+
+    node.mine();
+    assert_eq!(node.height(), 3);
+    assert!(node.mined(&by_height));
+    assert!(!node.mined(&by_time));
+
+    // the fifth block is the first one with a timestamp past the time lock
+    node.mine();
+    assert!(!node.mined(&by_time));
+    node.mine();
+    assert!(node.mined(&by_time));
+    assert_eq!(node.balance("bob"), 20);
+}
+
+#[test]
+fn multisig_needs_enough_signitures() {
+    let [alice, bob, carol] = [User::new(), User::new(), User::new()];
+    let mut node = Node::new(&[&alice]);
+    let script = Script::multisig(2, &[&alice.address, &bob.address, &carol.address]);
+    let vault = script.address();
+
+    let deposit = node.send(&alice, &vault, 100, TransactionKind::Normal);
+    node.chain.add_transaction(deposit).unwrap();
+    node.mine();
+    assert_eq!(node.balance(&vault), 100);
+
+    let withdrawal = Transaction::new(
+        Some(vault.clone()),
+        "dave".into(),
+        90,
+        TransactionKind::Normal,
+    )
+    .with_fee(1)
+    .with_nonce(node.chain.next_nonce(&vault))
+    .with_script(script);
+
+    // one signiture is not enough
+    let mut spend = withdrawal.clone();
+    spend.set_witness(vec![spend.witness_signiture(&bob.key)]);
+    assert!(matches!(
+        node.chain.add_transaction(spend),
+        Err(BlockchainError::InvalidTransaction)
+    ));
+
+    // two in the order of their addresses are
+    let mut spend = withdrawal;
+    spend.set_witness(vec![
+        spend.witness_signiture(&alice.key),
+        spend.witness_signiture(&carol.key),
+    ]);
+    node.chain.add_transaction(spend.clone()).unwrap();
+    node.mine();
+    assert!(node.mined(&spend));
+    assert_eq!(node.balance(&vault), 9);
+    assert_eq!(node.balance("dave"), 90);
+}
+
+#[test]
+fn htlc_is_claimed_with_the_secret_or_refunded_after_the_timeout() {
+    let [alice, bob] = [User::new(), User::new()];
+    let mut node = Node::new(&[&alice]);
+    let secret = b"the secret".to_vec();
+    let hash = Sha256::digest(&secret).into();
+
+    let [claimed, refunded] = [(100, 10), (200, 4)].map(|(amount, timeout)| {
+        let kind = TransactionKind::Htlc { hash, timeout };
+        let transaction = node.send(&alice, &bob.address, amount, kind);
+        node.chain.add_transaction(transaction.clone()).unwrap();
+        Htlc::of(&transaction).unwrap()
+    });
+    node.mine();
+    assert_eq!(node.balance(&claimed.address()), 100);
+
+    // the recipient needs the secret
+    let nonce = node.chain.next_nonce(&claimed.address());
+    let wrong = claimed.claim(b"a guess", 100, nonce, &bob.key);
+    assert!(matches!(
+        node.chain.add_transaction(wrong),
+        Err(BlockchainError::InvalidTransaction)
+    ));
+    let claim = claimed.claim(&secret, 100, nonce, &bob.key);
+    node.chain.add_transaction(claim.clone()).unwrap();
+
+    // the sender waits for the timeout
+    let nonce = node.chain.next_nonce(&refunded.address());
+    let refund = refunded.refund(200, nonce, &alice.key);
+    node.chain.add_transaction(refund.clone()).unwrap();
+    node.mine();
+    assert_eq!(node.balance(&bob.address), 100);
+    assert_eq!(claimed.secret(&claim), Some(secret));
+    assert!(!node.mined(&refund));
+
+    while node.height() < 4 {
+        node.mine();
+    }
+    assert!(node.mined(&refund));
+    assert_eq!(node.balance(&refunded.address()), 0);
+    assert_eq!(node.balance(&alice.address), 5000 - 100 - 2);
+}
+
+#[test]
+fn escrow_is_released_with_the_seller_or_refunded_after_the_timeout() {
+    let [buyer, seller, arbiter] = [User::new(), User::new(), User::new()];
+    let mut node = Node::new(&[&buyer]);
+
+    let [released, refunded] = [(100, 10), (200, 4)].map(|(amount, timeout)| {
+        let kind = TransactionKind::Escrow {
+            arbiter: arbiter.address.clone(),
+            timeout,
+        };
+        let transaction = node.send(&buyer, &seller.address, amount, kind);
+        node.chain.add_transaction(transaction.clone()).unwrap();
+        Escrow::of(&transaction).unwrap()
+    });
+    node.mine();
+    assert_eq!(node.balance(&Escrow::address(&released.hash)), 100);
+
+    // a release waits for the countersigniture of the seller and a refund for the timeout
+    let release = node.sign(&buyer, released.release().with_fee(1));
+    node.chain.add_transaction(release.clone()).unwrap();
+    let refund = node.sign(&buyer, refunded.refund().with_fee(1));
+    node.chain.add_transaction(refund.clone()).unwrap();
+    node.mine();
+    assert!(!node.mined(&release));
+
+    node.chain
+        .sign_release(&seller.key, release.hash())
+        .unwrap();
+    node.mine();
+    assert!(node.mined(&release));
+    assert!(!node.mined(&refund));
+    assert_eq!(node.balance(&seller.address), 100);
+    assert!(node
+        .chain
+        .escrows_of(&seller.address)
+        .iter()
+        .all(|escrow| escrow.hash != released.hash));
+
+    node.mine();
+    assert_eq!(node.height(), 4);
+    assert!(node.mined(&refund));
+    assert_eq!(node.balance(&Escrow::address(&refunded.hash)), 0);
+    assert_eq!(node.balance(&buyer.address), 5000 - 100 - 4);
+}
+
+#[test]
+fn issued_assets_are_paid_apart_from_the_coin() {
+    let [alice, bob] = [User::new(), User::new()];
+    let mut node = Node::new(&[&alice]);
+
+    let issuance = Transaction::issuance(alice.address.clone(), "GOLD".into(), 1000, 2);
+    let issuance = node.sign(&alice, issuance.with_fee(1));
+    let gold = issuance.hash();
+    node.chain.add_transaction(issuance).unwrap();
+    node.mine();
+    assert_eq!(
+        node.chain.balance_of(&alice.address, Some(gold)).unwrap(),
+        1000
+    );
+
+    let payment = Transaction::new(
+        Some(alice.address.clone()),
+        bob.address.clone(),
+        300,
+        TransactionKind::Normal,
+    )
+    .with_fee(1)
+    .with_asset(gold);
+    let too_much = node.sign(
+        &alice,
+        Transaction::new(
+            Some(alice.address.clone()),
+            bob.address.clone(),
+            1001,
+            TransactionKind::Normal,
+        )
+        .with_fee(1)
+        .with_asset(gold),
+    );
+    assert!(matches!(
+        node.chain.add_transaction(too_much),
+        Err(BlockchainError::BalanceTooSmall)
+    ));
+    node.chain
+        .add_transaction(node.sign(&alice, payment))
+        .unwrap();
+    node.mine();
+
+    // the asset moved and the fees were paid in the coin
+    assert_eq!(
+        node.chain.balance_of(&bob.address, Some(gold)).unwrap(),
+        300
+    );
+    assert_eq!(
+        node.chain.balance_of(&alice.address, Some(gold)).unwrap(),
+        700
+    );
+    assert_eq!(node.balance(&alice.address), 4998);
+    assert_eq!(node.balance(&bob.address), 0);
+}
+
+#[test]
+fn spent_outputs_can_not_be_spent_again() {
+    let [alice, bob] = [User::new(), User::new()];
+    let mut node = Node::with_ledger(&[&alice], Ledger::Utxo);
+
+    let (inputs, total) = node.chain.select_coins(&alice.address, 101).unwrap();
+    let spend = |node: &Node, to: &str| {
+        let transaction = Transaction::spend(
+            alice.address.clone(),
+            to.into(),
+            100,
+            1,
+            inputs.clone(),
+            total,
+        )
+        .unwrap();
+        node.sign(&alice, transaction)
+    };
+    let payment = spend(&node, &bob.address);
+    node.chain.add_transaction(payment.clone()).unwrap();
+
+    // an amount and fee that do not add up leave no change to pay back
+    assert!(matches!(
+        Transaction::spend(
+            alice.address.clone(),
+            bob.address.clone(),
+            u64::MAX,
+            1,
+            inputs.clone(),
+            total
+        ),
+        Err(TransactionError::Overflow)
+    ));
+
+    // another pending transaction can not spend the same outputs
+    assert!(matches!(
+        node.chain.add_transaction(spend(&node, "carol")),
+        Err(BlockchainError::DoubleSpend)
+    ));
+    node.mine();
+    assert!(node.mined(&payment));
+
+    // and neither can a later one once they are spent on the chain
+    assert!(matches!(
+        node.chain.add_transaction(spend(&node, "carol")),
+        Err(BlockchainError::DoubleSpend)
+    ));
+    let outputs = node.chain.utxos_of(&bob.address).unwrap();
+    assert_eq!(outputs.len(), 1);
+    assert_eq!(outputs[0].1.amount, 100);
+    assert_eq!(node.balance(&alice.address), 5000 - 101);
+    assert_eq!(node.balance("carol"), 0);
+}